@@ -1,23 +1,57 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 use std::slice::{Iter, IterMut};
-use std::ops::{Index, IndexMut};
-use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, TagBuf};
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, CharacterReference, RawSource, RawTags, Span, TagBuf};
 
 // ————————————————————————————————————————————————————————————————————————————
 // DATA MODEL — XML NODES
 // ————————————————————————————————————————————————————————————————————————————
 
-
+/// A node of the tree.
+///
+/// # Serialization
+///
+/// With the `serde` feature, nodes serialize to a compact shape (shown as JSON)
+/// that round-trips everything the tree holds:
+///
+/// - text is a string, `"hello"`, or, with a span, CDATA, lossless source or
+///   character references, `{"text": "hello", "span": […], "cdata": true,
+///   "raw": …, "references": […]}`;
+/// - an element is an array, `["div", {"class": "x"}, …children]`. The tag is
+///   an object, `{"name": "div", "namespace": …, "span": […], "raw": …,
+///   "placeholders": […], "template_contents": true}`, if it has any of those.
+///   The namespace is left out when it is that of the parent element (HTML at
+///   the top level), and `null` for an element without one. Attributes are in
+///   order; a value that has a namespace, span or character references is an
+///   object, `{"value": "x", "namespace": …, "span": […], "references": […]}`;
+/// - any other node is an object with a single key: `{"fragment": […nodes]}`,
+///   `{"comment": "…"}`, `{"doctype": {"name": "html", "public_id": "",
+///   "system_id": ""}}`, `{"processing_instruction": {"target": …, "data": …}}`
+///   or `{"placeholder": {"open": "{{", "expression": " x ", "close": "}}"}}`.
+///
+/// A [`Fragment`] is an array of nodes and a span is `[start offset, line,
+/// column, end offset, line, column]`; [`Node::clear_spans`] leaves them out.
+/// Lossless source text keeps the fingerprint it is checked against before
+/// reuse.
+///
+/// Each element nests one level deeper, and `serde_json` refuses input nested
+/// more than 128 levels by default. Deeper trees need
+/// `Deserializer::disable_recursion_limit` (the `unbounded_depth` feature of
+/// `serde_json`), ideally with `serde_stacker` to grow the stack.
 #[derive(Clone)]
 pub enum Node {
-    Text(String),
+    Text(Text),
     Element(Element),
     Fragment(Fragment),
+    Comment(String),
+    Doctype(Doctype),
+    ProcessingInstruction(ProcessingInstruction),
+    Placeholder(Placeholder),
 }
 
 impl Node {
     pub fn text(value: impl Into<String>) -> Self {
-        Self::Text(value.into())
+        Self::Text(Text::new(value))
     }
     pub fn element(
         tag: impl Into<TagBuf>,
@@ -25,11 +59,14 @@ impl Node {
         children: impl Into<Fragment>,
     ) -> Self {
         Self::Element(Element {
-            tag: tag.into(),
             attributes: attributes.into(),
             children: children.into(),
+            ..Element::new(tag)
         })
     }
+    pub fn comment(value: impl Into<String>) -> Self {
+        Self::Comment(value.into())
+    }
     pub fn as_text(&self) -> Option<&str> {
         match self {
             Self::Text(x) => Some(x.as_str()),
@@ -48,12 +85,65 @@ impl Node {
             _ => None,
         }
     }
+    pub fn as_comment(&self) -> Option<&str> {
+        match self {
+            Self::Comment(x) => Some(x.as_str()),
+            _ => None,
+        }
+    }
+    pub fn as_doctype(&self) -> Option<&Doctype> {
+        match self {
+            Self::Doctype(x) => Some(x),
+            _ => None,
+        }
+    }
+    pub fn as_placeholder(&self) -> Option<&Placeholder> {
+        match self {
+            Self::Placeholder(x) => Some(x),
+            _ => None,
+        }
+    }
     pub fn to_element(self) -> Option<Element> {
         match self {
             Self::Element(x) => Some(x),
             _ => None,
         }
     }
+    /// Where this node was parsed from, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Text(x) => x.span,
+            Self::Element(x) => x.span,
+            Self::Placeholder(x) => x.span,
+            _ => None,
+        }
+    }
+    /// Forgets where this node and its descendants were parsed from, e.g. to
+    /// leave spans out of the serialized form.
+    pub fn clear_spans(&mut self) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                Self::Element(element) => {
+                    element.span = None;
+                    for (_, placeholder) in element.placeholders.iter_mut() {
+                        placeholder.span = None;
+                    }
+                    if element.attributes.keys().any(|key| key.span().is_some()) {
+                        element.attributes = std::mem::take(&mut element.attributes)
+                            .into_iter()
+                            .map(|(key, value)| (key.with_span(None), value))
+                            .collect();
+                    }
+                    stack.extend(element.children.iter_mut());
+                }
+                Self::Fragment(fragment) => stack.extend(fragment.iter_mut()),
+                Self::Text(text) => text.span = None,
+                Self::Placeholder(placeholder) => placeholder.span = None,
+                Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) => (),
+            }
+        }
+    }
     pub fn lookup_element_attribute(&self, key: impl AsRef<str>) -> Option<&str> {
         self.as_element()
             .and_then(|element| {
@@ -69,13 +159,15 @@ impl Node {
             Node::Element(x) => vec![x],
             Node::Fragment(xs) => xs.extract_elements(),
             Node::Text(_) => Vec::new(),
+            Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) | Node::Placeholder(_) => Vec::new(),
         }
     }
     pub fn extract_text_strict(self) -> Result<Vec<String>, ()> {
         match self {
             Node::Element(_) => Err(()),
             Node::Fragment(xs) => xs.extract_text_strict(),
-            Node::Text(x) => Ok(vec![x]),
+            Node::Text(x) => Ok(vec![x.value]),
+            Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) | Node::Placeholder(_) => Ok(Vec::new()),
         }
     }
     pub fn flatten(self) -> Vec<Node> {
@@ -83,6 +175,15 @@ impl Node {
             Self::Text(text) => vec![Self::Text(text)],
             Self::Element(element) => vec![Self::Element(element)],
             Self::Fragment(fragment) => fragment.flatten(),
+            Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) | Self::Placeholder(_) => vec![self],
+        }
+    }
+    /// Returns the first top-level doctype declaration, looking through nested fragments.
+    pub fn find_doctype(&self) -> Option<&Doctype> {
+        match self {
+            Self::Doctype(doctype) => Some(doctype),
+            Self::Fragment(fragment) => fragment.iter().find_map(|x| x.find_doctype()),
+            _ => None,
         }
     }
 }
@@ -90,9 +191,13 @@ impl Node {
 impl Debug for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Text(text) => text.fmt(f),
+            Self::Text(text) => Debug::fmt(text, f),
             Self::Element(element) => element.fmt(f),
             Self::Fragment(nodes) => nodes.fmt(f),
+            Self::Comment(comment) => write!(f, "<!--{comment}-->"),
+            Self::Doctype(doctype) => Debug::fmt(doctype, f),
+            Self::ProcessingInstruction(pi) => Debug::fmt(pi, f),
+            Self::Placeholder(placeholder) => Debug::fmt(placeholder, f),
         }
     }
 }
@@ -103,6 +208,12 @@ impl From<Element> for Node {
     }
 }
 
+impl From<Fragment> for Node {
+    fn from(value: Fragment) -> Self {
+        Node::Fragment(value)
+    }
+}
+
 // ————————————————————————————————————————————————————————————————————————————
 // DATA MODEL — XML ELEMENTS
 // ————————————————————————————————————————————————————————————————————————————
@@ -112,11 +223,41 @@ pub struct Element {
     pub tag: TagBuf,
     pub attributes: AttributeMap,
     pub children: Fragment,
+    /// Where this element was parsed from (start tag through end tag), if known.
+    pub span: Option<Span>,
+    /// The source text of its tags, in lossless mode.
+    pub raw: Option<RawTags>,
+    /// Template placeholders written among the attributes of the start tag
+    /// (`<input {% if checked %}checked{% endif %}>`), each with the number of
+    /// attributes that precede it.
+    pub placeholders: Vec<(usize, Placeholder)>,
+    /// Whether the children are the contents of an HTML `<template>`. In the
+    /// DOM these form a separate, inert document fragment rather than child
+    /// nodes; here they are kept as the children, and this tells them apart.
+    pub template_contents: bool,
 }
 
 impl Element {
+    /// An element without attributes or children. An HTML `<template>` (in
+    /// the HTML namespace, or in none) is marked as holding template contents.
     pub fn new(tag: impl Into<TagBuf>) -> Self {
-        Element { tag: tag.into(), attributes: Default::default(), children: Default::default() }
+        let tag = tag.into();
+        Element {
+            template_contents: crate::constants::is_template_tag(&tag),
+            tag,
+            attributes: Default::default(),
+            children: Default::default(),
+            span: None,
+            raw: None,
+            placeholders: Vec::new(),
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
     }
     pub fn with_attributes(mut self, attributes: AttributeMap) -> Self {
         self.attributes.extend(attributes);
@@ -136,6 +277,27 @@ impl Element {
     pub fn extract_child_text_strict(self) -> Result<Vec<String>, ()> {
         self.children.extract_text_strict()
     }
+    /// The mode of the declarative shadow root this `<template>` declares
+    /// (`<template shadowrootmode="open">`), if any. The parsers keep such
+    /// templates as ordinary elements; nothing is attached.
+    pub fn shadow_root_mode(&self) -> Option<ShadowRootMode> {
+        if !self.template_contents {
+            return None
+        }
+        let (_, value) = self.attributes.iter().find(|(key, _)| key.as_str().eq_ignore_ascii_case("shadowrootmode"))?;
+        match value.as_str() {
+            x if x.eq_ignore_ascii_case("open") => Some(ShadowRootMode::Open),
+            x if x.eq_ignore_ascii_case("closed") => Some(ShadowRootMode::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// See [`Element::shadow_root_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    Open,
+    Closed,
 }
 
 impl std::fmt::Debug for Element {
@@ -151,17 +313,241 @@ impl std::fmt::Debug for Element {
             dbg.field("children", &self.children);
         }
 
+        if self.template_contents {
+            dbg.field("template_contents", &self.template_contents);
+        }
+
         dbg.finish()
     }
 }
 
 
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL — XML TEXT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A text node.
+#[derive(Clone, Default)]
+pub struct Text {
+    pub value: String,
+    /// Where this text was parsed from, if known.
+    pub span: Option<Span>,
+    /// Whether the text was written as a `<![CDATA[…]]>` section (and should be
+    /// serialized as one).
+    pub cdata: bool,
+    /// The source text, entities and all, in lossless mode.
+    pub raw: Option<RawSource>,
+    /// The character references the value was resolved from, if kept.
+    pub references: Vec<CharacterReference>,
+}
+
+impl Text {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self { value: value.into(), span: None, cdata: false, raw: None, references: Vec::new() }
+    }
+    /// A CDATA section.
+    pub fn cdata(value: impl Into<String>) -> Self {
+        Self { value: value.into(), span: None, cdata: true, raw: None, references: Vec::new() }
+    }
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
+    }
+    pub fn with_references(mut self, references: Vec<CharacterReference>) -> Self {
+        self.references = references;
+        self
+    }
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+    pub fn into_string(self) -> String {
+        self.value
+    }
+}
+
+impl Deref for Text {
+    type Target = String;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl DerefMut for Text {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
+impl Debug for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.value, f)
+    }
+}
+
+impl Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.value, f)
+    }
+}
+
+impl AsRef<str> for Text {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for Text {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for Text {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        text.value
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL — MARKUP DECLARATIONS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A `<!DOCTYPE …>` declaration.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Doctype {
+    pub name: String,
+    pub public_id: String,
+    pub system_id: String,
+    /// The source text, in lossless mode.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub raw: Option<RawSource>,
+}
+
+impl Doctype {
+    /// The standard HTML5 doctype, `<!DOCTYPE html>`.
+    pub fn html5() -> Self {
+        Self { name: String::from("html"), public_id: String::new(), system_id: String::new(), raw: None }
+    }
+}
+
+impl Default for Doctype {
+    fn default() -> Self {
+        Self::html5()
+    }
+}
+
+impl Debug for Doctype {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Display for Doctype {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<!DOCTYPE {}", self.name)?;
+        if !self.public_id.is_empty() {
+            write!(f, " PUBLIC {}", quote_id(&self.public_id))?;
+        }
+        if !self.system_id.is_empty() {
+            if self.public_id.is_empty() {
+                write!(f, " SYSTEM")?;
+            }
+            write!(f, " {}", quote_id(&self.system_id))?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// A doctype identifier as written: in double quotes, or in single quotes if
+/// it contains a double quote (identifiers have no escapes).
+fn quote_id(id: &str) -> String {
+    let quote = if id.contains('"') { '\'' } else { '"' };
+    format!("{quote}{id}{quote}")
+}
+
+/// An XML processing instruction, e.g. `<?xml-stylesheet href="style.css"?>`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessingInstruction {
+    pub target: String,
+    pub data: String,
+}
+
+impl Debug for ProcessingInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Display for ProcessingInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.data.is_empty() {
+            write!(f, "<?{}?>", self.target)
+        } else {
+            write!(f, "<?{} {}?>", self.target, self.data)
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL — TEMPLATE PLACEHOLDERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A template placeholder (`{{ user.name }}`, `{% if admin %}`, …) recognized
+/// when parsing with [`crate::parser::ParserOptions::with_placeholders`].
+///
+/// Placeholders are opaque: the parser never looks inside them or moves them,
+/// and the serializer writes them back verbatim.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Placeholder {
+    /// The opening delimiter, e.g. `{{`.
+    pub open: String,
+    /// Everything between the delimiters, as written.
+    pub expression: String,
+    /// The closing delimiter, e.g. `}}`.
+    pub close: String,
+    /// Where this placeholder was parsed from, if known.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub span: Option<Span>,
+}
+
+impl Placeholder {
+    pub fn new(open: impl Into<String>, expression: impl Into<String>, close: impl Into<String>) -> Self {
+        Self { open: open.into(), expression: expression.into(), close: close.into(), span: None }
+    }
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
+    }
+}
+
+impl Debug for Placeholder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Display for Placeholder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.open, self.expression, self.close)
+    }
+}
+
+
 
 // ————————————————————————————————————————————————————————————————————————————
 // DATA MODEL — XML FRAGMENTS
 // ————————————————————————————————————————————————————————————————————————————
 
-#[derive(Clone, Default)]
+#[derive(Default)]
 pub struct Fragment {
     nodes: Vec<Node>,
 }
@@ -176,6 +562,7 @@ impl Fragment {
                     Node::Element(x) => vec![x],
                     Node::Fragment(xs) => xs.extract_elements(),
                     Node::Text(_) => Vec::default(),
+                    Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) | Node::Placeholder(_) => Vec::default(),
                 }
             })
             .collect::<Vec<_>>()
@@ -189,8 +576,9 @@ impl Fragment {
                     results.extend(xs.extract_text_strict()?);
                 },
                 Node::Text(x) => {
-                    results.push(x);
+                    results.push(x.value);
                 },
+                Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) | Node::Placeholder(_) => (),
             }
         }
         Ok(results)
@@ -261,8 +649,8 @@ impl Fragment {
         self.nodes.iter_mut()
     }
 
-    pub fn to_vec(self) -> Vec<Node> {
-        self.nodes
+    pub fn to_vec(mut self) -> Vec<Node> {
+        std::mem::take(&mut self.nodes)
     }
 
     pub fn retain<F: FnMut(&Node) -> bool>(&mut self, f: F) {
@@ -351,7 +739,7 @@ impl IntoIterator for Fragment {
     type Item = Node;
     type IntoIter = std::vec::IntoIter<Node>;
     fn into_iter(self) -> Self::IntoIter {
-        self.nodes.into_iter()
+        self.to_vec().into_iter()
     }
 }
 
@@ -406,12 +794,103 @@ impl AsMut<[Node]> for Fragment {
     }
 }
 
+/// How many levels of nested fragments (the children of elements, and
+/// fragment nodes) [`Debug`] and the debug tree write out before eliding the
+/// rest as `…`, so that formatting a very deep tree cannot overflow the stack.
+pub(crate) const DEBUG_DEPTH: usize = 128;
+
+thread_local! {
+    /// The number of fragments being formatted by [`Debug`] on this thread.
+    static DEBUG_NESTING: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 impl Debug for Fragment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Restores the nesting on the way out, even if a node panics.
+        struct Nested(usize);
+        impl Drop for Nested {
+            fn drop(&mut self) {
+                DEBUG_NESTING.set(self.0);
+            }
+        }
+        let depth = DEBUG_NESTING.get();
+        if depth >= DEBUG_DEPTH && !self.nodes.is_empty() {
+            return f.write_str("[…]")
+        }
+        let _nested = Nested(depth);
+        DEBUG_NESTING.set(depth + 1);
         self.nodes.fmt(f)
     }
 }
 
+// Cloning and dropping are implemented by hand: the derived versions recurse
+// once per level of nesting and overflow the stack on very deep trees.
+
+impl Clone for Fragment {
+    fn clone(&self) -> Self {
+        // One frame per fragment being copied: its remaining nodes, the copies
+        // made so far, and the element or fragment that will hold them (`None`
+        // for `self`).
+        let mut stack = vec![(self.iter(), Vec::with_capacity(self.len()), None)];
+        loop {
+            let (source, copies, _) = stack.last_mut().unwrap();
+            match source.next() {
+                Some(Node::Element(element)) => {
+                    let shell = Element {
+                        tag: element.tag.clone(),
+                        attributes: element.attributes.clone(),
+                        children: Fragment::default(),
+                        span: element.span,
+                        raw: element.raw.clone(),
+                        placeholders: element.placeholders.clone(),
+                        template_contents: element.template_contents,
+                    };
+                    let children = &element.children;
+                    stack.push((children.iter(), Vec::with_capacity(children.len()), Some(Node::Element(shell))));
+                }
+                Some(Node::Fragment(fragment)) => {
+                    let shell = Node::Fragment(Fragment::default());
+                    stack.push((fragment.iter(), Vec::with_capacity(fragment.len()), Some(shell)));
+                }
+                Some(leaf) => copies.push(leaf.clone()),
+                None => {
+                    let (_, copies, shell) = stack.pop().unwrap();
+                    let children = Fragment::from_nodes(copies);
+                    let node = match shell {
+                        None => return children,
+                        Some(Node::Element(element)) => Node::Element(Element { children, ..element }),
+                        Some(_) => Node::Fragment(children),
+                    };
+                    stack.last_mut().unwrap().1.push(node);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Fragment {
+    fn drop(&mut self) {
+        let has_descendants = |node: &Node| match node {
+            Node::Element(element) => !element.children.is_empty(),
+            Node::Fragment(fragment) => !fragment.is_empty(),
+            _ => false,
+        };
+        if !self.nodes.iter().any(has_descendants) {
+            return
+        }
+        // Detach the children of each node before it is dropped, so that no
+        // drop ever has more than one level to tear down.
+        let mut pending = std::mem::take(&mut self.nodes);
+        while let Some(mut node) = pending.pop() {
+            match &mut node {
+                Node::Element(element) => pending.append(&mut element.children.nodes),
+                Node::Fragment(fragment) => pending.append(&mut fragment.nodes),
+                _ => (),
+            }
+        }
+    }
+}
+
 // ————————————————————————————————————————————————————————————————————————————
 // GENERAL HELPERS
 // ————————————————————————————————————————————————————————————————————————————
@@ -0,0 +1,305 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, PoisonError};
+use once_cell::sync::Lazy;
+use crate::constants::{HTML_NAMESPACE, MATHML_NAMESPACE, SVG_NAMESPACE, XLINK_NAMESPACE, XMLNS_NAMESPACE, XML_NAMESPACE};
+
+// ————————————————————————————————————————————————————————————————————————————
+// ATOMS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// An interned name, as tags and attribute keys store theirs.
+///
+/// The standard HTML, SVG and MathML names (and their lowercase forms) are
+/// static atoms; any other name is interned the first time it is seen and
+/// freed again once nothing refers to it. There is only ever one atom per
+/// name, so atoms compare by address, and cloning one copies a pointer or
+/// bumps a reference count.
+#[derive(Clone)]
+pub struct Atom(Repr);
+
+/// Thin pointers, so that an atom takes two words.
+#[derive(Clone)]
+enum Repr {
+    Static(&'static String),
+    Dynamic(Arc<String>),
+}
+
+impl Atom {
+    /// The atom for `name`, interning it if it is not a standard name.
+    pub fn new(name: &str) -> Self {
+        if let Some(name) = STATIC_ATOMS.get(name) {
+            return Self(Repr::Static(name))
+        }
+        let mut interner = DYNAMIC_ATOMS.lock().unwrap_or_else(PoisonError::into_inner);
+        Self(Repr::Dynamic(interner.intern(name)))
+    }
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Static(name) => name,
+            Repr::Dynamic(name) => name,
+        }
+    }
+    /// Whether this is one of the standard names, which are never freed.
+    pub fn is_static(&self) -> bool {
+        matches!(self.0, Repr::Static(_))
+    }
+    /// How many non-standard names are interned, counting those no atom refers
+    /// to any more until they are swept out.
+    pub fn interned_count() -> usize {
+        DYNAMIC_ATOMS.lock().unwrap_or_else(PoisonError::into_inner).atoms.len()
+    }
+    fn address(&self) -> *const String {
+        match &self.0 {
+            Repr::Static(name) => *name,
+            Repr::Dynamic(name) => Arc::as_ptr(name),
+        }
+    }
+}
+
+impl Debug for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+// Conversions
+
+impl From<&str> for Atom {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Atom {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl From<&String> for Atom {
+    fn from(name: &String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl Deref for Atom {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Atom {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Atom {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// Equality is by address (one atom per name); hashing and ordering go by the
+// name, consistently with `str`.
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl Eq for Atom {}
+
+impl PartialEq<str> for Atom {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Atom {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Atom {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Interned names that are not standard. Names no atom refers to any more are
+/// swept out whenever the set has doubled since the last sweep.
+struct Interner {
+    atoms: HashSet<Interned>,
+    sweep_at: usize,
+}
+
+const MIN_SWEEP_AT: usize = 1024;
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Arc<String> {
+        if let Some(Interned(atom)) = self.atoms.get(name) {
+            return atom.clone()
+        }
+        if self.atoms.len() >= self.sweep_at {
+            // Only the set holds a name with a count of one, and only the set
+            // can hand it out again, which takes the lock held here.
+            self.atoms.retain(|Interned(atom)| Arc::strong_count(atom) > 1);
+            self.sweep_at = (self.atoms.len() * 2).max(MIN_SWEEP_AT);
+        }
+        let atom = Arc::new(name.to_string());
+        self.atoms.insert(Interned(atom.clone()));
+        atom
+    }
+}
+
+/// A name in the interner, looked up by `str`.
+#[derive(PartialEq, Eq, Hash)]
+struct Interned(Arc<String>);
+
+impl Borrow<str> for Interned {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+static DYNAMIC_ATOMS: Lazy<Mutex<Interner>> = Lazy::new(|| {
+    Mutex::new(Interner { atoms: HashSet::new(), sweep_at: MIN_SWEEP_AT })
+});
+
+// ————————————————————————————————————————————————————————————————————————————
+// STANDARD NAMES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The standard names, with the lowercase forms of the mixed-case SVG names
+/// (as HTML normalizes them).
+static STATIC_ATOMS: Lazy<HashMap<&'static str, &'static String>> = Lazy::new(|| {
+    let mut atoms = HashMap::new();
+    let lowercase = STANDARD_NAMES.iter().map(|x| x.to_ascii_lowercase());
+    for name in STANDARD_NAMES.iter().map(|x| x.to_string()).chain(lowercase) {
+        if !atoms.contains_key(name.as_str()) {
+            let name: &'static String = Box::leak(Box::new(name));
+            atoms.insert(name.as_str(), name);
+        }
+    }
+    atoms
+});
+
+static STANDARD_NAMES: &[&str] = &[
+    // — Namespaces and prefixes
+    HTML_NAMESPACE, SVG_NAMESPACE, MATHML_NAMESPACE, XLINK_NAMESPACE, XML_NAMESPACE, XMLNS_NAMESPACE,
+    "xlink", "xml", "xmlns",
+
+    // — HTML elements
+    "a", "abbr", "address", "area", "article", "aside", "audio", "b", "base", "bdi", "bdo",
+    "blockquote", "body", "br", "button", "canvas", "caption", "cite", "code", "col",
+    "colgroup", "data", "datalist", "dd", "del", "details", "dfn", "dialog", "div", "dl", "dt",
+    "em", "embed", "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4",
+    "h5", "h6", "head", "header", "hgroup", "hr", "html", "i", "iframe", "img", "input", "ins",
+    "kbd", "label", "legend", "li", "link", "main", "map", "mark", "menu", "meta", "meter", "nav",
+    "noscript", "object", "ol", "optgroup", "option", "output", "p", "param", "picture", "pre",
+    "progress", "q", "rp", "rt", "ruby", "s", "samp", "script", "search", "section", "select",
+    "slot", "small", "source", "span", "strong", "style", "sub", "summary", "sup", "table",
+    "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "time", "title", "tr", "track",
+    "u", "ul", "var", "video", "wbr",
+
+    // — Obsolete HTML elements still found in the wild
+    "acronym", "applet", "basefont", "big", "blink", "center", "dir", "font", "frame", "frameset",
+    "image", "isindex", "keygen", "listing", "marquee", "menuitem", "nobr", "noembed", "noframes",
+    "plaintext", "rb", "rtc", "strike", "tt", "xmp",
+
+    // — HTML attributes
+    "accept", "accept-charset", "accesskey", "action", "align", "allow", "allowfullscreen", "alt",
+    "as", "async", "autocapitalize", "autocomplete", "autofocus", "autoplay", "background",
+    "bgcolor", "border", "charset", "checked", "class", "color", "cols", "colspan", "content",
+    "contenteditable", "controls", "coords", "crossorigin", "datetime", "decoding", "default",
+    "defer", "dirname", "disabled", "download", "draggable", "enctype", "enterkeyhint", "for",
+    "formaction", "formenctype", "formmethod", "formnovalidate", "formtarget", "headers",
+    "height", "hidden", "high", "href", "hreflang", "http-equiv", "id", "inert", "inputmode",
+    "integrity", "is", "itemid", "itemprop", "itemref", "itemscope", "itemtype", "kind", "lang",
+    "language", "list", "loading", "loop", "low", "max", "maxlength", "media", "method", "min",
+    "minlength", "multiple", "muted", "name", "nomodule", "nonce", "novalidate", "open",
+    "optimum", "pattern", "ping", "placeholder", "playsinline", "popover", "popovertarget",
+    "popovertargetaction", "poster", "preload", "property", "readonly", "referrerpolicy", "rel",
+    "required", "reversed", "role", "rows", "rowspan", "sandbox", "scope", "selected", "shape",
+    "size", "sizes", "spellcheck", "src", "srcdoc", "srclang", "srcset", "start", "step",
+    "tabindex", "target", "translate", "type", "usemap", "valign", "value", "width", "wrap",
+
+    // — Event handler attributes
+    "onabort", "onblur", "onchange", "onclick", "oncontextmenu", "ondblclick", "onerror",
+    "onfocus", "oninput", "onkeydown", "onkeypress", "onkeyup", "onload", "onmousedown",
+    "onmouseenter", "onmouseleave", "onmousemove", "onmouseout", "onmouseover", "onmouseup",
+    "onreset", "onresize", "onscroll", "onselect", "onsubmit", "onunload",
+
+    // — ARIA attributes
+    "aria-checked", "aria-controls", "aria-current", "aria-describedby", "aria-disabled",
+    "aria-expanded", "aria-haspopup", "aria-hidden", "aria-label", "aria-labelledby",
+    "aria-live", "aria-pressed", "aria-selected",
+
+    // — SVG elements
+    "svg", "animate", "animateMotion", "animateTransform", "circle", "clipPath", "defs", "desc",
+    "ellipse", "feBlend", "feColorMatrix", "feComponentTransfer", "feComposite",
+    "feConvolveMatrix", "feDiffuseLighting", "feDisplacementMap", "feDistantLight",
+    "feDropShadow", "feFlood", "feFuncA", "feFuncB", "feFuncG", "feFuncR", "feGaussianBlur",
+    "feImage", "feMerge", "feMergeNode", "feMorphology", "feOffset", "fePointLight",
+    "feSpecularLighting", "feSpotLight", "feTile", "feTurbulence", "filter", "foreignObject",
+    "g", "line", "linearGradient", "marker", "mask", "metadata", "mpath", "path", "pattern",
+    "polygon", "polyline", "radialGradient", "rect", "set", "stop", "switch", "symbol", "text",
+    "textPath", "tspan", "use", "view",
+
+    // — SVG attributes
+    "attributeName", "attributeType", "baseFrequency", "begin", "by", "calcMode", "clip-path",
+    "clip-rule", "cx", "cy", "d", "display", "dur", "dx", "dy", "end", "fill", "fill-opacity",
+    "fill-rule", "filterUnits", "font-family", "font-size", "font-weight", "fr", "from", "fx",
+    "fy", "gradientTransform", "gradientUnits", "in", "in2", "keyPoints", "keySplines",
+    "keyTimes", "lengthAdjust", "marker-end", "marker-mid", "marker-start", "markerHeight",
+    "markerUnits", "markerWidth", "maskContentUnits", "maskUnits", "mode", "offset", "opacity",
+    "operator", "overflow", "pathLength", "patternContentUnits", "patternTransform",
+    "patternUnits", "points", "preserveAspectRatio", "primitiveUnits", "r", "refX", "refY",
+    "repeatCount", "result", "rx", "ry", "spreadMethod", "stdDeviation", "stop-color",
+    "stop-opacity", "stroke", "stroke-dasharray", "stroke-dashoffset", "stroke-linecap",
+    "stroke-linejoin", "stroke-miterlimit", "stroke-opacity", "stroke-width", "systemLanguage",
+    "text-anchor", "textLength", "to", "transform", "values", "version", "viewBox",
+    "visibility", "x", "x1", "x2", "y", "y1", "y2", "z",
+    "xlink:href", "xlink:title", "xml:lang", "xml:space", "xmlns:xlink",
+
+    // — MathML
+    "math", "maction", "annotation", "annotation-xml", "menclose", "merror", "mfenced", "mfrac",
+    "mglyph", "mi", "mlabeledtr", "mmultiscripts", "mn", "mo", "mover", "mpadded", "mphantom",
+    "mprescripts", "mroot", "mrow", "ms", "mspace", "msqrt", "mstyle", "msub", "msubsup", "msup",
+    "mtable", "mtd", "mtext", "mtr", "munder", "munderover", "none", "semantics",
+    "definitionURL", "encoding", "mathvariant",
+];
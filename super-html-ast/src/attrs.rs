@@ -5,6 +5,7 @@ use std::{cmp::Ordering, hash::Hash};
 use std::iter::IntoIterator;
 use std::ops::{Deref, Index, IndexMut};
 use indexmap::IndexMap;
+use crate::{Atom, CharacterReference, Span};
 
 // NOTE: keep indexmap dependencies internal (so it can be swapped if necessary) — prefer newtypes.
 
@@ -63,7 +64,7 @@ impl<'a> AttributeKeyRef<'a> {
 
     /// Creates an owned reference from any string-like value.
     pub fn owned(value: impl Into<String>) -> Self {
-        let buf = AttributeKeyBuf::new(value);
+        let buf = AttributeKeyBuf::new(value.into());
         Self(Cow::Owned(buf.into()))
     }
 
@@ -107,18 +108,66 @@ impl<'a> Display for AttributeKeyRef<'a> {
 // ————————————————————————————————————————————————————————————————————————————
 
 /// An owned attribute key.
+///
+/// Keys parsed from source remember where the whole `key="value"` attribute
+/// was written; the span is metadata only and never affects equality or hashing.
+///
+/// The name is kept as written, including any namespace prefix (`xlink:href`).
+/// Namespace-aware parsers additionally record the URI the prefix resolved to.
+/// Both are [`Atom`]s, so keys compare by address and clone without allocating.
 #[derive(Clone)]
-pub struct AttributeKeyBuf(String);
+pub struct AttributeKeyBuf {
+    name: Atom,
+    span: Option<Span>,
+    namespace: Option<Atom>,
+}
 
 impl AttributeKeyBuf {
     /// Creates a new owned attribute key.
-    pub fn new(value: impl Into<String>) -> Self {
-        Self(value.into())
+    pub fn new(value: impl AsRef<str>) -> Self {
+        Self { name: Atom::new(value.as_ref()), span: None, namespace: None }
+    }
+
+    /// Records the namespace URI of the attribute.
+    pub fn with_namespace(mut self, namespace: impl Into<Option<Atom>>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// The namespace URI, if one was resolved.
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// The part of the name before the `:`, if any.
+    pub fn prefix(&self) -> Option<&str> {
+        self.name.split_once(':').map(|(prefix, _)| prefix)
+    }
+
+    /// The name without its prefix.
+    pub fn local_name(&self) -> &str {
+        self.name.split_once(':').map(|(_, local)| local).unwrap_or(&self.name)
+    }
+
+    /// Attaches the source location of the attribute.
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    /// Where this attribute was parsed from, if known.
+    pub fn span(&self) -> Option<Span> {
+        self.span
     }
 
     /// Returns a borrowed string slice.
     pub fn as_str(&self) -> &str {
-        &self.0
+        &self.name
+    }
+
+    /// The name as an atom, for comparisons by address.
+    pub fn as_atom(&self) -> &Atom {
+        &self.name
     }
 
     /// Returns a borrowed `AttributeKeyStr`.
@@ -163,7 +212,7 @@ impl From<&AttributeKeyStr> for AttributeKeyBuf {
 
 impl From<AttributeKeyBuf> for String {
     fn from(buf: AttributeKeyBuf) -> Self {
-        buf.0
+        buf.name.to_string()
     }
 }
 
@@ -200,7 +249,7 @@ impl AsRef<AttributeKeyStr> for str {
 
 impl PartialEq for AttributeKeyBuf {
     fn eq(&self, other: &Self) -> bool {
-        self.as_str() == other.as_str()
+        self.name == other.name
     }
 }
 
@@ -208,13 +257,13 @@ impl Eq for AttributeKeyBuf {}
 
 impl PartialOrd for AttributeKeyBuf {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.as_str().partial_cmp(other.as_str())
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for AttributeKeyBuf {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.as_str().cmp(other.as_str())
+        self.name.cmp(&other.name)
     }
 }
 
@@ -290,20 +339,42 @@ impl PartialEq<AttributeKeyStr> for AttributeKeyBuf {
 #[derive(Clone)]
 pub enum AttributeValueBuf {
     Literal(String),
+    /// A value parsed with its character references kept (see
+    /// [`crate::parser::ParserOptions::with_character_references`]).
+    Referenced {
+        value: String,
+        references: Vec<CharacterReference>,
+    },
 }
 
 impl AttributeValueBuf {
     pub fn literal(value: impl Into<String>) -> Self {
         AttributeValueBuf::Literal(value.into())
     }
+    /// A value written with the given character references; a literal if there are none.
+    pub fn referenced(value: impl Into<String>, references: Vec<CharacterReference>) -> Self {
+        if references.is_empty() {
+            return AttributeValueBuf::Literal(value.into())
+        }
+        AttributeValueBuf::Referenced { value: value.into(), references }
+    }
     pub fn as_str(&self) -> &str {
         match self {
             AttributeValueBuf::Literal(x) => x,
+            AttributeValueBuf::Referenced { value, .. } => value,
         }
     }
     pub fn as_mut_string(&mut self) -> &mut String {
         match self {
             AttributeValueBuf::Literal(x) => x,
+            AttributeValueBuf::Referenced { value, .. } => value,
+        }
+    }
+    /// The character references the value was resolved from, if kept.
+    pub fn references(&self) -> &[CharacterReference] {
+        match self {
+            AttributeValueBuf::Literal(_) => &[],
+            AttributeValueBuf::Referenced { references, .. } => references,
         }
     }
 }
@@ -320,6 +391,34 @@ impl Display for AttributeValueBuf {
     }
 }
 
+/// Values compare by what they resolve to; kept character references are
+/// not part of the value.
+impl PartialEq for AttributeValueBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for AttributeValueBuf {}
+
+impl PartialOrd for AttributeValueBuf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AttributeValueBuf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for AttributeValueBuf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
 // ————————————————————————————————————————————————————————————————————————————
 // ATTRIBUTE MAP
 // ————————————————————————————————————————————————————————————————————————————
@@ -341,6 +440,26 @@ impl Debug for AttributeMap {
     }
 }
 
+/// Attributes compare in order, by name and value. See [`crate::Comparison`]
+/// to ignore the order.
+impl PartialEq for AttributeMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().eq(other.0.iter())
+    }
+}
+
+impl Eq for AttributeMap {}
+
+impl Hash for AttributeMap {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.0.len());
+        for (key, value) in &self.0 {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
 // ————————————————————————————————————————————————————————————————————————————
 // ATTRIBUTE MAP — CONSTRUCTION
 // ————————————————————————————————————————————————————————————————————————————
@@ -396,6 +515,12 @@ impl AttributeMap {
         self.0.get_mut(AttributeKeyStr::from_str(key.as_ref()))
     }
 
+    /// Looks `key` up by address instead of hashing its name, which is quicker
+    /// for the few attributes elements usually have.
+    pub fn get_atom(&self, key: &Atom) -> Option<&AttributeValueBuf> {
+        self.0.iter().find(|(name, _)| name.as_atom() == key).map(|(_, value)| value)
+    }
+
     pub fn insert<K: Into<AttributeKeyBuf>, V: Into<AttributeValueBuf>>(
         &mut self,
         key: K,
@@ -409,6 +534,13 @@ impl AttributeMap {
             .swap_remove(AttributeKeyStr::from_str(key.as_ref()))
     }
 
+    /// Returns where the attribute was parsed from, if known.
+    pub fn span<Q: AsRef<str>>(&self, key: Q) -> Option<Span> {
+        self.0
+            .get_key_value(AttributeKeyStr::from_str(key.as_ref()))
+            .and_then(|(key, _)| key.span())
+    }
+
     pub fn contains_key<Q: AsRef<str>>(&self, key: Q) -> bool {
         self.0.contains_key(AttributeKeyStr::from_str(key.as_ref()))
     }
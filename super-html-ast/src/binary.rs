@@ -0,0 +1,805 @@
+//! A compact binary encoding of trees, for caching parsed documents (see
+//! [`Node::to_bytes`] and [`EncodedTree`]).
+//!
+//! Layout, after the magic bytes `HAST` and a version byte:
+//!
+//! - a string table: a count, then each string as its length and UTF-8 bytes.
+//!   Tag names, attribute names and namespaces repeat a lot and are stored
+//!   here once, referred to by index;
+//! - the root node, then its descendants in document order. Each node is a
+//!   kind byte and a flags byte (which optional parts follow), then its
+//!   contents. Elements and fragments end with their number of children and
+//!   the byte length of their encoded children (4 bytes little-endian, so
+//!   readers can skip them), which come next.
+//!
+//! Other numbers are LEB128 varints, fingerprints of lossless source text 8
+//! bytes little-endian, and other strings are written inline like table entries.
+use std::collections::HashMap;
+use std::fmt::Display;
+use crate::{
+    Atom, AttributeKeyBuf, AttributeValueBuf, CharacterReference, Doctype, Element, Fragment, Node, Placeholder,
+    ProcessingInstruction, RawSource, RawTags, SourcePosition, Span, TagBuf, Text,
+};
+
+// ————————————————————————————————————————————————————————————————————————————
+// FORMAT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The version of the encoding [`Node::to_bytes`] writes. Readers reject any
+/// other, so caches keyed on it are invalidated when it changes.
+pub const BINARY_FORMAT_VERSION: u8 = 2;
+
+const MAGIC: &[u8; 4] = b"HAST";
+
+const TEXT: u8 = 0;
+const ELEMENT: u8 = 1;
+const FRAGMENT: u8 = 2;
+const COMMENT: u8 = 3;
+const DOCTYPE: u8 = 4;
+const PROCESSING_INSTRUCTION: u8 = 5;
+const PLACEHOLDER: u8 = 6;
+
+const HAS_SPAN: u8 = 1 << 0;
+const HAS_RAW: u8 = 1 << 1;
+const HAS_REFERENCES: u8 = 1 << 2;
+const HAS_NAMESPACE: u8 = 1 << 3;
+const HAS_PLACEHOLDERS: u8 = 1 << 4;
+const CDATA: u8 = 1 << 5;
+const TEMPLATE_CONTENTS: u8 = 1 << 6;
+/// Lossless source text for an end tag follows that of the start tag.
+const HAS_RAW_END: u8 = 1 << 7;
+
+impl Node {
+    /// Encodes the tree in a compact binary form (see [`BINARY_FORMAT_VERSION`])
+    /// that [`Node::from_bytes`] reads back, everything included.
+    ///
+    /// # Panics
+    ///
+    /// If the encoded children of an element take more than 4 GiB.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        // Children still to write, and where to patch in their byte length.
+        let mut stack = vec![(std::slice::from_ref(self).iter(), None)];
+        while let Some((nodes, _)) = stack.last_mut() {
+            let Some(node) = nodes.next() else {
+                if let (_, Some(patch)) = stack.pop().unwrap() {
+                    encoder.patch_length(patch);
+                }
+                continue
+            };
+            match node {
+                Node::Element(element) => {
+                    encoder.element(element);
+                    stack.push((element.children.iter(), Some(encoder.children(element.children.len()))));
+                }
+                Node::Fragment(fragment) => {
+                    encoder.header(FRAGMENT, 0);
+                    stack.push((fragment.iter(), Some(encoder.children(fragment.len()))));
+                }
+                leaf => encoder.leaf(leaf),
+            }
+        }
+        encoder.finish()
+    }
+    /// Decodes a tree written by [`Node::to_bytes`]. To look into the tree
+    /// without building it, see [`EncodedTree`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Node, BinaryFormatError> {
+        let (strings, root) = read_header(bytes)?;
+        let mut decoder = Decoder { bytes, position: root, strings: &strings };
+        let node = decoder.tree()?;
+        match decoder.remaining() {
+            0 => Ok(node),
+            _ => Err(decoder.error("Unexpected data after the tree")),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// ZERO-COPY ACCESS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A tree encoded by [`Node::to_bytes`], read in place: names and text are
+/// borrowed from the bytes, and only what is looked at is decoded.
+///
+/// The bytes are checked once, up front, so reading nodes cannot fail.
+pub struct EncodedTree<'a> {
+    bytes: &'a [u8],
+    strings: Vec<&'a str>,
+    root: usize,
+}
+
+impl<'a> EncodedTree<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, BinaryFormatError> {
+        let (strings, root) = read_header(bytes)?;
+        let mut decoder = Decoder { bytes, position: root, strings: &strings };
+        decoder.check_tree()?;
+        if decoder.remaining() > 0 {
+            return Err(decoder.error("Unexpected data after the tree"))
+        }
+        Ok(Self { bytes, strings, root })
+    }
+    pub fn root(&self) -> EncodedNode<'_, 'a> {
+        EncodedNode { tree: self, offset: self.root }
+    }
+    /// Decodes the whole tree, like [`Node::from_bytes`].
+    pub fn to_node(&self) -> Node {
+        self.root().to_node()
+    }
+    fn decoder(&self, offset: usize) -> Decoder<'_, 'a> {
+        Decoder { bytes: self.bytes, position: offset, strings: &self.strings }
+    }
+}
+
+/// A node of an [`EncodedTree`].
+#[derive(Clone, Copy)]
+pub struct EncodedNode<'t, 'a> {
+    tree: &'t EncodedTree<'a>,
+    offset: usize,
+}
+
+impl<'t, 'a> EncodedNode<'t, 'a> {
+    fn item(&self) -> Item<'a> {
+        self.tree.decoder(self.offset).item().expect("checked by `EncodedTree::new`")
+    }
+    pub fn is_element(&self) -> bool {
+        self.tree.bytes[self.offset] == ELEMENT
+    }
+    pub fn is_fragment(&self) -> bool {
+        self.tree.bytes[self.offset] == FRAGMENT
+    }
+    pub fn is_text(&self) -> bool {
+        self.tree.bytes[self.offset] == TEXT
+    }
+    /// The tag name of an element, as written.
+    pub fn tag_name(&self) -> Option<&'a str> {
+        match self.item() {
+            Item::Element { head, .. } => Some(head.tag),
+            _ => None,
+        }
+    }
+    /// The namespace URI of an element, if it has one.
+    pub fn namespace(&self) -> Option<&'a str> {
+        match self.item() {
+            Item::Element { head, .. } => head.namespace,
+            _ => None,
+        }
+    }
+    /// The attributes of an element, in order, as names and values.
+    pub fn attributes(&self) -> impl Iterator<Item = (&'a str, &'a str)> + 't {
+        let (count, offset) = match self.item() {
+            Item::Element { head, .. } => (head.attributes.0, head.attributes.1),
+            _ => (0, 0),
+        };
+        let mut decoder = self.tree.decoder(offset);
+        (0..count).map(move |_| decoder.attribute(|_, _, _| ()).expect("checked by `EncodedTree::new`"))
+    }
+    /// The value of the element attribute called `name` (compared exactly).
+    pub fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes().find(|(key, _)| *key == name).map(|(_, value)| value)
+    }
+    /// The value of a text node.
+    pub fn text(&self) -> Option<&'a str> {
+        match self.item() {
+            Item::Text { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+    /// The text of a comment.
+    pub fn comment(&self) -> Option<&'a str> {
+        match self.item() {
+            Item::Comment(comment) => Some(comment),
+            _ => None,
+        }
+    }
+    /// Where an element or text node was parsed from, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self.item() {
+            Item::Element { head, .. } => head.span,
+            Item::Text { span, .. } => span,
+            _ => None,
+        }
+    }
+    /// The children of an element or fragment; none for other nodes.
+    pub fn children(&self) -> impl Iterator<Item = EncodedNode<'t, 'a>> + 't {
+        let mut decoder = self.tree.decoder(self.offset);
+        let count = match decoder.item().expect("checked by `EncodedTree::new`") {
+            Item::Element { children, .. } | Item::Fragment { children, .. } => children.count,
+            _ => 0,
+        };
+        let tree = self.tree;
+        (0..count).map(move |_| {
+            let offset = decoder.position;
+            decoder.skip_node().expect("checked by `EncodedTree::new`");
+            EncodedNode { tree, offset }
+        })
+    }
+    /// Decodes this node and its descendants.
+    pub fn to_node(&self) -> Node {
+        self.tree.decoder(self.offset).tree().expect("checked by `EncodedTree::new`")
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// ERRORS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Bytes that could not be read as an encoded tree.
+#[derive(Debug, Clone)]
+pub struct BinaryFormatError {
+    pub message: String,
+    /// The byte offset where decoding failed.
+    pub offset: usize,
+}
+
+impl Display for BinaryFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for BinaryFormatError {}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL — ENCODER
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Default)]
+struct Encoder<'a> {
+    body: Vec<u8>,
+    strings: Vec<&'a str>,
+    indices: HashMap<&'a str, usize>,
+}
+
+impl<'a> Encoder<'a> {
+    fn finish(self) -> Vec<u8> {
+        let table_length: usize = self.strings.iter().map(|x| x.len() + 2).sum();
+        let mut output = Encoder { body: Vec::with_capacity(5 + table_length + self.body.len()), ..Encoder::default() };
+        output.body.extend_from_slice(MAGIC);
+        output.body.push(BINARY_FORMAT_VERSION);
+        output.number(self.strings.len());
+        for string in &self.strings {
+            output.string(string);
+        }
+        output.body.extend_from_slice(&self.body);
+        output.body
+    }
+    fn header(&mut self, kind: u8, flags: u8) {
+        self.body.extend_from_slice(&[kind, flags]);
+    }
+    fn number(&mut self, value: usize) {
+        self.wide_number(value as u64)
+    }
+    fn wide_number(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.body.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.body.push(value as u8);
+    }
+    fn string(&mut self, value: &str) {
+        self.number(value.len());
+        self.body.extend_from_slice(value.as_bytes());
+    }
+    /// Writes the table index of `value`, adding it if new.
+    fn shared(&mut self, value: &'a str) {
+        let next = self.strings.len();
+        let index = *self.indices.entry(value).or_insert(next);
+        if index == next {
+            self.strings.push(value);
+        }
+        self.number(index);
+    }
+    /// The end relative to the start, which keeps the numbers small.
+    fn span(&mut self, span: Span) {
+        let (start, end) = (span.start, span.end);
+        self.number(start.offset);
+        self.wide_number((end.offset as u64).wrapping_sub(start.offset as u64));
+        self.number(start.line);
+        self.number(start.column);
+        self.wide_number((end.line as u64).wrapping_sub(start.line as u64));
+        self.number(end.column);
+    }
+    fn raw(&mut self, raw: &RawSource) {
+        self.string(raw.as_str());
+        self.body.extend_from_slice(&raw.fingerprint().to_le_bytes());
+    }
+    fn references(&mut self, references: &[CharacterReference]) {
+        self.number(references.len());
+        for reference in references {
+            self.number(reference.offset);
+            self.string(&reference.source);
+            self.string(&reference.resolved);
+        }
+    }
+    fn placeholder(&mut self, placeholder: &Placeholder) {
+        self.body.push(flag(HAS_SPAN, placeholder.span.is_some()));
+        self.string(&placeholder.open);
+        self.string(&placeholder.expression);
+        self.string(&placeholder.close);
+        if let Some(span) = placeholder.span {
+            self.span(span);
+        }
+    }
+    /// Writes the number of children and room for their byte length, returning
+    /// where to patch it in.
+    fn children(&mut self, count: usize) -> usize {
+        self.number(count);
+        self.body.extend_from_slice(&[0; 4]);
+        self.body.len() - 4
+    }
+    fn patch_length(&mut self, patch: usize) {
+        let length = u32::try_from(self.body.len() - patch - 4).expect("encoded children take more than 4 GiB");
+        self.body[patch..patch + 4].copy_from_slice(&length.to_le_bytes());
+    }
+    /// Everything of `element` but its children.
+    fn element(&mut self, element: &'a Element) {
+        let mut flags = 0;
+        flags |= flag(HAS_NAMESPACE, element.tag.namespace().is_some());
+        flags |= flag(HAS_SPAN, element.span.is_some());
+        flags |= flag(HAS_RAW, element.raw.is_some());
+        flags |= flag(HAS_RAW_END, element.raw.as_ref().is_some_and(|x| x.end.is_some()));
+        flags |= flag(HAS_PLACEHOLDERS, !element.placeholders.is_empty());
+        flags |= flag(TEMPLATE_CONTENTS, element.template_contents);
+        self.header(ELEMENT, flags);
+        self.shared(element.tag.as_original());
+        if let Some(namespace) = element.tag.namespace() {
+            self.shared(namespace);
+        }
+        if let Some(span) = element.span {
+            self.span(span);
+        }
+        if let Some(raw) = &element.raw {
+            self.raw(&raw.start);
+            if let Some(end) = &raw.end {
+                self.raw(end);
+            }
+        }
+        self.number(element.attributes.len());
+        for (key, value) in element.attributes.iter() {
+            let mut flags = 0;
+            flags |= flag(HAS_NAMESPACE, key.namespace().is_some());
+            flags |= flag(HAS_SPAN, key.span().is_some());
+            flags |= flag(HAS_REFERENCES, !value.references().is_empty());
+            self.body.push(flags);
+            self.shared(key.as_str());
+            if let Some(namespace) = key.namespace() {
+                self.shared(namespace);
+            }
+            if let Some(span) = key.span() {
+                self.span(span);
+            }
+            self.string(value.as_str());
+            if !value.references().is_empty() {
+                self.references(value.references());
+            }
+        }
+        if !element.placeholders.is_empty() {
+            self.number(element.placeholders.len());
+            for (position, placeholder) in &element.placeholders {
+                self.number(*position);
+                self.placeholder(placeholder);
+            }
+        }
+    }
+    fn leaf(&mut self, node: &Node) {
+        match node {
+            Node::Text(text) => {
+                let mut flags = 0;
+                flags |= flag(HAS_SPAN, text.span.is_some());
+                flags |= flag(HAS_RAW, text.raw.is_some());
+                flags |= flag(HAS_REFERENCES, !text.references.is_empty());
+                flags |= flag(CDATA, text.cdata);
+                self.header(TEXT, flags);
+                self.string(&text.value);
+                if let Some(span) = text.span {
+                    self.span(span);
+                }
+                if let Some(raw) = &text.raw {
+                    self.raw(raw);
+                }
+                if !text.references.is_empty() {
+                    self.references(&text.references);
+                }
+            }
+            Node::Comment(comment) => {
+                self.header(COMMENT, 0);
+                self.string(comment);
+            }
+            Node::Doctype(doctype) => {
+                self.header(DOCTYPE, flag(HAS_RAW, doctype.raw.is_some()));
+                self.string(&doctype.name);
+                self.string(&doctype.public_id);
+                self.string(&doctype.system_id);
+                if let Some(raw) = &doctype.raw {
+                    self.raw(raw);
+                }
+            }
+            Node::ProcessingInstruction(pi) => {
+                self.header(PROCESSING_INSTRUCTION, 0);
+                self.string(&pi.target);
+                self.string(&pi.data);
+            }
+            Node::Placeholder(placeholder) => {
+                self.header(PLACEHOLDER, 0);
+                self.placeholder(placeholder);
+            }
+            Node::Element(_) | Node::Fragment(_) => unreachable!("containers are written by `Node::to_bytes`"),
+        }
+    }
+}
+
+fn flag(flag: u8, set: bool) -> u8 {
+    if set { flag } else { 0 }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL — DECODER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Reads the magic bytes, version and string table; returns the table and
+/// where the root node starts.
+fn read_header(bytes: &[u8]) -> Result<(Vec<&str>, usize), BinaryFormatError> {
+    let mut decoder = Decoder { bytes, position: 0, strings: &[] };
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(decoder.error("Not an encoded tree"))
+    }
+    let version = decoder.byte()?;
+    if version != BINARY_FORMAT_VERSION {
+        decoder.position -= 1;
+        return Err(decoder.error(&format!("Unsupported format version {version}")))
+    }
+    let count = decoder.number()?;
+    let mut strings = Vec::with_capacity(count.min(decoder.remaining()));
+    for _ in 0..count {
+        strings.push(decoder.str()?);
+    }
+    Ok((strings, decoder.position))
+}
+
+struct Decoder<'t, 'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strings: &'t [&'a str],
+}
+
+/// A node as read, borrowing from the bytes. Attributes and placeholders are
+/// checked but left in place, as their count and where they start.
+enum Item<'a> {
+    Text { value: &'a str, span: Option<Span>, cdata: bool, raw: Option<Raw<'a>>, references: Option<usize> },
+    Element { head: ElementHead<'a>, children: Children },
+    Fragment { children: Children },
+    Comment(&'a str),
+    Doctype { name: &'a str, public_id: &'a str, system_id: &'a str, raw: Option<Raw<'a>> },
+    ProcessingInstruction { target: &'a str, data: &'a str },
+    Placeholder(usize),
+}
+
+/// Lossless source text as read: the text and its fingerprint.
+type Raw<'a> = (&'a str, u64);
+
+struct ElementHead<'a> {
+    flags: u8,
+    tag: &'a str,
+    namespace: Option<&'a str>,
+    span: Option<Span>,
+    raw: Option<(Raw<'a>, Option<Raw<'a>>)>,
+    attributes: (usize, usize),
+    placeholders: (usize, usize),
+}
+
+/// The children of an element or fragment: how many, and their byte length.
+#[derive(Clone, Copy)]
+struct Children {
+    count: usize,
+    length: usize,
+}
+
+impl<'t, 'a> Decoder<'t, 'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+    fn error(&self, message: &str) -> BinaryFormatError {
+        BinaryFormatError { message: message.to_string(), offset: self.position }
+    }
+    fn take(&mut self, length: usize) -> Result<&'a [u8], BinaryFormatError> {
+        if length > self.remaining() {
+            return Err(self.error("Unexpected end of data"))
+        }
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+    fn byte(&mut self) -> Result<u8, BinaryFormatError> {
+        Ok(self.take(1)?[0])
+    }
+    fn wide_number(&mut self) -> Result<u64, BinaryFormatError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err(self.error("Number out of range"))
+    }
+    fn number(&mut self) -> Result<usize, BinaryFormatError> {
+        let value = self.wide_number()?;
+        usize::try_from(value).map_err(|_| self.error("Number out of range"))
+    }
+    fn str(&mut self) -> Result<&'a str, BinaryFormatError> {
+        let length = self.number()?;
+        let start = self.position;
+        let bytes = self.take(length)?;
+        std::str::from_utf8(bytes).map_err(|_| BinaryFormatError { message: "Invalid UTF-8".to_string(), offset: start })
+    }
+    fn shared(&mut self) -> Result<&'a str, BinaryFormatError> {
+        let index = self.number()?;
+        self.strings.get(index).copied().ok_or_else(|| self.error("String index out of range"))
+    }
+    fn span(&mut self) -> Result<Span, BinaryFormatError> {
+        let offset = self.number()?;
+        let length = self.wide_number()?;
+        let (line, column) = (self.number()?, self.number()?);
+        let lines = self.wide_number()?;
+        let end_column = self.number()?;
+        let start = SourcePosition { offset, line, column };
+        let end = SourcePosition {
+            offset: (offset as u64).wrapping_add(length) as usize,
+            line: (line as u64).wrapping_add(lines) as usize,
+            column: end_column,
+        };
+        Ok(Span::new(start, end))
+    }
+    fn optional_span(&mut self, present: bool) -> Result<Option<Span>, BinaryFormatError> {
+        if present { self.span().map(Some) } else { Ok(None) }
+    }
+    fn raw(&mut self) -> Result<Raw<'a>, BinaryFormatError> {
+        let text = self.str()?;
+        let fingerprint = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        Ok((text, fingerprint))
+    }
+    fn optional_raw(&mut self, present: bool) -> Result<Option<Raw<'a>>, BinaryFormatError> {
+        if present { self.raw().map(Some) } else { Ok(None) }
+    }
+    /// Reads character references, handing each to `each`.
+    fn references(&mut self, mut each: impl FnMut(usize, &'a str, &'a str)) -> Result<(), BinaryFormatError> {
+        let count = self.number()?;
+        for _ in 0..count {
+            each(self.number()?, self.str()?, self.str()?);
+        }
+        Ok(())
+    }
+    fn owned_references(&mut self) -> Result<Vec<CharacterReference>, BinaryFormatError> {
+        let mut references = Vec::new();
+        self.references(|offset, source, resolved| references.push(CharacterReference::new(offset, source, resolved)))?;
+        Ok(references)
+    }
+    /// Reads an attribute's name and value, handing its other parts to `each`:
+    /// the namespace, span and where references start (if any).
+    fn attribute(
+        &mut self,
+        each: impl FnOnce(Option<&'a str>, Option<Span>, Option<usize>),
+    ) -> Result<(&'a str, &'a str), BinaryFormatError> {
+        let flags = self.byte()?;
+        let name = self.shared()?;
+        let namespace = if flags & HAS_NAMESPACE != 0 { Some(self.shared()?) } else { None };
+        let span = self.optional_span(flags & HAS_SPAN != 0)?;
+        let value = self.str()?;
+        let references = (flags & HAS_REFERENCES != 0).then_some(self.position);
+        if references.is_some() {
+            self.references(|_, _, _| ())?;
+        }
+        each(namespace, span, references);
+        Ok((name, value))
+    }
+    fn placeholder(&mut self) -> Result<Placeholder, BinaryFormatError> {
+        let flags = self.byte()?;
+        let placeholder = Placeholder::new(self.str()?, self.str()?, self.str()?);
+        Ok(placeholder.with_span(self.optional_span(flags & HAS_SPAN != 0)?))
+    }
+    /// Reads the next node, not its children; its contents are checked.
+    fn item(&mut self) -> Result<Item<'a>, BinaryFormatError> {
+        let start = self.position;
+        let kind = self.byte()?;
+        let flags = self.byte()?;
+        let has = |flag: u8| flags & flag != 0;
+        let item = match kind {
+            TEXT => Item::Text {
+                value: self.str()?,
+                span: self.optional_span(has(HAS_SPAN))?,
+                cdata: has(CDATA),
+                raw: self.optional_raw(has(HAS_RAW))?,
+                references: match has(HAS_REFERENCES) {
+                    true => {
+                        let position = self.position;
+                        self.references(|_, _, _| ())?;
+                        Some(position)
+                    }
+                    false => None,
+                },
+            },
+            ELEMENT => {
+                let tag = self.shared()?;
+                let namespace = if has(HAS_NAMESPACE) { Some(self.shared()?) } else { None };
+                let span = self.optional_span(has(HAS_SPAN))?;
+                let raw = match has(HAS_RAW) {
+                    true => Some((self.raw()?, self.optional_raw(has(HAS_RAW_END))?)),
+                    false => None,
+                };
+                let attributes = (self.number()?, self.position);
+                for _ in 0..attributes.0 {
+                    self.attribute(|_, _, _| ())?;
+                }
+                let mut placeholders = (0, self.position);
+                if has(HAS_PLACEHOLDERS) {
+                    placeholders = (self.number()?, self.position);
+                    for _ in 0..placeholders.0 {
+                        self.number()?;
+                        self.placeholder()?;
+                    }
+                }
+                let head = ElementHead { flags, tag, namespace, span, raw, attributes, placeholders };
+                Item::Element { head, children: self.children()? }
+            }
+            FRAGMENT => Item::Fragment { children: self.children()? },
+            COMMENT => Item::Comment(self.str()?),
+            DOCTYPE => Item::Doctype {
+                name: self.str()?,
+                public_id: self.str()?,
+                system_id: self.str()?,
+                raw: self.optional_raw(has(HAS_RAW))?,
+            },
+            PROCESSING_INSTRUCTION => Item::ProcessingInstruction { target: self.str()?, data: self.str()? },
+            PLACEHOLDER => {
+                let position = self.position;
+                self.placeholder()?;
+                Item::Placeholder(position)
+            }
+            _ => {
+                self.position = start;
+                return Err(self.error("Unknown node kind"))
+            }
+        };
+        Ok(item)
+    }
+    /// Reads the number of children and their byte length, which must fit.
+    fn children(&mut self) -> Result<Children, BinaryFormatError> {
+        let count = self.number()?;
+        let length = u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize;
+        if length > self.remaining() {
+            return Err(self.error("Children run past the end of data"))
+        }
+        Ok(Children { count, length })
+    }
+    /// Moves past the next node and its descendants.
+    fn skip_node(&mut self) -> Result<(), BinaryFormatError> {
+        if let Item::Element { children, .. } | Item::Fragment { children, .. } = self.item()? {
+            self.position += children.length;
+        }
+        Ok(())
+    }
+    /// Checks the next node and its descendants, including that children take
+    /// the byte length recorded for them.
+    fn check_tree(&mut self) -> Result<(), BinaryFormatError> {
+        // Per open element or fragment: children left to read, and where they end.
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        loop {
+            if let Item::Element { children, .. } | Item::Fragment { children, .. } = self.item()?
+                && children.count > 0
+            {
+                open.push((children.count, self.position + children.length));
+                continue
+            }
+            loop {
+                let Some((remaining, end)) = open.last_mut() else {
+                    return Ok(())
+                };
+                *remaining -= 1;
+                if *remaining > 0 {
+                    break
+                }
+                if self.position != *end {
+                    return Err(self.error("Children do not match their recorded length"))
+                }
+                open.pop();
+            }
+        }
+    }
+    /// Reads the next node and its descendants, like [`Decoder::check_tree`].
+    fn tree(&mut self) -> Result<Node, BinaryFormatError> {
+        // Per open element or fragment: the node without children, children
+        // left to read, where they end, and those read so far.
+        let mut open: Vec<(Node, usize, usize, Vec<Node>)> = Vec::new();
+        loop {
+            let item = self.item()?;
+            let children = match &item {
+                Item::Element { children, .. } | Item::Fragment { children, .. } => Some(*children),
+                _ => None,
+            };
+            let mut node = item.into_node(self);
+            if let Some(children) = children
+                && children.count > 0
+            {
+                // At most one node per remaining byte pair.
+                let capacity = children.count.min(children.length / 2);
+                open.push((node, children.count, self.position + children.length, Vec::with_capacity(capacity)));
+                continue
+            }
+            loop {
+                let Some((_, remaining, end, children)) = open.last_mut() else {
+                    return Ok(node)
+                };
+                children.push(node);
+                *remaining -= 1;
+                if *remaining > 0 {
+                    break
+                }
+                if self.position != *end {
+                    return Err(self.error("Children do not match their recorded length"))
+                }
+                let (shell, _, _, children) = open.pop().unwrap();
+                let children = Fragment::from_nodes(children);
+                node = match shell {
+                    Node::Element(element) => Node::Element(Element { children, ..element }),
+                    _ => Node::Fragment(children),
+                };
+            }
+        }
+    }
+}
+
+impl Item<'_> {
+    /// The owned node, without children. Placeholders and references, already
+    /// checked, are read again from the bytes of `decoder`.
+    fn into_node(self, decoder: &Decoder) -> Node {
+        let raw_source = |(text, fingerprint): (&str, u64)| RawSource::from_parts(text, fingerprint);
+        let at = |position: usize| Decoder { bytes: decoder.bytes, position, strings: decoder.strings };
+        match self {
+            Item::Text { value, span, cdata, raw, references } => Node::Text(Text {
+                value: value.to_string(),
+                span,
+                cdata,
+                raw: raw.map(raw_source),
+                references: references.map(|x| at(x).owned_references().unwrap()).unwrap_or_default(),
+            }),
+            Item::Element { head, .. } => {
+                let has = |flag: u8| head.flags & flag != 0;
+                let mut tag = TagBuf::new(head.tag);
+                if let Some(namespace) = head.namespace {
+                    tag = tag.with_namespace(Atom::new(namespace));
+                }
+                let mut element = Element::new(tag);
+                element.template_contents = has(TEMPLATE_CONTENTS);
+                element.span = head.span;
+                element.raw = head.raw.map(|(start, end)| RawTags { start: raw_source(start), end: end.map(raw_source) });
+                let mut attributes = at(head.attributes.1);
+                for _ in 0..head.attributes.0 {
+                    let mut parts = (None, None, None);
+                    let (name, value) = attributes.attribute(|namespace, span, references| parts = (namespace, span, references)).unwrap();
+                    let key = AttributeKeyBuf::new(name).with_namespace(parts.0.map(Atom::new)).with_span(parts.1);
+                    let references = parts.2.map(|x| at(x).owned_references().unwrap()).unwrap_or_default();
+                    element.attributes.insert(key, AttributeValueBuf::referenced(value, references));
+                }
+                let mut placeholders = at(head.placeholders.1);
+                for _ in 0..head.placeholders.0 {
+                    let position = placeholders.number().unwrap();
+                    element.placeholders.push((position, placeholders.placeholder().unwrap()));
+                }
+                Node::Element(element)
+            }
+            Item::Fragment { .. } => Node::Fragment(Fragment::default()),
+            Item::Comment(comment) => Node::Comment(comment.to_string()),
+            Item::Doctype { name, public_id, system_id, raw } => Node::Doctype(Doctype {
+                name: name.to_string(),
+                public_id: public_id.to_string(),
+                system_id: system_id.to_string(),
+                raw: raw.map(raw_source),
+            }),
+            Item::ProcessingInstruction { target, data } => {
+                Node::ProcessingInstruction(ProcessingInstruction { target: target.to_string(), data: data.to_string() })
+            }
+            Item::Placeholder(position) => Node::Placeholder(at(position).placeholder().unwrap()),
+        }
+    }
+}
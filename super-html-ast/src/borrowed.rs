@@ -0,0 +1,418 @@
+//! A borrowed variant of the tree, for read-only processing of many documents.
+//!
+//! Names, attribute values and text that appear verbatim in the source are
+//! slices of it rather than copies (see [`crate::parser::parse_borrowed`]), and
+//! tags keep a single name. Only what read-only passes need is kept: spans of
+//! elements and text, but not of attributes; no lossless source text,
+//! character references or placeholders among attributes. [`Node::into_owned`]
+//! turns the tree into a [`crate::Node`] tree.
+use std::borrow::Cow;
+use std::ops::Index;
+use crate::{Atom, AttributeKeyBuf, AttributeValueBuf, Doctype, NodePath, Placeholder, ProcessingInstruction, Selector, SelectorElement, Span, TagBuf, WithPaths};
+use crate::path::{get_by_path, get_by_path_mut, Tree};
+use crate::query::select_paths;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DATA MODEL
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+pub enum Node<'a> {
+    Text(Text<'a>),
+    Element(Element<'a>),
+    Fragment(Fragment<'a>),
+    Comment(Cow<'a, str>),
+    Doctype(Doctype),
+    ProcessingInstruction(ProcessingInstruction),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone)]
+pub struct Text<'a> {
+    /// The text with character references resolved.
+    pub value: Cow<'a, str>,
+    pub span: Option<Span>,
+    /// Whether the text was written as a CDATA section (in XML).
+    pub cdata: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Element<'a> {
+    pub tag: Tag<'a>,
+    /// In the order written.
+    pub attributes: Vec<Attribute<'a>>,
+    pub children: Fragment<'a>,
+    /// Where this element was parsed from (start tag through end tag), if known.
+    pub span: Option<Span>,
+    /// See [`crate::Element::template_contents`].
+    pub template_contents: bool,
+}
+
+/// A tag name as written, prefix included, and its namespace URI if known.
+/// Unlike [`TagBuf`], no normalized copy of the name is kept; comparisons
+/// normalize as they go.
+#[derive(Debug, Clone)]
+pub struct Tag<'a> {
+    pub name: Cow<'a, str>,
+    pub namespace: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Attribute<'a> {
+    /// The name as written, prefix included.
+    pub name: Cow<'a, str>,
+    /// The value with character references resolved.
+    pub value: Cow<'a, str>,
+    pub namespace: Option<Cow<'a, str>>,
+}
+
+/// The children of an element, or top-level nodes.
+#[derive(Debug, Default)]
+pub struct Fragment<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(&text.value),
+            _ => None,
+        }
+    }
+    pub fn as_element(&self) -> Option<&Element<'a>> {
+        match self {
+            Self::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+    pub fn as_fragment(&self) -> Option<&Fragment<'a>> {
+        match self {
+            Self::Fragment(fragment) => Some(fragment),
+            _ => None,
+        }
+    }
+    pub fn as_comment(&self) -> Option<&str> {
+        match self {
+            Self::Comment(comment) => Some(comment),
+            _ => None,
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Text(text) => text.span,
+            Self::Element(element) => element.span,
+            Self::Placeholder(placeholder) => placeholder.span,
+            _ => None,
+        }
+    }
+    pub fn lookup_element_attribute(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.as_element()?.attribute(key)
+    }
+    /// The same tree as a [`crate::Node`], every string owned.
+    pub fn into_owned(self) -> crate::Node {
+        into_owned(self)
+    }
+}
+
+impl<'a> Element<'a> {
+    pub fn new(tag: Tag<'a>) -> Self {
+        Self { tag, attributes: Vec::new(), children: Fragment::default(), span: None, template_contents: false }
+    }
+    /// The value of the first attribute called `key` (compared exactly).
+    pub fn attribute(&self, key: impl AsRef<str>) -> Option<&str> {
+        let key = key.as_ref();
+        self.attributes.iter().find(|x| x.name == key).map(|x| x.value.as_ref())
+    }
+}
+
+impl<'a> Tag<'a> {
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Self {
+        Self { name: name.into(), namespace: None }
+    }
+    pub fn with_namespace(mut self, namespace: impl Into<Option<Cow<'a, str>>>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+    pub fn as_original(&self) -> &str {
+        &self.name
+    }
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+    /// The part of the name before the `:`, if any.
+    pub fn prefix(&self) -> Option<&str> {
+        self.name.split_once(':').map(|(prefix, _)| prefix)
+    }
+    /// The name without its prefix.
+    pub fn local_name(&self) -> &str {
+        self.name.split_once(':').map_or(&self.name, |(_, local)| local)
+    }
+    /// Like [`TagBuf::matches`].
+    pub fn matches(&self, other: &TagBuf) -> bool {
+        match (self.namespace(), other.namespace()) {
+            (Some(left), Some(right)) => left == right && self.local_name().eq_ignore_ascii_case(other.local_name()),
+            _ if self.name.is_ascii() => self.name.eq_ignore_ascii_case(other.as_normalized()),
+            _ => self.name.to_lowercase() == other.as_normalized(),
+        }
+    }
+    pub fn into_owned(self) -> TagBuf {
+        TagBuf::new(self.name).with_namespace(self.namespace.as_deref().map(Atom::new))
+    }
+}
+
+impl<'a> Fragment<'a> {
+    pub fn from_nodes(nodes: impl Into<Vec<Node<'a>>>) -> Self {
+        Self { nodes: nodes.into() }
+    }
+    pub fn as_node_slice(&self) -> &[Node<'a>] {
+        &self.nodes
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn get(&self, index: usize) -> Option<&Node<'a>> {
+        self.nodes.get(index)
+    }
+    pub fn push(&mut self, node: Node<'a>) {
+        self.nodes.push(node);
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Node<'a>> {
+        self.nodes.iter()
+    }
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Node<'a>> {
+        self.nodes.iter_mut()
+    }
+    pub fn to_vec(mut self) -> Vec<Node<'a>> {
+        std::mem::take(&mut self.nodes)
+    }
+}
+
+impl<'a> From<Vec<Node<'a>>> for Fragment<'a> {
+    fn from(nodes: Vec<Node<'a>>) -> Self {
+        Self::from_nodes(nodes)
+    }
+}
+
+impl<'a> Index<usize> for Fragment<'a> {
+    type Output = Node<'a>;
+    fn index(&self, index: usize) -> &Node<'a> {
+        &self.nodes[index]
+    }
+}
+
+impl<'a, 'f> IntoIterator for &'f Fragment<'a> {
+    type Item = &'f Node<'a>;
+    type IntoIter = std::slice::Iter<'f, Node<'a>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.iter()
+    }
+}
+
+impl<'a> FromIterator<Node<'a>> for Fragment<'a> {
+    fn from_iter<T: IntoIterator<Item = Node<'a>>>(iter: T) -> Self {
+        Self::from_nodes(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<'a> From<Element<'a>> for Node<'a> {
+    fn from(element: Element<'a>) -> Self {
+        Node::Element(element)
+    }
+}
+
+impl From<Node<'_>> for crate::Node {
+    fn from(node: Node<'_>) -> Self {
+        node.into_owned()
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// QUERIES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The same queries as on [`crate::Node`].
+impl<'a> Node<'a> {
+    pub fn find_first(&self, target: &TagBuf) -> Option<Node<'a>> {
+        self.iter_with_paths()
+            .find(|(_, node)| node.as_element().is_some_and(|x| x.tag.matches(target)))
+            .map(|(_, node)| node.clone())
+    }
+    /// Where [`Node::find_first`] finds its match, below this node.
+    pub fn find_first_path(&self, target: &TagBuf) -> Option<NodePath> {
+        self.iter_with_paths()
+            .find(|(_, node)| node.as_element().is_some_and(|x| x.tag.matches(target)))
+            .map(|(path, _)| path)
+    }
+    /// See [`crate::Node::select_paths`].
+    pub fn select_paths(&self, selector: &Selector) -> Vec<NodePath> {
+        select_paths(self, selector, Node::as_element)
+    }
+    /// See [`crate::Node::get_by_path`].
+    pub fn get_by_path(&self, path: impl AsRef<[usize]>) -> Option<&Node<'a>> {
+        get_by_path(self, path.as_ref())
+    }
+    pub fn get_by_path_mut(&mut self, path: impl AsRef<[usize]>) -> Option<&mut Node<'a>> {
+        get_by_path_mut(self, path.as_ref())
+    }
+    /// This node and its descendants in document order, each with its path.
+    pub fn iter_with_paths(&self) -> WithPaths<'_, Node<'a>> {
+        WithPaths::new(self)
+    }
+}
+
+impl Tree for Node<'_> {
+    fn child_nodes(&self) -> Option<&[Self]> {
+        match self {
+            Node::Element(element) => Some(&element.children.nodes),
+            Node::Fragment(fragment) => Some(&fragment.nodes),
+            _ => None,
+        }
+    }
+    fn child_nodes_mut(&mut self) -> Option<&mut [Self]> {
+        match self {
+            Node::Element(element) => Some(&mut element.children.nodes),
+            Node::Fragment(fragment) => Some(&mut fragment.nodes),
+            _ => None,
+        }
+    }
+}
+
+impl SelectorElement for &Element<'_> {
+    fn local_name(&self) -> &str {
+        self.tag.local_name()
+    }
+    fn attribute(&self, name: &Atom) -> Option<&str> {
+        self.attributes.iter().find(|x| x.name.eq_ignore_ascii_case(name)).map(|x| x.value.as_ref())
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL — CONVERSION, CLONING AND DROPPING
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Converts bottom-up with an explicit stack, like cloning an owned tree.
+fn into_owned(node: Node<'_>) -> crate::Node {
+    // One frame per element or fragment being converted: its remaining nodes,
+    // the converted ones, and the owned element or fragment that will hold them.
+    let mut stack: Vec<(std::vec::IntoIter<Node>, Vec<crate::Node>, crate::Node)> = Vec::new();
+    let mut next = node;
+    loop {
+        let mut converted = match next {
+            Node::Element(mut element) => {
+                let mut attributes = crate::AttributeMap::default();
+                for attribute in std::mem::take(&mut element.attributes) {
+                    let key = AttributeKeyBuf::new(attribute.name).with_namespace(attribute.namespace.as_deref().map(Atom::new));
+                    attributes.insert(key, AttributeValueBuf::literal(attribute.value));
+                }
+                let shell = crate::Element {
+                    attributes,
+                    span: element.span,
+                    template_contents: element.template_contents,
+                    ..crate::Element::new(std::mem::replace(&mut element.tag, Tag::new("")).into_owned())
+                };
+                let children = std::mem::take(&mut element.children.nodes);
+                stack.push((children.into_iter(), Vec::new(), crate::Node::Element(shell)));
+                None
+            }
+            Node::Fragment(mut fragment) => {
+                let children = std::mem::take(&mut fragment.nodes);
+                stack.push((children.into_iter(), Vec::new(), crate::Node::Fragment(crate::Fragment::default())));
+                None
+            }
+            Node::Text(text) => {
+                let owned = match text.cdata {
+                    true => crate::Text::cdata(text.value),
+                    false => crate::Text::new(text.value),
+                };
+                Some(crate::Node::Text(owned.with_span(text.span)))
+            }
+            Node::Comment(comment) => Some(crate::Node::Comment(comment.into_owned())),
+            Node::Doctype(doctype) => Some(crate::Node::Doctype(doctype)),
+            Node::ProcessingInstruction(pi) => Some(crate::Node::ProcessingInstruction(pi)),
+            Node::Placeholder(placeholder) => Some(crate::Node::Placeholder(placeholder)),
+        };
+        loop {
+            let Some((pending, outputs, _)) = stack.last_mut() else {
+                return converted.unwrap()
+            };
+            if let Some(node) = converted.take() {
+                outputs.push(node);
+            }
+            if let Some(node) = pending.next() {
+                next = node;
+                break
+            }
+            let (_, outputs, shell) = stack.pop().unwrap();
+            let children = crate::Fragment::from_nodes(outputs);
+            converted = Some(match shell {
+                crate::Node::Element(element) => crate::Node::Element(crate::Element { children, ..element }),
+                _ => crate::Node::Fragment(children),
+            });
+        }
+    }
+}
+
+impl Clone for Fragment<'_> {
+    fn clone(&self) -> Self {
+        // As for owned fragments: one frame per fragment being copied, with its
+        // remaining nodes, the copies so far and the node that will hold them.
+        let mut stack = vec![(self.iter(), Vec::with_capacity(self.len()), None)];
+        loop {
+            let (source, copies, _) = stack.last_mut().unwrap();
+            match source.next() {
+                Some(Node::Element(element)) => {
+                    let shell = Element {
+                        tag: element.tag.clone(),
+                        attributes: element.attributes.clone(),
+                        children: Fragment::default(),
+                        span: element.span,
+                        template_contents: element.template_contents,
+                    };
+                    let children = &element.children;
+                    stack.push((children.iter(), Vec::with_capacity(children.len()), Some(Node::Element(shell))));
+                }
+                Some(Node::Fragment(fragment)) => {
+                    let shell = Node::Fragment(Fragment::default());
+                    stack.push((fragment.iter(), Vec::with_capacity(fragment.len()), Some(shell)));
+                }
+                Some(leaf) => copies.push(leaf.clone()),
+                None => {
+                    let (_, copies, shell) = stack.pop().unwrap();
+                    let children = Fragment::from_nodes(copies);
+                    let node = match shell {
+                        None => return children,
+                        Some(Node::Element(element)) => Node::Element(Element { children, ..element }),
+                        Some(_) => Node::Fragment(children),
+                    };
+                    stack.last_mut().unwrap().1.push(node);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Fragment<'_> {
+    fn drop(&mut self) {
+        let has_descendants = |node: &Node| match node {
+            Node::Element(element) => !element.children.is_empty(),
+            Node::Fragment(fragment) => !fragment.is_empty(),
+            _ => false,
+        };
+        if !self.nodes.iter().any(has_descendants) {
+            return
+        }
+        // Detach the children of each node before it is dropped, so that no
+        // drop ever has more than one level to tear down.
+        let mut pending = std::mem::take(&mut self.nodes);
+        while let Some(mut node) = pending.pop() {
+            match &mut node {
+                Node::Element(element) => pending.append(&mut element.children.nodes),
+                Node::Fragment(fragment) => pending.append(&mut fragment.nodes),
+                _ => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use crate::{Element, Fragment, Node};
+use crate::constants::HTML_NAMESPACE;
+
+// ————————————————————————————————————————————————————————————————————————————
+// COMPARISON MODES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// How [`Compare`] tells trees apart.
+///
+/// Either way only content counts: spans, lossless source text, kept character
+/// references and whether text was written as CDATA are ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Comparison {
+    /// Compare tag and attribute names ASCII case-insensitively, and elements
+    /// in the HTML namespace as equal to ones without a namespace (as built by
+    /// hand rather than parsed).
+    pub ignore_case: bool,
+    /// Compare attributes as sets rather than in the order written (placeholders
+    /// among them then also lose their positions).
+    pub ignore_attribute_order: bool,
+    /// Trim text and collapse whitespace runs to a single space, dropping text
+    /// that is only whitespace, except in `<pre>`, `<textarea>`, `<script>`
+    /// and the like.
+    pub ignore_whitespace: bool,
+    /// Splice nested fragments into their parent and merge the text nodes that
+    /// become adjacent, so only the resulting text matters.
+    pub merge_text: bool,
+}
+
+impl Comparison {
+    /// Every difference in content counts. What the `Eq`, `Ord` and `Hash`
+    /// implementations of [`Node`], [`Element`] and [`Fragment`] use.
+    pub fn exact() -> Self {
+        Self::default()
+    }
+    /// Differences HTML does not care about are ignored: case in names,
+    /// attribute order, insignificant whitespace and how text is split up.
+    pub fn semantic() -> Self {
+        Self { ignore_case: true, ignore_attribute_order: true, ignore_whitespace: true, merge_text: true }
+    }
+    pub fn with_ignore_case(mut self, ignore: bool) -> Self {
+        self.ignore_case = ignore;
+        self
+    }
+    pub fn with_ignore_attribute_order(mut self, ignore: bool) -> Self {
+        self.ignore_attribute_order = ignore;
+        self
+    }
+    pub fn with_ignore_whitespace(mut self, ignore: bool) -> Self {
+        self.ignore_whitespace = ignore;
+        self
+    }
+    pub fn with_merge_text(mut self, merge: bool) -> Self {
+        self.merge_text = merge;
+        self
+    }
+}
+
+/// Structural equality, ordering and hashing under a [`Comparison`]. The
+/// three agree: values that compare equal hash the same.
+pub trait Compare {
+    fn eq_with(&self, other: &Self, comparison: Comparison) -> bool;
+    /// A total order, consistent with [`Compare::eq_with`] but otherwise arbitrary.
+    fn cmp_with(&self, other: &Self, comparison: Comparison) -> Ordering;
+    fn hash_with<H: Hasher>(&self, state: &mut H, comparison: Comparison);
+}
+
+impl Compare for Node {
+    fn eq_with(&self, other: &Self, comparison: Comparison) -> bool {
+        keys(Root::Node(self), comparison) == keys(Root::Node(other), comparison)
+    }
+    fn cmp_with(&self, other: &Self, comparison: Comparison) -> Ordering {
+        keys(Root::Node(self), comparison).cmp(&keys(Root::Node(other), comparison))
+    }
+    fn hash_with<H: Hasher>(&self, state: &mut H, comparison: Comparison) {
+        keys(Root::Node(self), comparison).hash(state)
+    }
+}
+
+impl Compare for Element {
+    fn eq_with(&self, other: &Self, comparison: Comparison) -> bool {
+        keys(Root::Element(self), comparison) == keys(Root::Element(other), comparison)
+    }
+    fn cmp_with(&self, other: &Self, comparison: Comparison) -> Ordering {
+        keys(Root::Element(self), comparison).cmp(&keys(Root::Element(other), comparison))
+    }
+    fn hash_with<H: Hasher>(&self, state: &mut H, comparison: Comparison) {
+        keys(Root::Element(self), comparison).hash(state)
+    }
+}
+
+impl Compare for Fragment {
+    fn eq_with(&self, other: &Self, comparison: Comparison) -> bool {
+        keys(Root::Fragment(self), comparison) == keys(Root::Fragment(other), comparison)
+    }
+    fn cmp_with(&self, other: &Self, comparison: Comparison) -> Ordering {
+        keys(Root::Fragment(self), comparison).cmp(&keys(Root::Fragment(other), comparison))
+    }
+    fn hash_with<H: Hasher>(&self, state: &mut H, comparison: Comparison) {
+        keys(Root::Fragment(self), comparison).hash(state)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SEMANTIC WRAPPER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Compares, orders and hashes the wrapped tree with [`Comparison::semantic`],
+/// e.g. to dedupe nodes in a `HashSet<Semantic<Node>>`.
+#[derive(Debug, Clone)]
+pub struct Semantic<T>(pub T);
+
+impl<T: Compare> PartialEq for Semantic<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_with(&other.0, Comparison::semantic())
+    }
+}
+
+impl<T: Compare> Eq for Semantic<T> {}
+
+impl<T: Compare> PartialOrd for Semantic<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Compare> Ord for Semantic<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_with(&other.0, Comparison::semantic())
+    }
+}
+
+impl<T: Compare> Hash for Semantic<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_with(state, Comparison::semantic())
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// EXACT TRAIT IMPLEMENTATIONS
+// ————————————————————————————————————————————————————————————————————————————
+
+macro_rules! exact_comparisons {
+    ($($type:ty),*) => {$(
+        impl PartialEq for $type {
+            fn eq(&self, other: &Self) -> bool {
+                self.eq_with(other, Comparison::exact())
+            }
+        }
+
+        impl Eq for $type {}
+
+        impl PartialOrd for $type {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $type {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.cmp_with(other, Comparison::exact())
+            }
+        }
+
+        impl Hash for $type {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.hash_with(state, Comparison::exact())
+            }
+        }
+    )*};
+}
+
+exact_comparisons!(Node, Element, Fragment);
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL — COMPARISON KEYS
+// ————————————————————————————————————————————————————————————————————————————
+
+// Trees are flattened into a sequence of keys, normalized as the comparison
+// asks; equality, ordering and hashing then all work on the sequences, which
+// keeps them consistent and avoids recursing into deep trees.
+
+#[derive(Clone, Copy)]
+enum Root<'a> {
+    Node(&'a Node),
+    Element(&'a Element),
+    Fragment(&'a Fragment),
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Key<'a> {
+    Open { name: Cow<'a, str>, namespace: Option<&'a str>, template_contents: bool },
+    Attribute { name: Cow<'a, str>, namespace: Option<&'a str>, value: &'a str },
+    /// A placeholder among the attributes, after `position` of them (`None` if
+    /// attribute order is ignored).
+    AttributePlaceholder { position: Option<usize>, open: &'a str, expression: &'a str, close: &'a str },
+    /// A fragment kept as such (see [`Comparison::merge_text`]).
+    OpenFragment,
+    /// The end of an element or kept fragment.
+    Close,
+    Text(Cow<'a, str>),
+    Comment(&'a str),
+    Doctype { name: &'a str, public_id: &'a str, system_id: &'a str },
+    ProcessingInstruction { target: &'a str, data: &'a str },
+    Placeholder { open: &'a str, expression: &'a str, close: &'a str },
+}
+
+/// Elements whose whitespace is always significant.
+const PREFORMATTED_TAGS: &[&str] = &["pre", "listing", "plaintext", "textarea", "script", "style"];
+
+/// Children still to visit, and whether they end with a [`Key::Close`].
+struct Frame<'a> {
+    nodes: std::slice::Iter<'a, Node>,
+    closes: bool,
+    preformatted: bool,
+}
+
+struct KeyWriter<'a> {
+    comparison: Comparison,
+    keys: Vec<Key<'a>>,
+    /// Text not yet written, when merging.
+    text: Option<Cow<'a, str>>,
+    /// How many preformatted elements enclose the current node.
+    preformatted: usize,
+}
+
+fn keys(root: Root<'_>, comparison: Comparison) -> Vec<Key<'_>> {
+    let mut writer = KeyWriter { comparison, keys: Vec::new(), text: None, preformatted: 0 };
+    let mut stack = Vec::new();
+    match root {
+        Root::Node(node) => stack.push(Frame { nodes: std::slice::from_ref(node).iter(), closes: false, preformatted: false }),
+        Root::Element(element) => stack.push(writer.open_element(element)),
+        Root::Fragment(fragment) => stack.push(writer.open_fragment(fragment)),
+    }
+    while let Some(frame) = stack.last_mut() {
+        let Some(node) = frame.nodes.next() else {
+            let frame = stack.pop().unwrap();
+            if frame.closes {
+                writer.flush_text();
+                writer.keys.push(Key::Close);
+            }
+            if frame.preformatted {
+                writer.preformatted -= 1;
+            }
+            continue
+        };
+        match node {
+            Node::Text(text) => writer.push_text(&text.value),
+            Node::Element(element) => {
+                let frame = writer.open_element(element);
+                stack.push(frame);
+            }
+            Node::Fragment(fragment) => {
+                let frame = writer.open_fragment(fragment);
+                stack.push(frame);
+            }
+            Node::Comment(comment) => writer.push(Key::Comment(comment)),
+            Node::Doctype(doctype) => writer.push(Key::Doctype {
+                name: &doctype.name,
+                public_id: &doctype.public_id,
+                system_id: &doctype.system_id,
+            }),
+            Node::ProcessingInstruction(pi) => {
+                writer.push(Key::ProcessingInstruction { target: &pi.target, data: &pi.data })
+            }
+            Node::Placeholder(placeholder) => writer.push(Key::Placeholder {
+                open: &placeholder.open,
+                expression: &placeholder.expression,
+                close: &placeholder.close,
+            }),
+        }
+    }
+    writer.flush_text();
+    writer.keys
+}
+
+impl<'a> KeyWriter<'a> {
+    fn push(&mut self, key: Key<'a>) {
+        self.flush_text();
+        self.keys.push(key);
+    }
+    fn name(&self, name: &'a str) -> Cow<'a, str> {
+        match self.comparison.ignore_case && name.bytes().any(|x| x.is_ascii_uppercase()) {
+            true => Cow::Owned(name.to_ascii_lowercase()),
+            false => Cow::Borrowed(name),
+        }
+    }
+    fn open_element(&mut self, element: &'a Element) -> Frame<'a> {
+        let tag = &element.tag;
+        let namespace = tag.namespace().filter(|x| !self.comparison.ignore_case || *x != HTML_NAMESPACE);
+        self.push(Key::Open {
+            name: self.name(tag.as_original()),
+            namespace,
+            template_contents: element.template_contents,
+        });
+        let mut attributes = element
+            .attributes
+            .iter()
+            .map(|(key, value)| Key::Attribute { name: self.name(key.as_str()), namespace: key.namespace(), value: value.as_str() })
+            .collect::<Vec<_>>();
+        let mut placeholders = element
+            .placeholders
+            .iter()
+            .map(|(position, placeholder)| Key::AttributePlaceholder {
+                position: Some(*position).filter(|_| !self.comparison.ignore_attribute_order),
+                open: &placeholder.open,
+                expression: &placeholder.expression,
+                close: &placeholder.close,
+            })
+            .collect::<Vec<_>>();
+        if self.comparison.ignore_attribute_order {
+            attributes.sort();
+            placeholders.sort();
+        }
+        self.keys.extend(attributes);
+        self.keys.extend(placeholders);
+        let preformatted = self.comparison.ignore_whitespace && PREFORMATTED_TAGS.contains(&tag.as_normalized());
+        if preformatted {
+            self.preformatted += 1;
+        }
+        Frame { nodes: element.children.iter(), closes: true, preformatted }
+    }
+    fn open_fragment(&mut self, fragment: &'a Fragment) -> Frame<'a> {
+        let closes = !self.comparison.merge_text;
+        if closes {
+            self.push(Key::OpenFragment);
+        }
+        Frame { nodes: fragment.iter(), closes, preformatted: false }
+    }
+    fn push_text(&mut self, value: &'a str) {
+        match &mut self.text {
+            Some(text) if self.comparison.merge_text => text.to_mut().push_str(value),
+            _ => {
+                self.flush_text();
+                self.text = Some(Cow::Borrowed(value));
+            }
+        }
+    }
+    fn flush_text(&mut self) {
+        let Some(text) = self.text.take() else {
+            return
+        };
+        if !self.comparison.ignore_whitespace || self.preformatted > 0 {
+            self.keys.push(Key::Text(text));
+            return
+        }
+        let collapsed = text.split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+        if !collapsed.is_empty() {
+            self.keys.push(Key::Text(Cow::Owned(collapsed)));
+        }
+    }
+}
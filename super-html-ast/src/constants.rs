@@ -37,6 +37,15 @@ static VOID_TAGS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
     .collect()
 });
 
+pub const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+pub const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+/// Bound to the `xml` prefix in every XML document.
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+/// The namespace of `xmlns` and `xmlns:*` declaration attributes.
+pub const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
+
 pub fn is_inline_tag(tag: &TagBuf) -> bool {
     INLINE_TAGS.contains(tag.as_normalized())
 }
@@ -49,6 +58,12 @@ pub fn is_void_tag(tag: &TagBuf) -> bool {
     VOID_TAGS.contains(tag.as_normalized())
 }
 
+/// Whether `tag` is an HTML `<template>`, whose children are template contents.
+/// A tag without a namespace, as built by hand, is taken to be HTML.
+pub fn is_template_tag(tag: &TagBuf) -> bool {
+    tag.local_name().eq_ignore_ascii_case("template") && tag.namespace().is_none_or(|x| x == HTML_NAMESPACE)
+}
+
 
 // pub(crate) static ROOT_HTML_TAG: Lazy<TagBuf> = Lazy::new(|| TagBuf::new("html"));
 
@@ -0,0 +1,188 @@
+use crate::{Element, Fragment, Node, NodePath};
+use crate::path::children_mut;
+
+// ————————————————————————————————————————————————————————————————————————————
+// CURSOR
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A position in an owned tree, for moving around it and editing in place
+/// without writing a recursive function each time.
+///
+/// The cursor holds the tree while it is used: the node in focus, and for
+/// each ancestor, the ancestor itself and the siblings on either side.
+/// Moving to a sibling or the parent is cheap; moving to a child takes the
+/// children of the node in focus apart. [`Cursor::finish`] puts the tree back
+/// together.
+///
+/// Moves return whether the cursor moved; if not, it stays where it was.
+pub struct Cursor {
+    focus: Node,
+    /// From the root down to the parent of the focus.
+    path: Vec<Crumb>,
+}
+
+/// An ancestor of the focus, with its children other than the one on the path.
+struct Crumb {
+    parent: Node,
+    before: Vec<Node>,
+    /// Nearest last.
+    after: Vec<Node>,
+}
+
+impl Cursor {
+    /// A cursor on the root of `tree`.
+    pub fn new(tree: impl Into<Node>) -> Self {
+        Self { focus: tree.into(), path: Vec::new() }
+    }
+    /// The node in focus, with its children.
+    pub fn node(&self) -> &Node {
+        &self.focus
+    }
+    pub fn node_mut(&mut self) -> &mut Node {
+        &mut self.focus
+    }
+    pub fn is_root(&self) -> bool {
+        self.path.is_empty()
+    }
+    /// How many ancestors the node in focus has.
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+    /// The position of the node in focus among its siblings.
+    pub fn index(&self) -> usize {
+        self.path.last().map_or(0, |crumb| crumb.before.len())
+    }
+    /// The child indices leading from the root to the node in focus.
+    pub fn path(&self) -> NodePath {
+        self.path.iter().map(|crumb| crumb.before.len()).collect()
+    }
+    /// Puts the tree back together and returns it.
+    pub fn finish(mut self) -> Node {
+        self.to_root();
+        self.focus
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// MOVING
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Cursor {
+    pub fn to_parent(&mut self) -> bool {
+        let Some(Crumb { mut parent, mut before, after }) = self.path.pop() else {
+            return false
+        };
+        before.push(std::mem::replace(&mut self.focus, Node::empty()));
+        before.extend(after.into_iter().rev());
+        *children_mut(&mut parent).unwrap() = Fragment::from_nodes(before);
+        self.focus = parent;
+        true
+    }
+    pub fn to_root(&mut self) {
+        while self.to_parent() {}
+    }
+    /// Moves to the child at `index` of an element or fragment.
+    pub fn to_child(&mut self, index: usize) -> bool {
+        let Some(children) = children_mut(&mut self.focus).filter(|x| index < x.len()) else {
+            return false
+        };
+        let mut before = std::mem::take(children).to_vec();
+        let mut after = before.split_off(index + 1);
+        after.reverse();
+        let child = before.pop().unwrap();
+        let parent = std::mem::replace(&mut self.focus, child);
+        self.path.push(Crumb { parent, before, after });
+        true
+    }
+    pub fn to_first_child(&mut self) -> bool {
+        self.to_child(0)
+    }
+    pub fn to_last_child(&mut self) -> bool {
+        let length = children_mut(&mut self.focus).map_or(0, |x| x.len());
+        length > 0 && self.to_child(length - 1)
+    }
+    pub fn to_next_sibling(&mut self) -> bool {
+        let Some(crumb) = self.path.last_mut() else {
+            return false
+        };
+        let Some(next) = crumb.after.pop() else {
+            return false
+        };
+        crumb.before.push(std::mem::replace(&mut self.focus, next));
+        true
+    }
+    pub fn to_previous_sibling(&mut self) -> bool {
+        let Some(crumb) = self.path.last_mut() else {
+            return false
+        };
+        let Some(previous) = crumb.before.pop() else {
+            return false
+        };
+        crumb.after.push(std::mem::replace(&mut self.focus, previous));
+        true
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// EDITING
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Cursor {
+    /// Puts `node` in place of the node in focus, which is returned.
+    pub fn replace(&mut self, node: Node) -> Node {
+        std::mem::replace(&mut self.focus, node)
+    }
+    /// Adds a sibling just before the node in focus, which stays in focus.
+    ///
+    /// # Panics
+    ///
+    /// At the root.
+    pub fn insert_before(&mut self, node: Node) {
+        self.path.last_mut().expect("cannot insert next to the root").before.push(node);
+    }
+    /// Adds a sibling just after the node in focus, which stays in focus.
+    ///
+    /// # Panics
+    ///
+    /// At the root.
+    pub fn insert_after(&mut self, node: Node) {
+        self.path.last_mut().expect("cannot insert next to the root").after.push(node);
+    }
+    /// Puts the node in focus inside `wrapper`, after any children it has,
+    /// and moves to `wrapper`.
+    pub fn wrap(&mut self, mut wrapper: Element) {
+        wrapper.children.push(std::mem::replace(&mut self.focus, Node::empty()));
+        self.focus = Node::Element(wrapper);
+    }
+    /// Replaces the element or fragment in focus with its children and moves
+    /// to the first of them, or as [`Cursor::remove`] does if there are none.
+    /// At the root, an element becomes a fragment instead. Returns whether the
+    /// node in focus was an element or fragment.
+    pub fn unwrap(&mut self) -> bool {
+        let Some(children) = children_mut(&mut self.focus) else {
+            return false
+        };
+        let mut children = std::mem::take(children).to_vec();
+        let Some(crumb) = self.path.last_mut() else {
+            self.focus = Node::Fragment(Fragment::from_nodes(children));
+            return true
+        };
+        if children.is_empty() {
+            self.remove();
+            return true
+        }
+        crumb.after.extend(children.drain(1..).rev());
+        self.focus = children.pop().unwrap();
+        true
+    }
+    /// Takes the node in focus out of the tree and moves to its next sibling,
+    /// else its previous sibling, else its parent. Returns `None` at the root.
+    pub fn remove(&mut self) -> Option<Node> {
+        let crumb = self.path.last_mut()?;
+        let removed = match crumb.after.pop().or_else(|| crumb.before.pop()) {
+            Some(sibling) => std::mem::replace(&mut self.focus, sibling),
+            None => std::mem::replace(&mut self.focus, self.path.pop().unwrap().parent),
+        };
+        Some(removed)
+    }
+}
@@ -2,52 +2,75 @@ use pretty_tree::ToPrettyTree;
 use pretty_tree::PrettyTree;
 
 use crate::AttributeMap;
-use crate::ast::{Node, Element, Fragment};
+use crate::ast::{Node, Element, Fragment, DEBUG_DEPTH};
 
 impl ToPrettyTree for Node {
     fn to_pretty_tree(&self) -> PrettyTree {
-        match self {
-            Node::Text(x) => {
-                let x = x.trim();
-                if x.trim().is_empty() {
-                    return PrettyTree::Empty
-                }
-                PrettyTree::str(x)
-            },
-            Node::Element(x) => x.to_pretty_tree(),
-            Node::Fragment(x) => x.to_pretty_tree(),
-        }
+        node_tree(self, 0)
     }
 }
 
 impl ToPrettyTree for Element {
     fn to_pretty_tree(&self) -> PrettyTree {
-        let tag = self.tag.as_original();
-        let xs = self.attributes
-            .iter()
-            .map(|(k, v)| {
-                let k = k.as_str();
-                let v = v.as_str();
-                PrettyTree::key_value(k, v)
-            })
-            .chain({
-                self.children.iter().map(|x| x.to_pretty_tree())
-            })
-            .collect::<Vec<_>>();
-        PrettyTree::branch_of(tag, xs)
+        element_tree(self, 0)
     }
 }
 
 impl ToPrettyTree for Fragment {
     fn to_pretty_tree(&self) -> PrettyTree {
-        if self.is_empty() {
-            return PrettyTree::Empty
-        }
-        if self.len() == 1 {
-            return self.get(0).unwrap().to_pretty_tree()
-        }
-        PrettyTree::fragment(self.as_node_slice())
+        fragment_tree(self, 0)
+    }
+}
+
+/// The tree of `node`, `depth` fragments down; fragments past
+/// [`DEBUG_DEPTH`] are written as `…`.
+fn node_tree(node: &Node, depth: usize) -> PrettyTree {
+    match node {
+        Node::Text(x) => {
+            let x = x.trim();
+            if x.trim().is_empty() {
+                return PrettyTree::Empty
+            }
+            PrettyTree::str(x)
+        },
+        Node::Element(x) => element_tree(x, depth),
+        Node::Fragment(x) => fragment_tree(x, depth + 1),
+        Node::Comment(x) => PrettyTree::str(format!("<!--{x}-->")),
+        Node::Doctype(x) => PrettyTree::str(x.to_string()),
+        Node::ProcessingInstruction(x) => PrettyTree::str(x.to_string()),
+        Node::Placeholder(x) => PrettyTree::str(x.to_string()),
+    }
+}
+
+fn element_tree(element: &Element, depth: usize) -> PrettyTree {
+    let tag = element.tag.as_original();
+    let children = match depth + 1 {
+        depth if depth > DEBUG_DEPTH && !element.children.is_empty() => vec![PrettyTree::str("…")],
+        depth => element.children.iter().map(|x| node_tree(x, depth)).collect(),
+    };
+    let xs = element.attributes
+        .iter()
+        .map(|(k, v)| {
+            let k = k.as_str();
+            let v = v.as_str();
+            PrettyTree::key_value(k, v)
+        })
+        .chain(children)
+        .collect::<Vec<_>>();
+    PrettyTree::branch_of(tag, xs)
+}
+
+fn fragment_tree(fragment: &Fragment, depth: usize) -> PrettyTree {
+    if fragment.is_empty() {
+        return PrettyTree::Empty
+    }
+    if depth > DEBUG_DEPTH {
+        return PrettyTree::str("…")
+    }
+    if fragment.len() == 1 {
+        return node_tree(fragment.get(0).unwrap(), depth)
     }
+    PrettyTree::fragment(fragment.iter().map(|x| node_tree(x, depth)).collect::<Vec<_>>())
 }
 
 impl ToPrettyTree for AttributeMap {
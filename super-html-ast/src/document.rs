@@ -0,0 +1,398 @@
+use std::iter::FusedIterator;
+use crate::{Element, Fragment, Node, NodePath};
+
+// ————————————————————————————————————————————————————————————————————————————
+// DOCUMENT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A tree stored flat, with links between nodes, for passes that move around
+/// it: from a node to its parent, its siblings or its ancestors, and detaching
+/// or inserting nodes anywhere, all without walking from the root.
+///
+/// Nodes are referred to by [`NodeId`]s, which stay valid as the tree changes.
+/// Detached nodes (and their descendants) keep their ids and can be inserted
+/// again; their memory is only freed with the document.
+///
+/// Each node is kept as a [`Node`] without its children (elements and
+/// fragments have empty children here); the links make up the tree. Converting
+/// from and to [`Node`] moves everything, without cloning.
+///
+/// Inserting a node where it is an ancestor of the destination would make a
+/// cycle; that is a logic error, caught in debug builds only so that insertion
+/// stays constant-time.
+#[derive(Debug, Clone)]
+pub struct Document {
+    nodes: Vec<Node>,
+    links: Vec<Links>,
+    root: NodeId,
+}
+
+/// A node of a [`Document`]. Only meaningful for the document that made it;
+/// using it with another one may panic or refer to some other node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Links {
+    parent: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+}
+
+impl Document {
+    /// Takes the tree apart into a document whose root is `root`.
+    pub fn new(root: Node) -> Self {
+        let mut document = Self { nodes: Vec::new(), links: Vec::new(), root: NodeId(0) };
+        document.root = document.create(root);
+        document
+    }
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+    /// How many nodes the document holds, detached ones included.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    /// The node, without its children.
+    pub fn get(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+    /// The node, without its children. Children added to it are not part of
+    /// the document and are dropped on conversion; use [`Document::append`]
+    /// and the like.
+    pub fn get_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+    pub fn element(&self, id: NodeId) -> Option<&Element> {
+        self.get(id).as_element()
+    }
+    pub fn element_mut(&mut self, id: NodeId) -> Option<&mut Element> {
+        match self.get_mut(id) {
+            Node::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+    /// Whether the node is an element or fragment, which can have children.
+    pub fn is_container(&self, id: NodeId) -> bool {
+        matches!(self.get(id), Node::Element(_) | Node::Fragment(_))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// NAVIGATION
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Document {
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.0].parent
+    }
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.0].previous_sibling
+    }
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.0].next_sibling
+    }
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.0].first_child
+    }
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.0].last_child
+    }
+    pub fn children(&self, id: NodeId) -> Siblings<'_> {
+        Siblings { document: self, front: self.first_child(id), back: self.last_child(id) }
+    }
+    /// The parent of the node, its parent and so on up to the root (or the
+    /// top of a detached subtree).
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors { document: self, next: self.parent(id) }
+    }
+    /// The siblings after the node, nearest first.
+    pub fn following_siblings(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.next_sibling(id), |x| self.next_sibling(*x))
+    }
+    /// The siblings before the node, nearest first.
+    pub fn preceding_siblings(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.previous_sibling(id), |x| self.previous_sibling(*x))
+    }
+    /// The node and its descendants, in document order.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants { document: self, top: id, next: Some(id) }
+    }
+    /// The nearest ancestor that is an element matching `predicate`.
+    pub fn closest_element(&self, id: NodeId, mut predicate: impl FnMut(&Element) -> bool) -> Option<NodeId> {
+        self.ancestors(id).find(|x| self.element(*x).is_some_and(&mut predicate))
+    }
+    /// Whether `ancestor` is the node itself or one of its ancestors.
+    pub fn is_inclusive_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        id == ancestor || self.ancestors(id).any(|x| x == ancestor)
+    }
+    /// Where the node is below the root (or the top of its detached subtree),
+    /// as in the tree [`Document::to_node`] makes of it.
+    pub fn path(&self, id: NodeId) -> NodePath {
+        let mut path: Vec<usize> = std::iter::once(id)
+            .chain(self.ancestors(id))
+            .take_while(|x| self.parent(*x).is_some())
+            .map(|x| self.preceding_siblings(x).count())
+            .collect();
+        path.reverse();
+        NodePath::from(path)
+    }
+}
+
+/// The children of a node in a [`Document`] (see [`Document::children`]).
+pub struct Siblings<'a> {
+    document: &'a Document,
+    front: Option<NodeId>,
+    back: Option<NodeId>,
+}
+
+impl Iterator for Siblings<'_> {
+    type Item = NodeId;
+    fn next(&mut self) -> Option<NodeId> {
+        let next = self.front?;
+        if self.front == self.back {
+            (self.front, self.back) = (None, None);
+        } else {
+            self.front = self.document.next_sibling(next);
+        }
+        Some(next)
+    }
+}
+
+impl DoubleEndedIterator for Siblings<'_> {
+    fn next_back(&mut self) -> Option<NodeId> {
+        let next = self.back?;
+        if self.front == self.back {
+            (self.front, self.back) = (None, None);
+        } else {
+            self.back = self.document.previous_sibling(next);
+        }
+        Some(next)
+    }
+}
+
+impl FusedIterator for Siblings<'_> {}
+
+/// See [`Document::ancestors`].
+pub struct Ancestors<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+    fn next(&mut self) -> Option<NodeId> {
+        let next = self.next?;
+        self.next = self.document.parent(next);
+        Some(next)
+    }
+}
+
+impl FusedIterator for Ancestors<'_> {}
+
+/// See [`Document::descendants`].
+pub struct Descendants<'a> {
+    document: &'a Document,
+    top: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+    fn next(&mut self) -> Option<NodeId> {
+        let next = self.next?;
+        self.next = self.document.first_child(next).or_else(|| {
+            let mut id = next;
+            loop {
+                if id == self.top {
+                    return None
+                }
+                if let Some(sibling) = self.document.next_sibling(id) {
+                    return Some(sibling)
+                }
+                id = self.document.parent(id)?;
+            }
+        });
+        Some(next)
+    }
+}
+
+impl FusedIterator for Descendants<'_> {}
+
+// ————————————————————————————————————————————————————————————————————————————
+// MUTATION
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Document {
+    /// Adds a tree to the document, detached, returning the id of its top node.
+    pub fn create(&mut self, node: Node) -> NodeId {
+        let top = self.push(node);
+        // Nodes whose children are still to be added, with those children.
+        let mut stack = vec![(top, self.take_children(top).into_iter())];
+        while let Some((parent, children)) = stack.last_mut() {
+            let parent = *parent;
+            let Some(child) = children.next() else {
+                stack.pop();
+                continue
+            };
+            let id = self.push(child);
+            self.link(parent, self.last_child(parent), None, id);
+            stack.push((id, self.take_children(id).into_iter()));
+        }
+        top
+    }
+    /// Takes the node (and its descendants) out of its parent; it keeps its id.
+    pub fn detach(&mut self, id: NodeId) {
+        let links = &mut self.links[id.0];
+        let (parent, previous, next) = (links.parent.take(), links.previous_sibling.take(), links.next_sibling.take());
+        let Some(parent) = parent else {
+            return
+        };
+        match previous {
+            Some(previous) => self.links[previous.0].next_sibling = next,
+            None => self.links[parent.0].first_child = next,
+        }
+        match next {
+            Some(next) => self.links[next.0].previous_sibling = previous,
+            None => self.links[parent.0].last_child = previous,
+        }
+    }
+    /// Moves the node to the end of the children of `parent`.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not an element or fragment.
+    pub fn append(&mut self, parent: NodeId, id: NodeId) {
+        self.insert(parent, self.last_child(parent), None, id);
+    }
+    /// Moves the node to the start of the children of `parent`.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` is not an element or fragment.
+    pub fn prepend(&mut self, parent: NodeId, id: NodeId) {
+        self.insert(parent, None, self.first_child(parent), id);
+    }
+    /// Moves the node to just before `sibling`.
+    ///
+    /// # Panics
+    ///
+    /// If `sibling` has no parent.
+    pub fn insert_before(&mut self, sibling: NodeId, id: NodeId) {
+        let parent = self.parent(sibling).expect("cannot insert next to a node without a parent");
+        self.insert(parent, self.previous_sibling(sibling), Some(sibling), id);
+    }
+    /// Moves the node to just after `sibling`.
+    ///
+    /// # Panics
+    ///
+    /// If `sibling` has no parent.
+    pub fn insert_after(&mut self, sibling: NodeId, id: NodeId) {
+        let parent = self.parent(sibling).expect("cannot insert next to a node without a parent");
+        self.insert(parent, Some(sibling), self.next_sibling(sibling), id);
+    }
+    /// Swaps the node for another, leaving it detached.
+    pub fn replace(&mut self, id: NodeId, with: NodeId) {
+        if id != with && self.parent(id).is_some() {
+            self.insert_before(id, with);
+            self.detach(id);
+        }
+    }
+    /// Makes `root` the root of the document, detaching it first.
+    pub fn set_root(&mut self, root: NodeId) {
+        self.detach(root);
+        self.root = root;
+    }
+    fn insert(&mut self, parent: NodeId, previous: Option<NodeId>, next: Option<NodeId>, id: NodeId) {
+        assert!(self.is_container(parent), "only elements and fragments can have children");
+        debug_assert!(!self.is_inclusive_ancestor(id, parent), "cannot insert a node into itself");
+        // Moving a node next to itself: its own neighbours become the new ones.
+        let previous = if previous == Some(id) { self.previous_sibling(id) } else { previous };
+        let next = if next == Some(id) { self.next_sibling(id) } else { next };
+        self.detach(id);
+        self.link(parent, previous, next, id);
+    }
+    /// Links a detached node between adjacent children of `parent`.
+    fn link(&mut self, parent: NodeId, previous: Option<NodeId>, next: Option<NodeId>, id: NodeId) {
+        let links = &mut self.links[id.0];
+        (links.parent, links.previous_sibling, links.next_sibling) = (Some(parent), previous, next);
+        match previous {
+            Some(previous) => self.links[previous.0].next_sibling = Some(id),
+            None => self.links[parent.0].first_child = Some(id),
+        }
+        match next {
+            Some(next) => self.links[next.0].previous_sibling = Some(id),
+            None => self.links[parent.0].last_child = Some(id),
+        }
+    }
+    fn push(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        self.links.push(Links::default());
+        NodeId(self.nodes.len() - 1)
+    }
+    fn take_children(&mut self, id: NodeId) -> Fragment {
+        match &mut self.nodes[id.0] {
+            Node::Element(element) => std::mem::take(&mut element.children),
+            Node::Fragment(fragment) => std::mem::take(fragment),
+            _ => Fragment::default(),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// CONVERSION
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Document {
+    /// A copy of the node and its descendants as a [`Node`] tree.
+    pub fn to_node(&self, id: NodeId) -> Node {
+        build(&self.links, id, |id| self.nodes[id.0].clone())
+    }
+    /// The tree under the root as a [`Node`] tree; detached nodes are dropped.
+    pub fn into_node(self) -> Node {
+        let Self { mut nodes, links, root } = self;
+        build(&links, root, |id| std::mem::replace(&mut nodes[id.0], Node::empty()))
+    }
+}
+
+/// Puts the tree under `top` back together, getting each node from `take`.
+fn build(links: &[Links], top: NodeId, mut take: impl FnMut(NodeId) -> Node) -> Node {
+    // Nodes whose children are being built: the node, its children so far and
+    // the next child.
+    let mut stack = vec![(take(top), Vec::new(), links[top.0].first_child)];
+    loop {
+        let (_, _, next) = stack.last_mut().unwrap();
+        if let Some(id) = *next {
+            *next = links[id.0].next_sibling;
+            stack.push((take(id), Vec::new(), links[id.0].first_child));
+            continue
+        }
+        let (node, children, _) = stack.pop().unwrap();
+        let node = match node {
+            Node::Element(element) => Node::Element(Element { children: Fragment::from_nodes(children), ..element }),
+            Node::Fragment(_) => Node::Fragment(Fragment::from_nodes(children)),
+            leaf => leaf,
+        };
+        match stack.last_mut() {
+            Some((_, siblings, _)) => siblings.push(node),
+            None => return node,
+        }
+    }
+}
+
+impl From<Node> for Document {
+    fn from(root: Node) -> Self {
+        Self::new(root)
+    }
+}
+
+impl From<Document> for Node {
+    fn from(document: Document) -> Self {
+        document.into_node()
+    }
+}
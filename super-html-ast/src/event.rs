@@ -0,0 +1,297 @@
+use crate::{AttributeMap, CharacterReference, Doctype, Element, Fragment, Node, Placeholder, ProcessingInstruction, Span, TagBuf, Text};
+
+// ————————————————————————————————————————————————————————————————————————————
+// MARKUP EVENTS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A piece of markup in document order, for processing documents without
+/// holding their whole tree (see [`crate::parser::EventReader`] and
+/// [`crate::format::EventWriter`]).
+///
+/// Events are balanced: every [`Event::StartTag`] is followed, after the
+/// events of the element's contents, by an [`Event::EndTag`] — also for void
+/// and self-closing elements, and for end tags that were left implied.
+#[derive(Debug, Clone)]
+pub enum Event {
+    StartTag(StartTag),
+    EndTag(EndTag),
+    Text(Text),
+    Comment(String),
+    Doctype(Doctype),
+    ProcessingInstruction(ProcessingInstruction),
+    Placeholder(Placeholder),
+}
+
+impl Event {
+    pub fn as_start_tag(&self) -> Option<&StartTag> {
+        match self {
+            Self::StartTag(x) => Some(x),
+            _ => None,
+        }
+    }
+    pub fn as_end_tag(&self) -> Option<&EndTag> {
+        match self {
+            Self::EndTag(x) => Some(x),
+            _ => None,
+        }
+    }
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(x) => Some(x.as_str()),
+            _ => None,
+        }
+    }
+    /// Where this event was parsed from, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::StartTag(x) => x.span,
+            Self::EndTag(x) => x.span,
+            Self::Text(x) => x.span,
+            Self::Placeholder(x) => x.span,
+            Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) => None,
+        }
+    }
+}
+
+/// The start of an element: its tag and attributes, without its contents.
+#[derive(Debug, Clone)]
+pub struct StartTag {
+    pub tag: TagBuf,
+    pub attributes: AttributeMap,
+    /// Template placeholders among the attributes (see [`Element::placeholders`]).
+    pub placeholders: Vec<(usize, Placeholder)>,
+    /// Where the start tag was parsed from, if known.
+    pub span: Option<Span>,
+}
+
+impl StartTag {
+    pub fn new(tag: impl Into<TagBuf>) -> Self {
+        Self { tag: tag.into(), attributes: AttributeMap::default(), placeholders: Vec::new(), span: None }
+    }
+    pub fn with_attributes(mut self, attributes: AttributeMap) -> Self {
+        self.attributes.extend(attributes);
+        self
+    }
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
+    }
+    /// The element this tag starts, without children.
+    pub fn to_element(&self) -> Element {
+        Element {
+            attributes: self.attributes.clone(),
+            placeholders: self.placeholders.clone(),
+            ..Element::new(self.tag.clone())
+        }
+    }
+}
+
+/// The end of an element.
+#[derive(Debug, Clone)]
+pub struct EndTag {
+    pub tag: TagBuf,
+    /// Where the end tag was parsed from, if known; an empty span where an
+    /// implied end tag would go.
+    pub span: Option<Span>,
+}
+
+impl EndTag {
+    pub fn new(tag: impl Into<TagBuf>) -> Self {
+        Self { tag: tag.into(), span: None }
+    }
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TREES TO EVENTS
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Node {
+    /// The events describing this node, in document order. Fragments are
+    /// transparent; tags get no spans, as elements only know the span of the
+    /// whole element.
+    pub fn events(&self) -> NodeEvents<'_> {
+        NodeEvents { steps: vec![NodeEventStep::Node(self)] }
+    }
+    /// Builds the tree the `events` describe (see [`NodeBuilder`]).
+    pub fn from_events(events: impl IntoIterator<Item = Event>) -> Node {
+        Node::Fragment(Fragment::from_events(events))
+    }
+}
+
+impl Fragment {
+    /// The events describing the nodes of this fragment, in document order.
+    pub fn events(&self) -> NodeEvents<'_> {
+        NodeEvents { steps: self.iter().rev().map(NodeEventStep::Node).collect() }
+    }
+    /// Builds the nodes the `events` describe (see [`NodeBuilder`]).
+    pub fn from_events(events: impl IntoIterator<Item = Event>) -> Fragment {
+        let mut builder = NodeBuilder::new();
+        for event in events {
+            builder.push(event);
+        }
+        builder.finish()
+    }
+}
+
+/// Iterator over the events of a tree, see [`Node::events`].
+pub struct NodeEvents<'a> {
+    /// Pending work, the next step last.
+    steps: Vec<NodeEventStep<'a>>,
+}
+
+enum NodeEventStep<'a> {
+    Node(&'a Node),
+    EndTag(&'a TagBuf),
+}
+
+impl Iterator for NodeEvents<'_> {
+    type Item = Event;
+    fn next(&mut self) -> Option<Event> {
+        loop {
+            let node = match self.steps.pop()? {
+                NodeEventStep::Node(node) => node,
+                NodeEventStep::EndTag(tag) => return Some(Event::EndTag(EndTag::new(tag.clone()))),
+            };
+            let event = match node {
+                Node::Element(element) => {
+                    self.steps.push(NodeEventStep::EndTag(&element.tag));
+                    self.steps.extend(element.children.iter().rev().map(NodeEventStep::Node));
+                    Event::StartTag(StartTag {
+                        tag: element.tag.clone(),
+                        attributes: element.attributes.clone(),
+                        placeholders: element.placeholders.clone(),
+                        span: None,
+                    })
+                }
+                Node::Fragment(fragment) => {
+                    self.steps.extend(fragment.iter().rev().map(NodeEventStep::Node));
+                    continue
+                }
+                Node::Text(text) => Event::Text(text.clone()),
+                Node::Comment(comment) => Event::Comment(comment.clone()),
+                Node::Doctype(doctype) => Event::Doctype(doctype.clone()),
+                Node::ProcessingInstruction(pi) => Event::ProcessingInstruction(pi.clone()),
+                Node::Placeholder(placeholder) => Event::Placeholder(placeholder.clone()),
+            };
+            return Some(event)
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// EVENTS TO TREES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Assembles events into nodes, one event at a time.
+///
+/// Adjacent text is merged, as the parser does. An end tag closes the
+/// innermost open element with a matching tag, along with any elements opened
+/// inside it; one that matches nothing is ignored. Whatever is still open when
+/// the builder is finished is closed then. An element spans from its start
+/// tag through its end tag, if both have spans.
+///
+/// To keep only some subtrees of a large document, feed a fresh builder from
+/// a start tag of interest until [`NodeBuilder::depth`] is back to zero:
+///
+/// ```
+/// # use super_html_ast::{parser, NodeBuilder};
+/// # use super_html_ast::parser::ParserOptions;
+/// # fn process(_: super_html_ast::Fragment) {}
+/// # fn main() -> std::io::Result<()> {
+/// # let source = "<feed><item>a</item><item>b</item></feed>";
+/// let mut builder = None::<NodeBuilder>;
+/// for event in parser::parse_events(&source, &ParserOptions::native()) {
+///     let event = event?;
+///     if builder.is_none() && event.as_start_tag().is_some_and(|x| x.tag.as_normalized() == "item") {
+///         builder = Some(NodeBuilder::new());
+///     }
+///     if let Some(current) = builder.as_mut() {
+///         current.push(event);
+///         if current.depth() == 0 {
+///             process(builder.take().unwrap().finish());
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct NodeBuilder {
+    root: Fragment,
+    /// Open elements, each with the span of its start tag.
+    stack: Vec<(Element, Option<Span>)>,
+}
+
+impl NodeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// How many elements are open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+    pub fn push(&mut self, event: Event) {
+        match event {
+            Event::StartTag(start) => {
+                let span = start.span;
+                let element = Element { span, ..start.to_element() };
+                self.stack.push((element, span));
+            }
+            Event::EndTag(end) => {
+                let Some(position) = self.stack.iter().rposition(|(open, _)| open.tag.matches(&end.tag)) else {
+                    return
+                };
+                while self.stack.len() > position + 1 {
+                    self.close(None);
+                }
+                self.close(end.span);
+            }
+            Event::Text(text) => self.text(text),
+            Event::Comment(comment) => self.current_children().push(Node::Comment(comment)),
+            Event::Doctype(doctype) => self.current_children().push(Node::Doctype(doctype)),
+            Event::ProcessingInstruction(pi) => self.current_children().push(Node::ProcessingInstruction(pi)),
+            Event::Placeholder(placeholder) => self.current_children().push(Node::Placeholder(placeholder)),
+        }
+    }
+    /// Closes everything still open and returns the nodes.
+    pub fn finish(mut self) -> Fragment {
+        while !self.stack.is_empty() {
+            self.close(None);
+        }
+        std::mem::take(&mut self.root)
+    }
+    fn current_children(&mut self) -> &mut Fragment {
+        match self.stack.last_mut() {
+            Some((open, _)) => &mut open.children,
+            None => &mut self.root,
+        }
+    }
+    fn text(&mut self, text: Text) {
+        let children = self.current_children();
+        let last = children.len().checked_sub(1);
+        if let Some(Node::Text(previous)) = last.and_then(|ix| children.get_mut(ix))
+            && !previous.cdata
+            && !text.cdata {
+            let offset = previous.value.len();
+            let references = text.references.into_iter().map(|x| CharacterReference { offset: x.offset + offset, ..x });
+            previous.references.extend(references);
+            previous.value.push_str(&text.value);
+            previous.span = previous.span.zip(text.span).map(|(previous, span)| previous.merge(&span));
+            previous.raw = None;
+            return
+        }
+        children.push(Node::Text(text));
+    }
+    /// Closes the innermost open element, at an end tag spanning `end`.
+    fn close(&mut self, end: Option<Span>) {
+        let Some((mut element, start)) = self.stack.pop() else {
+            return
+        };
+        element.span = start.zip(end).map(|(start, end)| start.merge(&end));
+        self.current_children().push(Node::Element(element));
+    }
+}
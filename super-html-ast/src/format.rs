@@ -1,7 +1,11 @@
 #![allow(unused)]
 // use std::collections::{BTreeMap, HashMap};
 
-use crate::{AttributeMap, Element, Fragment, Node, TagBuf};
+use std::io::Write;
+use crate::{AttributeMap, CharacterReference, Doctype, Element, Event, Fragment, Node, Placeholder, StartTag, TagBuf, Text};
+use crate::parser::PlaceholderSyntax;
+use crate::reference::{segments, Segment};
+use crate::constants::{MATHML_NAMESPACE, SVG_NAMESPACE};
 
 mod pretty_html;
 
@@ -10,7 +14,49 @@ mod pretty_html;
 // ————————————————————————————————————————————————————————————————————————————
 
 #[derive(Debug, Clone, Default)]
-pub struct FormatSettings {}
+pub struct FormatSettings {
+    /// Emit the source text kept by lossless parsing (see [`crate::RawSource`])
+    /// verbatim for every text, tag and doctype that was not modified since,
+    /// and fresh markup only for what was.
+    pub lossless: bool,
+    /// How to write the character references kept by the parser (see
+    /// [`crate::CharacterReference`]).
+    pub references: ReferenceStyle,
+    /// Write template placeholders inside text and attribute names and values
+    /// verbatim instead of escaping them. Placeholder nodes and placeholders
+    /// between attributes are always written verbatim.
+    pub placeholders: Option<PlaceholderSyntax>,
+}
+
+impl FormatSettings {
+    pub fn lossless() -> Self {
+        Self { lossless: true, ..Self::default() }
+    }
+    pub fn with_references(mut self, style: ReferenceStyle) -> Self {
+        self.references = style;
+        self
+    }
+    pub fn with_placeholders(mut self, syntax: PlaceholderSyntax) -> Self {
+        self.placeholders = Some(syntax);
+        self
+    }
+}
+
+/// How [`FormatSettings`] writes kept character references. Characters that
+/// must be escaped (`&`, `<`, `"`, …) are escaped regardless.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReferenceStyle {
+    /// Exactly as written in the source.
+    #[default]
+    AsWritten,
+    /// As named references (`&nbsp;`), falling back to numeric ones for
+    /// characters without a name.
+    Named,
+    /// As decimal numeric references (`&#160;`); numeric references keep their written form.
+    Numeric,
+    /// As the characters they stand for.
+    Literal,
+}
 
 // ————————————————————————————————————————————————————————————————————————————
 // INTERNAL HELPERS
@@ -112,9 +158,14 @@ impl FormatEnvironment {
 // ————————————————————————————————————————————————————————————————————————————
 
 impl Node {
+    /// Formats the node as a complete document. The parsed doctype is emitted
+    /// as-is; `<!DOCTYPE html>` is only prepended when the tree has none.
     pub fn format_document(&self) -> String {
-        let doc_type = "<!DOCTYPE html>";
         let html = self.format(FormatSettings::default());
+        if self.find_doctype().is_some() {
+            return html
+        }
+        let doc_type = Doctype::html5();
         format!("{doc_type}\n{html}")
     }
     pub fn format_document_pretty(&self) -> String {
@@ -124,6 +175,11 @@ impl Node {
         let environment = FormatEnvironment::new(settings);
         self.render_impl(&environment)
     }
+    /// Formats the node reproducing the parsed source wherever it is unchanged;
+    /// see [`FormatSettings::lossless`].
+    pub fn format_lossless(&self) -> String {
+        self.format(FormatSettings::lossless())
+    }
     pub fn pretty_format(&self) -> String {
         let format_settings = FormatSettings::default();
         let string = self.format(format_settings);
@@ -135,43 +191,96 @@ impl Node {
     }
 }
 
+// ————————————————————————————————————————————————————————————————————————————
+// EVENT WRITER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Writes [`Event`]s as markup to `output`, the way [`Node::format`] writes
+/// the tree they describe (see [`crate::parser::EventReader`]). Tags are always
+/// written fresh, as events do not carry their source text.
+///
+/// ```
+/// # use super_html_ast::format::EventWriter;
+/// # fn main() -> std::io::Result<()> {
+/// # let node = super_html_ast::Node::text("hi");
+/// let mut writer = EventWriter::new(std::io::stdout().lock());
+/// for event in node.events() {
+///     writer.write(&event)?;
+/// }
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct EventWriter<W: Write> {
+    output: W,
+    environment: FormatEnvironment,
+    /// The last start tag, held back until it is known whether its end tag
+    /// follows right away (see [`closes_itself`]).
+    pending: Option<StartTag>,
+}
+
+impl<W: Write> EventWriter<W> {
+    pub fn new(output: W) -> Self {
+        Self::with_settings(output, FormatSettings::default())
+    }
+    pub fn with_settings(output: W, settings: FormatSettings) -> Self {
+        Self { output, environment: FormatEnvironment::new(settings), pending: None }
+    }
+    pub fn write(&mut self, event: &Event) -> std::io::Result<()> {
+        if let Event::EndTag(end) = event
+            && let Some(start) = self.pending.take_if(|start| start.tag.matches(&end.tag)) {
+            let markup = match closes_itself(&start.tag) {
+                true => format_start_tag(&start.tag, &start.attributes, &start.placeholders, &self.environment, true),
+                false => {
+                    let start_tag = format_start_tag(&start.tag, &start.attributes, &start.placeholders, &self.environment, false);
+                    format!("{start_tag}{}", format_end_tag(&end.tag))
+                }
+            };
+            return self.output.write_all(markup.as_bytes())
+        }
+        self.flush_pending()?;
+        let markup = match event {
+            Event::StartTag(start) => {
+                self.pending = Some(start.clone());
+                return Ok(())
+            }
+            Event::EndTag(end) => format_end_tag(&end.tag),
+            Event::Text(text) => format_text(text, &self.environment),
+            Event::Comment(comment) => format!("<!--{comment}-->"),
+            Event::Doctype(doctype) => format_doctype(doctype, &self.environment),
+            Event::ProcessingInstruction(pi) => pi.to_string(),
+            Event::Placeholder(placeholder) => placeholder.to_string(),
+        };
+        self.output.write_all(markup.as_bytes())
+    }
+    /// Writes a pending start tag, flushes and returns the output.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.flush_pending()?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+    fn flush_pending(&mut self) -> std::io::Result<()> {
+        let Some(start) = self.pending.take() else {
+            return Ok(())
+        };
+        let markup = format_start_tag(&start.tag, &start.attributes, &start.placeholders, &self.environment, false);
+        self.output.write_all(markup.as_bytes())
+    }
+}
+
 // ————————————————————————————————————————————————————————————————————————————
 // INTERNAL — IMPLEMENTATION
 // ————————————————————————————————————————————————————————————————————————————
 
 impl Node {
     fn render_impl(&self, environment: &FormatEnvironment) -> String {
-        match self {
-            Self::Text(text) => {
-                html_escape::encode_double_quoted_attribute(text.as_str()).to_string()
-            },
-            Self::Element(element) => element.render_impl(environment),
-            Self::Fragment(fragment) => fragment.render_impl(environment),
-        }
+        render(vec![RenderStep::Node(self, environment.clone())])
     }
 }
 
 impl Element {
     fn render_impl(&self, environment: &FormatEnvironment) -> String {
-        let environment = environment.scope(&self.tag);
-        // let level = environment.indent_spacing_string();
-        let attributes = format_attributes(&self.attributes, &environment);
-        if crate::constants::is_void_tag(&self.tag) && self.children.len() == 0 {
-            format!(
-                "<{tag}{attributes} />",
-                tag=self.tag.as_original(),
-            )
-        } else {
-            // let environment = environment.with_escape_tokens()
-            let children = format_fragment(&self.children, &environment);
-            let contents = {
-                children
-            };
-            format!(
-                "<{tag}{attributes}>{contents}</{tag}>",
-                tag=self.tag.as_original(),
-            )
-        }
+        render(vec![RenderStep::Element(self, environment.clone())])
     }
 }
 
@@ -181,6 +290,138 @@ impl Fragment {
     }
 }
 
+/// Pending work for [`render`].
+enum RenderStep<'a> {
+    Node(&'a Node, FormatEnvironment),
+    Element(&'a Element, FormatEnvironment),
+    EndTag(&'a TagBuf),
+    /// Source text reused verbatim.
+    Raw(&'a str),
+}
+
+/// Renders the `steps` (the last one first) into a single string. Children
+/// and end tags are pushed back onto the work stack instead of being rendered
+/// recursively, so nesting depth is bounded only by memory.
+fn render(mut steps: Vec<RenderStep<'_>>) -> String {
+    let mut output = String::new();
+    while let Some(step) = steps.pop() {
+        match step {
+            RenderStep::Node(node, environment) => match node {
+                Node::Text(text) => output.push_str(&format_text(text, &environment)),
+                Node::Element(element) => steps.push(RenderStep::Element(element, environment)),
+                Node::Fragment(fragment) => push_children(&mut steps, fragment, &environment),
+                Node::Comment(comment) => output.push_str(&format!("<!--{comment}-->")),
+                Node::Doctype(doctype) => output.push_str(&format_doctype(doctype, &environment)),
+                Node::ProcessingInstruction(pi) => output.push_str(&pi.to_string()),
+                Node::Placeholder(placeholder) => output.push_str(&placeholder.to_string()),
+            },
+            RenderStep::Element(element, environment) if let Some((start, end)) = raw_tags(element, &environment) => {
+                output.push_str(start);
+                if let Some(end) = end {
+                    steps.push(RenderStep::Raw(end));
+                }
+                let environment = environment.scope(&element.tag);
+                push_children(&mut steps, &element.children, &environment);
+            }
+            RenderStep::Element(element, environment) => {
+                let environment = environment.scope(&element.tag);
+                let (tag, attributes, placeholders) = (&element.tag, &element.attributes, &element.placeholders);
+                if closes_itself(&element.tag) && element.children.is_empty() {
+                    output.push_str(&format_start_tag(tag, attributes, placeholders, &environment, true));
+                } else {
+                    output.push_str(&format_start_tag(tag, attributes, placeholders, &environment, false));
+                    let raw_end = element.raw.as_ref()
+                        .and_then(|raw| raw.end.as_ref())
+                        .filter(|_| environment.settings.lossless)
+                        .and_then(|raw| raw.for_end_tag(element));
+                    match raw_end {
+                        Some(raw) => steps.push(RenderStep::Raw(raw)),
+                        None => steps.push(RenderStep::EndTag(&element.tag)),
+                    }
+                    push_children(&mut steps, &element.children, &environment);
+                }
+            }
+            RenderStep::EndTag(tag) => output.push_str(&format_end_tag(tag)),
+            RenderStep::Raw(raw) => output.push_str(raw),
+        }
+    }
+    output
+}
+
+fn format_text(text: &Text, environment: &FormatEnvironment) -> String {
+    if let Some(raw) = raw_text(text, environment) {
+        return raw.to_string()
+    }
+    if text.cdata {
+        // `]]>` cannot occur inside a section, so split it across two.
+        let value = text.as_str().replace("]]>", "]]]]><![CDATA[>");
+        return format!("<![CDATA[{value}]]>")
+    }
+    encode_with_references(&text.value, &text.references, environment)
+}
+
+fn format_doctype(doctype: &Doctype, environment: &FormatEnvironment) -> String {
+    match doctype.raw.as_ref().filter(|_| environment.settings.lossless) {
+        Some(raw) if let Some(raw) = raw.for_doctype(doctype) => raw.to_string(),
+        _ => format!("{doctype}\n"),
+    }
+}
+
+/// `<tag …>`, or `<tag … />` if `self_closing`.
+fn format_start_tag(
+    tag: &TagBuf,
+    attributes: &AttributeMap,
+    placeholders: &[(usize, Placeholder)],
+    environment: &FormatEnvironment,
+    self_closing: bool,
+) -> String {
+    let attributes = format_attributes(attributes, placeholders, environment);
+    let tag = tag.as_original();
+    match self_closing {
+        true => format!("<{tag}{attributes} />"),
+        false => format!("<{tag}{attributes}>"),
+    }
+}
+
+fn format_end_tag(tag: &TagBuf) -> String {
+    format!("</{}>", tag.as_original())
+}
+
+/// Whether an element without children is written as a self-closing tag:
+/// void elements, and foreign (SVG and MathML) ones.
+fn closes_itself(tag: &TagBuf) -> bool {
+    let is_foreign = matches!(tag.namespace(), Some(SVG_NAMESPACE | MATHML_NAMESPACE));
+    crate::constants::is_void_tag(tag) || is_foreign
+}
+
+/// The source text of an unmodified text node, in lossless mode.
+fn raw_text<'a>(text: &'a Text, environment: &FormatEnvironment) -> Option<&'a str> {
+    if !environment.settings.lossless {
+        return None
+    }
+    text.raw.as_ref()?.for_text(&text.value, text.cdata)
+}
+
+/// The source start tag and end tag (if the source had one) of an element
+/// whose tag and attributes are unmodified, in lossless mode.
+fn raw_tags<'a>(element: &'a Element, environment: &FormatEnvironment) -> Option<(&'a str, Option<&'a str>)> {
+    if !environment.settings.lossless {
+        return None
+    }
+    let raw = element.raw.as_ref()?;
+    let start = raw.start.for_start_tag(element)?;
+    // A self-closing tag cannot take the children added since.
+    if start.ends_with("/>") && !element.children.is_empty() {
+        return None
+    }
+    Some((start, raw.end.as_ref().map(|end| end.as_str())))
+}
+
+/// Schedules the nodes of `fragment` to be rendered next, in order.
+fn push_children<'a>(steps: &mut Vec<RenderStep<'a>>, fragment: &'a Fragment, environment: &FormatEnvironment) {
+    steps.extend(fragment.iter().rev().map(|child| RenderStep::Node(child, environment.clone())));
+}
+
 
 // ————————————————————————————————————————————————————————————————————————————
 // INTERNAL — UTILITIES
@@ -195,36 +436,75 @@ fn indent_spacing_string(level: usize) -> String {
 }
 
 fn format_fragment(nodes: &Fragment, environment: &FormatEnvironment) -> String {
-    let xs = nodes
-        .iter()
-        .map(|child| {
-            let environment = environment.clone();
-            child.render_impl(&environment)
-        })
-        .collect::<Vec<_>>();
-    if xs.is_empty() {
-        String::new()
-    } else {
-        xs.join("")
+    let mut steps = Vec::with_capacity(nodes.len());
+    push_children(&mut steps, nodes, environment);
+    render(steps)
+}
+
+/// Escapes `value`, writing the `references` that still apply to it as
+/// [`FormatSettings::references`] asks.
+fn encode_with_references(value: &str, references: &[CharacterReference], environment: &FormatEnvironment) -> String {
+    if references.is_empty() {
+        return escape(value, environment)
     }
+    let mut output = String::with_capacity(value.len());
+    for segment in segments(value, references) {
+        match segment {
+            Segment::Text(text) => output.push_str(&escape(text, environment)),
+            Segment::Reference(reference) => match environment.settings.references {
+                ReferenceStyle::AsWritten => output.push_str(&reference.source),
+                ReferenceStyle::Named => {
+                    let named = reference.to_named().unwrap_or_else(|| reference.to_numeric());
+                    output.push_str(&named);
+                }
+                ReferenceStyle::Numeric => output.push_str(&reference.to_numeric()),
+                ReferenceStyle::Literal => {
+                    output.push_str(&html_escape::encode_double_quoted_attribute(&reference.resolved));
+                }
+            },
+        }
+    }
+    output
+}
+
+/// Escapes `text` for text or a double quoted attribute, leaving the template
+/// placeholders [`FormatSettings::placeholders`] recognizes as they are.
+fn escape(text: &str, environment: &FormatEnvironment) -> String {
+    let Some(syntax) = environment.settings.placeholders.as_ref() else {
+        return html_escape::encode_double_quoted_attribute(text).into_owned()
+    };
+    let mut output = String::with_capacity(text.len());
+    let mut copied = 0;
+    for placeholder in syntax.find_iter(text) {
+        output.push_str(&html_escape::encode_double_quoted_attribute(&text[copied..placeholder.start]));
+        output.push_str(&text[placeholder.clone()]);
+        copied = placeholder.end;
+    }
+    output.push_str(&html_escape::encode_double_quoted_attribute(&text[copied..]));
+    output
 }
 
 fn format_attributes(
     attributes: &AttributeMap,
+    placeholders: &[(usize, Placeholder)],
     environment: &FormatEnvironment,
 ) -> String {
-    let attributes = attributes
-        .into_iter()
-        .map(|(key, value)| {
-            let key = html_escape::encode_double_quoted_attribute(key.as_str()).to_string();
-            let value = html_escape::encode_double_quoted_attribute(value.as_str()).to_string();
-            format!("{key}={value:?}")
-        })
-        .collect::<Vec<_>>();
-    if attributes.is_empty() {
+    let mut placeholders = placeholders.iter().peekable();
+    let mut parts = Vec::with_capacity(attributes.len() + placeholders.len());
+    for (index, (key, value)) in attributes.iter().enumerate() {
+        while let Some((_, placeholder)) = placeholders.next_if(|(position, _)| *position <= index) {
+            parts.push(placeholder.to_string());
+        }
+        let key = escape(key.as_str(), environment);
+        let value = encode_with_references(value.as_str(), value.references(), environment);
+        parts.push(format!("{key}=\"{value}\""));
+    }
+    // Placeholders after the last attribute, or past the end if attributes were removed since.
+    parts.extend(placeholders.map(|(_, placeholder)| placeholder.to_string()));
+    if parts.is_empty() {
         String::new()
     } else {
-        format!(" {}", attributes.join(" "))
+        format!(" {}", parts.join(" "))
     }
 }
 
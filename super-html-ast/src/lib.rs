@@ -2,11 +2,35 @@ extern crate super_markdown_ast as markdown_ast;
 
 mod attrs;
 mod tag;
+mod atom;
 mod ast;
+mod span;
+mod raw;
+mod reference;
+mod event;
+mod selector;
+mod compare;
+mod binary;
+mod document;
+mod cursor;
+mod path;
+#[cfg(feature = "serde")]
+mod serialize;
 
 pub use attrs::*;
 pub use tag::*;
+pub use atom::*;
 pub use ast::*;
+pub use span::*;
+pub use raw::*;
+pub use reference::*;
+pub use event::*;
+pub use selector::*;
+pub use compare::*;
+pub use binary::*;
+pub use document::*;
+pub use cursor::*;
+pub use path::*;
 
 pub mod parser;
 pub mod text_format;
@@ -16,5 +40,7 @@ pub mod visitors;
 pub mod format;
 pub mod constants;
 pub mod query;
+pub mod borrowed;
+pub mod rewriter;
 
 pub mod debug;
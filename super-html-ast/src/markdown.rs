@@ -49,6 +49,10 @@ impl Node {
             Self::Text(text) => {
                 vec![markdown_ast::MdNode::Inline(markdown_ast::MdInlineNode::Text(text.to_string()))]
             }
+            Self::Placeholder(placeholder) => {
+                vec![markdown_ast::MdNode::Inline(markdown_ast::MdInlineNode::Text(placeholder.to_string()))]
+            }
+            Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) => Vec::new(),
         }
     }
     fn to_md_inline_nodes(&self) -> Vec<markdown_ast::MdInlineNode> {
@@ -59,13 +63,19 @@ impl Node {
                 let md = markdown_ast::MdInlineNode::Text(text.to_string());
                 vec![md]
             }
+            Self::Placeholder(placeholder) => {
+                let md = markdown_ast::MdInlineNode::Text(placeholder.to_string());
+                vec![md]
+            }
+            Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) => Vec::new(),
         }
     }
     fn md_list_items(&self) -> Vec<markdown_ast::MdListItemNode> {
         match self {
             Self::Element(element) => vec![element.md_list_item()],
             Self::Fragment(fragment) => fragment.md_list_items(),
-            Self::Text(_) => Vec::new(),
+            Self::Text(_) | Self::Placeholder(_) => Vec::new(),
+            Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) => Vec::new(),
         }
     }
 }
@@ -1,31 +1,97 @@
-use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, Fragment, Node, TagBuf};
+use std::io::Read;
+use html5ever::tendril::TendrilSink;
+use html5ever::{local_name, namespace_url, ns, QualName};
+use crate::constants::{HTML_NAMESPACE, MATHML_NAMESPACE, SVG_NAMESPACE};
+use crate::{Atom, AttributeKeyBuf, AttributeMap, AttributeValueBuf, Doctype, Element, Fragment, LineIndex, Node, ProcessingInstruction, TagBuf};
+
+mod borrowed;
+mod diagnostic;
+mod encoding;
+mod incremental;
+mod limits;
+mod locate;
+mod options;
+mod placeholders;
+mod sink;
+mod stream;
+pub mod native;
+
+pub use borrowed::parse_borrowed;
+pub use diagnostic::*;
+pub use encoding::*;
+pub use incremental::*;
+pub use limits::{LimitAction, LimitKind, ParseLimits};
+pub use options::*;
+pub use stream::*;
+
+use limits::LimitTracker;
+use native::tokenizer::Syntax;
 
 #[derive(Debug, Clone)]
 pub struct ParseResult<T> {
     output: T,
-    errors: Vec<String>,
+    diagnostics: Vec<ParseDiagnostic>,
+    encoding: Option<DetectedEncoding>,
 }
 
 impl<T> ParseResult<T> {
-    pub fn errors(&self) -> &[String] {
-        self.errors.as_ref()
+    /// The encoding byte input was decoded with; `None` when parsing text.
+    pub fn encoding(&self) -> Option<DetectedEncoding> {
+        self.encoding
+    }
+    /// All diagnostics, warnings included.
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        self.diagnostics.as_ref()
+    }
+    /// Diagnostics with [`Severity::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &ParseDiagnostic> {
+        self.diagnostics.iter().filter(|x| x.is_error())
+    }
+    /// Diagnostics with [`Severity::Warning`].
+    pub fn warnings(&self) -> impl Iterator<Item = &ParseDiagnostic> {
+        self.diagnostics.iter().filter(|x| !x.is_error())
+    }
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+    /// Drops, downgrades or upgrades diagnostics according to `policy`.
+    pub fn apply_policy(self, policy: &DiagnosticPolicy) -> Self {
+        let diagnostics = self.diagnostics
+            .into_iter()
+            .filter_map(|x| policy.apply(x))
+            .collect::<Vec<_>>();
+        Self { diagnostics, ..self }
     }
     pub fn log_errors(&self) {
-        for error in self.errors.iter() {
-            eprintln!("⚠️ {error}")
+        for diagnostic in self.diagnostics.iter() {
+            eprintln!("⚠️ {diagnostic}")
         }
     }
+    /// Renders every diagnostic as a code frame against the parsed `source`.
+    pub fn format_diagnostics(&self, source: impl AsRef<str>) -> String {
+        let source = source.as_ref();
+        self.diagnostics
+            .iter()
+            .map(|x| x.code_frame(source))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
     pub fn expect(self, message: impl AsRef<str>) -> T {
         let message = message.as_ref();
-        if !self.errors.is_empty() {
+        if self.has_errors() {
             self.log_errors();
             panic!("{}", message)
         }
         self.output
     }
-    pub fn to_result(self) -> Result<T, Vec<String>> {
-        if !self.errors.is_empty() {
-            return Err(self.errors)
+    /// Fails with the error diagnostics, if any; warnings are discarded.
+    pub fn to_result(self) -> Result<T, Vec<ParseDiagnostic>> {
+        if self.has_errors() {
+            let errors = self.diagnostics
+                .into_iter()
+                .filter(|x| x.is_error())
+                .collect::<Vec<_>>();
+            return Err(errors)
         }
         Ok(self.output)
     }
@@ -36,88 +102,376 @@ impl<T> ParseResult<T> {
 
 impl ParseResult<Node> {
     pub fn html(&self) -> Option<&Node> {
-        if self.errors.is_empty() {
-            Some(&self.output)
-        } else {
+        if self.has_errors() {
             None
+        } else {
+            Some(&self.output)
         }
     }
     pub fn transform(self, apply: impl FnOnce(Node) -> Node) -> Self {
-        Self { output: apply(self.output), errors: self.errors }
+        Self { output: apply(self.output), ..self }
     }
 }
 
+/// The outcome of [`parse_document_bytes`] and the other byte-oriented entry
+/// points.
+#[derive(Debug, Clone)]
+pub struct DecodedParse {
+    pub result: ParseResult<Node>,
+    /// The decoded text, which the spans of the tree and the diagnostics
+    /// point into (see [`ParseResult::format_diagnostics`]).
+    pub source: String,
+}
+
+/// Parses an HTML fragment as the contents of a `<body>`. The result is a
+/// [`Node::Fragment`] of every top-level node, text included. Elements, text
+/// and attributes carry source spans (see [`Node::span`]).
 pub fn parse_from_fragment(source: impl AsRef<str>) -> ParseResult<Node> {
-    let result = scraper::Html::parse_fragment(source.as_ref());
-    transform_scraper_html(result).transform(|node| {
+    parse_fragment_with(source, &ParserOptions::default().with_locate_spans(true))
+}
+
+/// Parses an HTML fragment as the contents of `context`, the way assigning to
+/// the context element's `innerHTML` would: `<tr>` snippets need `tbody`,
+/// `<option>`s need `select`, SVG shapes need `svg`, and so on.
+///
+/// ```
+/// # use super_html_ast::parser::{parse_fragment_in, ParseDiagnostic};
+/// # fn main() -> Result<(), Vec<ParseDiagnostic>> {
+/// let rows = parse_fragment_in("<tr><td>1</td></tr>", "tbody").to_result()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_fragment_in(source: impl AsRef<str>, context: impl Into<TagBuf>) -> ParseResult<Node> {
+    parse_fragment_with(source, &ParserOptions::default().with_locate_spans(true).with_fragment_context(context))
+}
+
+/// Parses a complete HTML document. Elements, text and attributes carry source
+/// spans (see [`Node::span`]).
+pub fn parse_from_document(source: impl AsRef<str>) -> ParseResult<Node> {
+    parse_document_with(source, &ParserOptions::default().with_locate_spans(true))
+}
+
+/// Like [`parse_from_fragment`], with the given options.
+pub fn parse_fragment_with(source: impl AsRef<str>, options: &ParserOptions) -> ParseResult<Node> {
+    let mut limits = LimitTracker::new(&options.limits);
+    let Some(source) = limits.admit_input(source.as_ref()) else {
+        return ParseResult { output: Node::empty(), diagnostics: limits.diagnostics, encoding: None }
+    };
+    let context = options.fragment_context.as_ref();
+    match options.backend {
+        ParserBackend::Html5 => match options.placeholders.as_ref() {
+            Some(syntax) => placeholders::parse_html5(source, syntax, options.scripting_enabled, |source| {
+                parse_html5_fragment(source, options, limits)
+            }),
+            None => parse_html5_fragment(source, options, limits),
+        },
+        ParserBackend::Native => native::parse_with(source, Syntax::Html, context, limits, options),
+        ParserBackend::Xml => native::parse_with(source, Syntax::Xml, None, limits, options),
+    }
+}
+
+/// Like [`parse_from_document`], with the given options. The native and XML
+/// backends do not distinguish documents from fragments.
+pub fn parse_document_with(source: impl AsRef<str>, options: &ParserOptions) -> ParseResult<Node> {
+    let mut limits = LimitTracker::new(&options.limits);
+    let Some(source) = limits.admit_input(source.as_ref()) else {
+        return ParseResult { output: Node::empty(), diagnostics: limits.diagnostics, encoding: None }
+    };
+    match options.backend {
+        ParserBackend::Html5 => match options.placeholders.as_ref() {
+            Some(syntax) => placeholders::parse_html5(source, syntax, options.scripting_enabled, |source| {
+                parse_html5_document(source, options, limits)
+            }),
+            None => parse_html5_document(source, options, limits),
+        },
+        ParserBackend::Native => native::parse_with(source, Syntax::Html, None, limits, options),
+        ParserBackend::Xml => native::parse_with(source, Syntax::Xml, None, limits, options),
+    }
+}
+
+/// Parses an XML document: names keep their case, `<x/>` closes any element,
+/// CDATA sections and processing instructions are kept, namespace prefixes are
+/// resolved, and well-formedness errors are reported as diagnostics.
+pub fn parse_from_xml(source: impl AsRef<str>) -> ParseResult<Node> {
+    parse_document_with(source, &ParserOptions::xml())
+}
+
+/// Decodes `bytes` (see [`decode_html`]; [`decode_xml`] for the XML backend)
+/// and parses them as a document. The detected encoding is available from
+/// [`ParseResult::encoding`]; decoding problems come first among the
+/// diagnostics, with spans into the decoded text.
+pub fn parse_document_bytes(bytes: impl AsRef<[u8]>, options: &ParserOptions) -> DecodedParse {
+    parse_bytes(bytes.as_ref(), options, |text, options| parse_document_with(text, options))
+}
+
+/// Like [`parse_document_bytes`], parsing a fragment.
+pub fn parse_fragment_bytes(bytes: impl AsRef<[u8]>, options: &ParserOptions) -> DecodedParse {
+    parse_bytes(bytes.as_ref(), options, |text, options| parse_fragment_with(text, options))
+}
+
+/// Reads `reader` to the end and parses it with [`parse_document_bytes`].
+/// Reading stops early past [`ParseLimits::max_input_bytes`].
+pub fn parse_document_reader(reader: impl Read, options: &ParserOptions) -> std::io::Result<DecodedParse> {
+    let bytes = read_limited(reader, &options.limits)?;
+    Ok(parse_document_bytes(bytes, options))
+}
+
+/// Reads `reader` to the end and parses it with [`parse_fragment_bytes`].
+/// Reading stops early past [`ParseLimits::max_input_bytes`].
+pub fn parse_fragment_reader(reader: impl Read, options: &ParserOptions) -> std::io::Result<DecodedParse> {
+    let bytes = read_limited(reader, &options.limits)?;
+    Ok(parse_fragment_bytes(bytes, options))
+}
+
+/// Reads at most one byte more than the input limit, so that exceeding it is still detected.
+fn read_limited(mut reader: impl Read, limits: &ParseLimits) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match limits.max_input_bytes {
+        Some(limit) => reader.take(limit as u64 + 1).read_to_end(&mut bytes)?,
+        None => reader.read_to_end(&mut bytes)?,
+    };
+    Ok(bytes)
+}
+
+fn parse_bytes(
+    bytes: &[u8],
+    options: &ParserOptions,
+    parse: impl FnOnce(&str, &ParserOptions) -> ParseResult<Node>,
+) -> DecodedParse {
+    // The input limit applies to the bytes; the decoded text is not checked again.
+    let mut limits = LimitTracker::new(&options.limits);
+    let Some(len) = limits.admit_input_len(bytes.len()) else {
+        let result = ParseResult { output: Node::empty(), diagnostics: limits.diagnostics, encoding: None };
+        return DecodedParse { result, source: String::new() }
+    };
+    let bytes = &bytes[..len];
+    let mut options = options.clone();
+    options.limits.max_input_bytes = None;
+    let options = &options;
+    let transport_charset = options.transport_charset.as_deref();
+    let decoded = match options.backend {
+        ParserBackend::Html5 | ParserBackend::Native => decode_html(bytes, transport_charset),
+        ParserBackend::Xml => decode_xml(bytes, transport_charset),
+    };
+    let result = parse(&decoded.text, options);
+    let mut diagnostics = limits.diagnostics;
+    diagnostics.extend(decoded.diagnostics);
+    diagnostics.extend(result.diagnostics);
+    let result = ParseResult { output: result.output, diagnostics, encoding: Some(decoded.encoding) };
+    DecodedParse { result, source: decoded.text }
+}
+
+fn parse_html5_fragment(source: &str, options: &ParserOptions, limits: LimitTracker) -> ParseResult<Node> {
+    let context = match options.fragment_context.as_ref() {
+        Some(context) => context_name(context),
+        None => QualName::new(None, ns!(html), local_name!("body")),
+    };
+    let sink = sink::LocatingSink::new(scraper::Html::new_fragment());
+    let result = html5ever::driver::parse_fragment(sink, html5_options(options.scripting_enabled), context, Vec::new()).one(source);
+    // html5ever parents the fragment's nodes to a synthetic `<html>` root.
+    transform_scraper_html(result, source, options, limits).transform(|node| {
         let nodes = node
             .flatten()
             .into_iter()
-            .flat_map(|x| x.extract_elements())
-            .map(|element| {
-                let tag = TagBuf::from("html");
-                if element.tag.matches(&tag) {
-                    return Node::Fragment(element.children)
+            .flat_map(|node| match node {
+                Node::Element(element) if element.tag.matches(&TagBuf::from("html")) => {
+                    element.children.flatten()
                 }
-                Node::Element(element)
+                node => vec![node],
             })
-            .flat_map(|x| x.flatten())
             .collect::<Vec<_>>();
         Node::Fragment(Fragment::from_nodes(nodes))
     })
 }
 
-pub fn parse_from_document(source: impl AsRef<str>) -> ParseResult<Node> {
-    let result = scraper::Html::parse_document(source.as_ref());
-    transform_scraper_html(result)
+/// The html5ever name of a fragment context. Without an explicit namespace,
+/// `svg` and `math` are foreign elements and everything else is HTML.
+fn context_name(context: &TagBuf) -> QualName {
+    let namespace = match (context.namespace(), context.as_normalized()) {
+        (Some(namespace), _) => namespace,
+        (None, "svg") => SVG_NAMESPACE,
+        (None, "math") => MATHML_NAMESPACE,
+        (None, _) => HTML_NAMESPACE,
+    };
+    let local = match namespace {
+        HTML_NAMESPACE => context.local_name().to_ascii_lowercase(),
+        _ => context.local_name().to_string(),
+    };
+    QualName::new(None, html5ever::Namespace::from(namespace), html5ever::LocalName::from(local))
 }
 
-fn transform_scraper_html(html: scraper::Html) -> ParseResult<Node> {
-    let errors = html.errors
+fn parse_html5_document(source: &str, options: &ParserOptions, limits: LimitTracker) -> ParseResult<Node> {
+    let sink = sink::LocatingSink::new(scraper::Html::new_document());
+    let result = html5ever::driver::parse_document(sink, html5_options(options.scripting_enabled)).one(source);
+    transform_scraper_html(result, source, options, limits)
+}
+
+fn html5_options(scripting_enabled: bool) -> html5ever::ParseOpts {
+    let tree_builder = html5ever::tree_builder::TreeBuilderOpts { scripting_enabled, ..Default::default() };
+    html5ever::ParseOpts { tree_builder, ..Default::default() }
+}
+
+fn transform_scraper_html(
+    (html, errors): (scraper::Html, Vec<sink::RawParseError>),
+    source: &str,
+    options: &ParserOptions,
+    mut limits: LimitTracker,
+) -> ParseResult<Node> {
+    let index = LineIndex::new(source);
+    let mut diagnostics = errors
         .iter()
-        .map(|x| x.to_string())
+        .map(|x| diagnostic::from_html5ever(&x.message, x.line, &index))
         .collect::<Vec<_>>();
     let root = html.tree.root();
-    let converted = convert_ego_tree(root);
-    ParseResult { output: converted, errors }
+    let mut converted = convert_ego_tree(root, &mut limits).unwrap_or_else(Node::empty);
+    // Placeholders are put back in place by their spans, and lossless source
+    // text and character references are found by them.
+    if options.locate_spans || options.placeholders.is_some() || options.lossless || options.character_references {
+        locate::locate(&mut converted, source, options);
+    }
+    diagnostics.append(&mut limits.diagnostics);
+    ParseResult { output: converted, diagnostics, encoding: None }
+}
+
+/// `prefix:local`, or just `local` (html5ever only sets prefixes on foreign
+/// attributes such as `xlink:href` and `xml:lang`).
+fn qualified_name(name: &QualName) -> String {
+    match name.prefix.as_ref() {
+        Some(prefix) => format!("{prefix}:{}", name.local),
+        None => name.local.to_string(),
+    }
+}
+
+fn namespace_uri(name: &QualName) -> Option<String> {
+    Some(name.ns.to_string()).filter(|x| !x.is_empty())
+}
+
+/// Converts an html5ever tree. Returns `None` if `limits` drop the root.
+/// Open elements are kept on an explicit stack, as nesting is unbounded.
+fn convert_ego_tree(root: ego_tree::NodeRef<'_, scraper::node::Node>, limits: &mut LimitTracker) -> Option<Node> {
+    let mut stack = Vec::new();
+    let mut converted = enter_ego_node(root, 0, limits, &mut stack);
+    if stack.is_empty() {
+        return converted
+    }
+    loop {
+        let frame = stack.last_mut().unwrap();
+        if let Some(node) = converted.take() {
+            frame.children.push(node);
+        }
+        match frame.pending.next() {
+            Some(child) => {
+                let depth = frame.depth;
+                converted = enter_ego_node(child, depth, limits, &mut stack);
+            }
+            None => {
+                let EgoFrame { shell, children, .. } = stack.pop().unwrap();
+                let children = Fragment::from_nodes(children);
+                let node = match shell {
+                    Node::Element(element) => Node::Element(Element { children, ..element }),
+                    _ => Node::Fragment(children),
+                };
+                if stack.is_empty() {
+                    return Some(node)
+                }
+                converted = Some(node);
+            }
+        }
+    }
+}
+
+/// An element or fragment whose children are being converted.
+struct EgoFrame<'a> {
+    /// The converted node, still without children.
+    shell: Node,
+    /// How many elements the children are nested in.
+    depth: usize,
+    pending: ego_tree::iter::Children<'a, scraper::node::Node>,
+    children: Vec<Node>,
 }
 
-fn convert_ego_tree(node: ego_tree::NodeRef<'_, scraper::node::Node>) -> Node {
-    match node.value() {
+/// Converts a leaf nested in `depth` elements, or opens a frame for an
+/// element or fragment (returning `None`). Also `None` for nodes dropped by `limits`.
+fn enter_ego_node<'a>(
+    node: ego_tree::NodeRef<'a, scraper::node::Node>,
+    depth: usize,
+    limits: &mut LimitTracker,
+    stack: &mut Vec<EgoFrame<'a>>,
+) -> Option<Node> {
+    // The `<html>` root html5ever gives fragments is unwrapped later and not
+    // counted. Template contents are fragments too, but never the root.
+    let is_synthetic = node.parent().is_some_and(|parent| {
+        matches!(parent.value(), scraper::node::Node::Fragment) && parent.parent().is_none()
+    });
+    let is_container = is_synthetic || matches!(node.value(), scraper::node::Node::Document | scraper::node::Node::Fragment);
+    let is_element = matches!(node.value(), scraper::node::Node::Element(_));
+    let depth = if is_element && !is_synthetic { depth + 1 } else { depth };
+    if is_element && !limits.admit_depth(depth, None) {
+        return None
+    }
+    if !is_container && !limits.admit_node(None) {
+        return None
+    }
+    let leaf = match node.value() {
         scraper::node::Node::Text(text) => {
-            Node::text(text.to_string())
+            let mut text = text.to_string();
+            limits.truncate_text(&mut text, None);
+            Node::text(text)
         }
 
         scraper::node::Node::Element(element) => {
-            let tag = TagBuf::new(element.name.local.to_string());
+            let name = &element.name;
+            let tag = TagBuf::new(qualified_name(name))
+                .with_namespace(namespace_uri(name).map(Atom::from));
 
+            let keep = limits.admit_attributes(element.attrs.len(), None);
             let attributes: AttributeMap = element.attrs.iter()
+                .take(keep)
                 .map(|(key, value)| {
+                    let mut value = value.to_string();
+                    limits.truncate_attribute(&mut value, None);
                     (
-                        AttributeKeyBuf::from(key.local.to_string()),
-                        AttributeValueBuf::literal(value.to_string()),
+                        AttributeKeyBuf::new(qualified_name(key)).with_namespace(namespace_uri(key).map(Atom::from)),
+                        AttributeValueBuf::literal(value),
                     )
                 })
                 .collect();
 
-            let children: Fragment = Fragment::from_iter(
-                node.children().map(convert_ego_tree)
-            );
+            // html5ever keeps the contents of a `<template>` in a fragment of
+            // their own, its only child; they become the element's children.
+            let contents = node.first_child().filter(|x| matches!(x.value(), scraper::node::Node::Fragment));
+            let pending = contents.unwrap_or(node).children();
+            let shell = Node::Element(Element::new(tag).with_attributes(attributes));
+            stack.push(EgoFrame { shell, depth, pending, children: Vec::new() });
+            return None
+        }
 
-            Node::element(tag, attributes, children)
+        scraper::node::Node::Comment(comment) => {
+            Node::comment(comment.comment.to_string())
         }
 
-        scraper::node::Node::Comment(_) | scraper::node::Node::Doctype(_) | scraper::node::Node::Document | scraper::node::Node::Fragment => {
-            let children: Fragment = Fragment::from_iter(
-                node.children().map(convert_ego_tree)
-            );
-            Node::Fragment(children)
+        scraper::node::Node::Doctype(doctype) => {
+            Node::Doctype(Doctype {
+                name: doctype.name().to_string(),
+                public_id: doctype.public_id().to_string(),
+                system_id: doctype.system_id().to_string(),
+                raw: None,
+            })
         }
 
-        scraper::node::Node::ProcessingInstruction(_) => {
-            // You can choose to skip this, or add a new Node variant if needed.
-            Node::empty()
+        scraper::node::Node::Document | scraper::node::Node::Fragment => {
+            let shell = Node::Fragment(Fragment::default());
+            stack.push(EgoFrame { shell, depth, pending: node.children(), children: Vec::new() });
+            return None
         }
 
-    }
+        scraper::node::Node::ProcessingInstruction(pi) => {
+            Node::ProcessingInstruction(ProcessingInstruction {
+                target: pi.target.to_string(),
+                data: pi.data.to_string(),
+            })
+        }
+
+    };
+    Some(leaf)
 }
@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+use crate::borrowed::{Attribute, Element, Fragment, Node, Tag, Text};
+use crate::constants::{is_template_tag, HTML_NAMESPACE, MATHML_NAMESPACE, SVG_NAMESPACE, XLINK_NAMESPACE, XMLNS_NAMESPACE, XML_NAMESPACE};
+use crate::{Doctype, Placeholder, ProcessingInstruction, Span, TagBuf};
+use super::native::{build, TextRun, TreeNodes};
+use super::native::tokenizer::{AttributeToken, Syntax, TagToken};
+use super::{DiagnosticCode, LimitTracker, ParseDiagnostic, ParseResult, ParserBackend, ParserOptions, Severity};
+
+/// Parses `source` into a [`crate::borrowed`] tree, whose names, attribute
+/// values and text are slices of `source` wherever they appear in it verbatim.
+///
+/// The tree is built as the native parser builds it, or the XML parser with
+/// the XML backend. The HTML5 backend cannot borrow: the native parser stands
+/// in for it, with a warning. Lossless source text and character references
+/// are not kept either, and asking for them is warned about too.
+pub fn parse_borrowed<'a>(source: &'a str, options: &ParserOptions) -> ParseResult<Node<'a>> {
+    let unsupported = [
+        (options.backend == ParserBackend::Html5, "Borrowed parsing uses the native parser; the HTML5 backend is not supported"),
+        (options.lossless, "Lossless parsing is not supported by borrowed parsing"),
+        (options.character_references, "Keeping character references is not supported by borrowed parsing"),
+    ];
+    let warnings = unsupported
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, message)| ParseDiagnostic::new(DiagnosticCode::UnsupportedOption, message).with_severity(Severity::Warning))
+        .collect::<Vec<_>>();
+    let mut limits = LimitTracker::new(&options.limits);
+    let Some(source) = limits.admit_input(source) else {
+        let diagnostics = warnings.into_iter().chain(limits.diagnostics).collect();
+        return ParseResult { output: Node::Fragment(Fragment::default()), diagnostics, encoding: None }
+    };
+    let syntax = match options.backend {
+        ParserBackend::Xml => Syntax::Xml,
+        ParserBackend::Html5 | ParserBackend::Native => Syntax::Html,
+    };
+    let context = options.fragment_context.as_ref().filter(|_| syntax == Syntax::Html);
+    // Resolving references into owned text only to drop them would defeat borrowing.
+    let options = ParserOptions { character_references: false, ..options.clone() };
+    let mut result = build(source, syntax, context, limits, &options, BorrowedNodes { source });
+    result.diagnostics.splice(0..0, warnings);
+    result
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TREE BUILDER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Builds [`crate::borrowed`] nodes for the native tree builder (see
+/// [`super::native::TreeBuilder`]).
+struct BorrowedNodes<'a> {
+    source: &'a str,
+}
+
+impl<'a> TreeNodes<'a> for BorrowedNodes<'a> {
+    type Node = Node<'a>;
+    type Element = Element<'a>;
+    type Fragment = Fragment<'a>;
+    fn push(fragment: &mut Fragment<'a>, node: Node<'a>) {
+        fragment.push(node);
+    }
+    fn has_element(fragment: &Fragment<'a>) -> bool {
+        fragment.iter().any(|x| x.as_element().is_some())
+    }
+    fn children<'e>(element: &'e mut Element<'a>) -> &'e mut Fragment<'a> {
+        &mut element.children
+    }
+    fn fragment(fragment: Fragment<'a>) -> Node<'a> {
+        Node::Fragment(fragment)
+    }
+    fn element(&self, tag: TagBuf, token: &TagToken<'a>, _: &'a str) -> Element<'a> {
+        let namespace = tag.namespace().map(shared_namespace);
+        Element {
+            // An XML element without a namespace is not HTML.
+            template_contents: is_template_tag(&tag) && tag.namespace().is_some(),
+            ..Element::new(Tag::new(token.name.clone()).with_namespace(namespace))
+        }
+    }
+    fn attribute(element: &mut Element<'a>, attribute: AttributeToken<'a>, namespace: Option<String>, _: Span) {
+        let namespace = namespace.as_deref().map(shared_namespace);
+        element.attributes.push(Attribute { name: attribute.name, value: attribute.value, namespace });
+    }
+    fn placeholder_attribute(_: &mut Element<'a>, _: usize, _: Placeholder) {}
+    fn end_tag(&self, _: &mut Element<'a>, _: &'a str) {}
+    fn close(mut element: Element<'a>, span: Span) -> Node<'a> {
+        element.span = Some(span);
+        Node::Element(element)
+    }
+    fn text(&self, children: &mut Fragment<'a>, text: TextRun<'a>, limits: &mut LimitTracker) {
+        let TextRun { mut value, cdata, span, .. } = text;
+        if let Some(Node::Text(previous)) = children.iter_mut().last()
+            && !previous.cdata
+            && !cdata {
+            match join(self.source, &previous.value, &value) {
+                Some(joined) => previous.value = Cow::Borrowed(joined),
+                None => previous.value.to_mut().push_str(&value),
+            }
+            previous.span = previous.span.map(|x| x.merge(&span));
+            limits.truncate_text(&mut previous.value, Some(span));
+            return
+        }
+        if !limits.admit_node(Some(span)) {
+            return
+        }
+        limits.truncate_text(&mut value, Some(span));
+        children.push(Node::Text(Text { value, span: Some(span), cdata }));
+    }
+    fn comment(comment: Cow<'a, str>) -> Node<'a> {
+        Node::Comment(comment)
+    }
+    fn doctype(&self, doctype: Doctype, _: &'a str) -> Node<'a> {
+        Node::Doctype(doctype)
+    }
+    fn processing_instruction(pi: ProcessingInstruction) -> Node<'a> {
+        Node::ProcessingInstruction(pi)
+    }
+    fn placeholder(placeholder: Placeholder) -> Node<'a> {
+        Node::Placeholder(placeholder)
+    }
+}
+
+/// Namespace URIs the parser assigns itself are shared rather than copied.
+fn shared_namespace(uri: &str) -> Cow<'static, str> {
+    let known = [HTML_NAMESPACE, SVG_NAMESPACE, MATHML_NAMESPACE, XLINK_NAMESPACE, XML_NAMESPACE, XMLNS_NAMESPACE];
+    match known.into_iter().find(|x| *x == uri) {
+        Some(known) => Cow::Borrowed(known),
+        None => Cow::Owned(uri.to_string()),
+    }
+}
+
+/// `left` and `right` as one slice of `source`, if they are adjacent slices of it.
+fn join<'a>(source: &'a str, left: &str, right: &str) -> Option<&'a str> {
+    let start = (left.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+    let end = start + left.len() + right.len();
+    let adjacent = left.as_ptr().wrapping_add(left.len()) == right.as_ptr();
+    (adjacent && end <= source.len()).then(|| &source[start..end])
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use crate::{LineIndex, Span};
+
+// ————————————————————————————————————————————————————————————————————————————
+// DIAGNOSTIC CODES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The class of malformed markup a diagnostic reports.
+///
+/// Codes render as stable kebab-case identifiers (see [`DiagnosticCode::as_str`]),
+/// which is what policies and CI configuration should refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DiagnosticCode {
+    /// A token that is not allowed where it appears.
+    UnexpectedToken,
+    /// A character that is not allowed where it appears (e.g. in a tag name).
+    UnexpectedCharacter,
+    /// The input ended in the middle of a construct.
+    UnexpectedEof,
+    /// An end tag without a matching open element.
+    UnmatchedEndTag,
+    /// An element left open when its parent was closed.
+    UnclosedElement,
+    /// Overlapping or wrongly ordered tags (`<b><i></b></i>`).
+    MisnestedTags,
+    /// An element nested in itself where that is not allowed (`<form>`, `<button>`, headings, …).
+    InvalidNesting,
+    /// Text or elements inside a `<table>` that must be moved out of it.
+    MisplacedTableContent,
+    /// The same attribute given twice on one element.
+    DuplicateAttribute,
+    /// An end tag with attributes.
+    EndTagWithAttributes,
+    /// A self-closing slash where it has no effect (`</p/>`, `<div/>`).
+    InvalidSelfClosingTag,
+    /// A malformed or unknown character reference (`&#xZZ;`, `&bogus;`).
+    InvalidCharacterReference,
+    /// A malformed or misplaced `<!DOCTYPE>`.
+    BadDoctype,
+    /// Bytes that are not valid in the document's character encoding.
+    InvalidByteSequence,
+    /// A charset label that names no known encoding.
+    UnsupportedEncoding,
+    /// A [`ParseLimits`](super::ParseLimits) limit was exceeded.
+    LimitExceeded,
+    /// A namespace prefix without an `xmlns:prefix` declaration in scope (XML only).
+    UndeclaredNamespacePrefix,
+    /// A [`ParserOptions`](super::ParserOptions) setting the chosen backend
+    /// does not support, and ignored.
+    UnsupportedOption,
+    /// Anything not covered above.
+    Other,
+}
+
+impl DiagnosticCode {
+    pub const ALL: &'static [DiagnosticCode] = &[
+        Self::UnexpectedToken,
+        Self::UnexpectedCharacter,
+        Self::UnexpectedEof,
+        Self::UnmatchedEndTag,
+        Self::UnclosedElement,
+        Self::MisnestedTags,
+        Self::InvalidNesting,
+        Self::MisplacedTableContent,
+        Self::DuplicateAttribute,
+        Self::EndTagWithAttributes,
+        Self::InvalidSelfClosingTag,
+        Self::InvalidCharacterReference,
+        Self::BadDoctype,
+        Self::InvalidByteSequence,
+        Self::UnsupportedEncoding,
+        Self::LimitExceeded,
+        Self::UndeclaredNamespacePrefix,
+        Self::UnsupportedOption,
+        Self::Other,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnexpectedToken => "unexpected-token",
+            Self::UnexpectedCharacter => "unexpected-character",
+            Self::UnexpectedEof => "unexpected-eof",
+            Self::UnmatchedEndTag => "unmatched-end-tag",
+            Self::UnclosedElement => "unclosed-element",
+            Self::MisnestedTags => "misnested-tags",
+            Self::InvalidNesting => "invalid-nesting",
+            Self::MisplacedTableContent => "misplaced-table-content",
+            Self::DuplicateAttribute => "duplicate-attribute",
+            Self::EndTagWithAttributes => "end-tag-with-attributes",
+            Self::InvalidSelfClosingTag => "invalid-self-closing-tag",
+            Self::InvalidCharacterReference => "invalid-character-reference",
+            Self::BadDoctype => "bad-doctype",
+            Self::InvalidByteSequence => "invalid-byte-sequence",
+            Self::UnsupportedEncoding => "unsupported-encoding",
+            Self::LimitExceeded => "limit-exceeded",
+            Self::UndeclaredNamespacePrefix => "undeclared-namespace-prefix",
+            Self::UnsupportedOption => "unsupported-option",
+            Self::Other => "other",
+        }
+    }
+    /// Parses a kebab-case code identifier.
+    pub fn from_name(name: impl AsRef<str>) -> Option<Self> {
+        let name = name.as_ref();
+        Self::ALL.iter().copied().find(|code| code.as_str() == name)
+    }
+    /// Classifies an html5ever error message.
+    pub(super) fn classify(message: &str) -> Self {
+        let starts_with = |prefixes: &[&str]| prefixes.iter().any(|x| message.starts_with(x));
+        if starts_with(&["Unexpected characters", "Non-space table text"]) {
+            return Self::MisplacedTableContent
+        }
+        if starts_with(&["Unexpected open element"]) {
+            return Self::UnclosedElement
+        }
+        if starts_with(&["Unexpected token", "Unexpected open tag"]) {
+            return Self::UnexpectedToken
+        }
+        if starts_with(&["Unexpected EOF", "Saw EOF"]) {
+            return Self::UnexpectedEof
+        }
+        if starts_with(&["Bad character", "Saw "]) {
+            return Self::UnexpectedCharacter
+        }
+        if message.contains("character reference") {
+            return Self::InvalidCharacterReference
+        }
+        if message.contains("DOCTYPE") {
+            return Self::BadDoctype
+        }
+        if starts_with(&["Duplicate attribute"]) {
+            return Self::DuplicateAttribute
+        }
+        if starts_with(&["Attributes on an end tag"]) {
+            return Self::EndTagWithAttributes
+        }
+        if starts_with(&["Self-closing end tag", "Unacknowledged self-closing tag"]) {
+            return Self::InvalidSelfClosingTag
+        }
+        if starts_with(&["No ", "</body> with no", "</html> with no", "Form element not in scope", "Null form element", "Bad open element"]) {
+            return Self::UnmatchedEndTag
+        }
+        if starts_with(&["Formatting element", "Closing wrong heading", "Found special tag", "expected to close"]) {
+            return Self::MisnestedTags
+        }
+        if starts_with(&["nested ", "Nested "]) {
+            return Self::InvalidNesting
+        }
+        Self::Other
+    }
+}
+
+impl Display for DiagnosticCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// DIAGNOSTICS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => f.write_str("warning"),
+            Self::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// A problem found while parsing.
+#[derive(Debug, Clone)]
+pub struct ParseDiagnostic {
+    pub code: DiagnosticCode,
+    pub message: String,
+    pub severity: Severity,
+    /// Where the problem was found. Diagnostics from the HTML5 backend only know
+    /// the line, so their span covers that whole line.
+    pub span: Option<Span>,
+}
+
+impl ParseDiagnostic {
+    pub fn new(code: DiagnosticCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), severity: Severity::Error, span: None }
+    }
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+    pub fn with_span(mut self, span: impl Into<Option<Span>>) -> Self {
+        self.span = span.into();
+        self
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+    /// Renders the diagnostic followed by the offending source line with a caret
+    /// underline, e.g.
+    ///
+    /// ```text
+    /// error[duplicate-attribute]: Duplicate attribute
+    ///  --> 3:13
+    ///   |
+    /// 3 | <div id="a" id="b">
+    ///   |             ^^^^^^
+    /// ```
+    pub fn code_frame(&self, source: &str) -> String {
+        let header = format!("{}[{}]: {}", self.severity, self.code, self.message);
+        let Some(span) = self.span else {
+            return header
+        };
+        let index = LineIndex::new(source);
+        let Some(line) = index.line_text(span.start.line) else {
+            return header
+        };
+        let gutter = " ".repeat(span.start.line.to_string().len());
+        // Reuse the line's own tabs so the caret lines up however tabs are rendered.
+        let padding = line
+            .chars()
+            .take(span.start.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let line_chars = line.chars().count();
+        let end_column = if span.end.line == span.start.line {
+            span.end.column
+        } else {
+            line_chars + 1
+        };
+        let width = end_column
+            .min(line_chars + 1)
+            .saturating_sub(span.start.column)
+            .max(1);
+        let carets = "^".repeat(width);
+        [
+            header,
+            format!("{gutter}--> {}", span.start),
+            format!("{gutter} |"),
+            format!("{} | {line}", span.start.line),
+            format!("{gutter} | {padding}{carets}"),
+        ].join("\n")
+    }
+}
+
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(span) = self.span {
+            write!(f, " ({})", span.start)?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts an html5ever error into a diagnostic spanning its (trimmed) source line.
+pub(super) fn from_html5ever(message: &str, line: u64, index: &LineIndex<'_>) -> ParseDiagnostic {
+    let code = DiagnosticCode::classify(message);
+    let span = index.line_text(line as usize).and_then(|text| {
+        let line_start = index.line_start(line as usize)?;
+        let indent = text.len() - text.trim_start().len();
+        let start = line_start + indent;
+        let end = line_start + text.trim_end().len().max(indent);
+        Some(index.span(start..end))
+    });
+    ParseDiagnostic::new(code, message).with_span(span)
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// POLICY
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticLevel {
+    /// Drop the diagnostic.
+    Ignore,
+    /// Keep the diagnostic as a warning.
+    Warn,
+    /// Keep the diagnostic as an error (fails `expect`, `to_result`, …).
+    Deny,
+}
+
+/// Decides, per [`DiagnosticCode`], which diagnostics are dropped, kept as
+/// warnings or treated as errors.
+///
+/// ```
+/// # use super_html_ast::parser::*;
+/// # fn main() -> Result<(), Vec<ParseDiagnostic>> {
+/// # let source = "<table>x<tr><td>1</td></tr></table>";
+/// let policy = DiagnosticPolicy::default()
+///     .with_default(DiagnosticLevel::Warn)
+///     .deny(DiagnosticCode::DuplicateAttribute)
+///     .ignore(DiagnosticCode::MisplacedTableContent);
+/// let node = parse_from_document(source).apply_policy(&policy).to_result()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticPolicy {
+    default: Option<DiagnosticLevel>,
+    levels: HashMap<DiagnosticCode, DiagnosticLevel>,
+}
+
+impl DiagnosticPolicy {
+    /// A policy that drops every diagnostic.
+    pub fn ignore_all() -> Self {
+        Self::default().with_default(DiagnosticLevel::Ignore)
+    }
+    /// The level for codes without an explicit level. When unset, diagnostics
+    /// keep the severity they were reported with.
+    pub fn with_default(mut self, level: DiagnosticLevel) -> Self {
+        self.default = Some(level);
+        self
+    }
+    pub fn with_level(mut self, code: DiagnosticCode, level: DiagnosticLevel) -> Self {
+        self.levels.insert(code, level);
+        self
+    }
+    pub fn ignore(self, code: DiagnosticCode) -> Self {
+        self.with_level(code, DiagnosticLevel::Ignore)
+    }
+    pub fn warn(self, code: DiagnosticCode) -> Self {
+        self.with_level(code, DiagnosticLevel::Warn)
+    }
+    pub fn deny(self, code: DiagnosticCode) -> Self {
+        self.with_level(code, DiagnosticLevel::Deny)
+    }
+    pub fn level(&self, code: DiagnosticCode) -> Option<DiagnosticLevel> {
+        self.levels.get(&code).copied().or(self.default)
+    }
+    /// Applies the policy to a single diagnostic; `None` means it is ignored.
+    pub fn apply(&self, diagnostic: ParseDiagnostic) -> Option<ParseDiagnostic> {
+        match self.level(diagnostic.code) {
+            None => Some(diagnostic),
+            Some(DiagnosticLevel::Ignore) => None,
+            Some(DiagnosticLevel::Warn) => Some(diagnostic.with_severity(Severity::Warning)),
+            Some(DiagnosticLevel::Deny) => Some(diagnostic.with_severity(Severity::Error)),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt::Display;
+use std::ops::Range;
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use crate::LineIndex;
+use super::{DiagnosticCode, ParseDiagnostic, Severity};
+
+/// How many leading bytes are searched for a `<meta>` charset declaration.
+pub(crate) const PRESCAN_LENGTH: usize = 1024;
+
+// ————————————————————————————————————————————————————————————————————————————
+// DETECTED ENCODINGS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Where the character encoding of a byte input was determined from, in the
+/// order the sniffing rules consult them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncodingSource {
+    /// A UTF-8 or UTF-16 byte order mark.
+    ByteOrderMark,
+    /// The charset given by the caller, e.g. from a `Content-Type` header.
+    TransportHint,
+    /// `<meta charset="…">`.
+    MetaCharset,
+    /// `<meta http-equiv="Content-Type" content="…; charset=…">`.
+    MetaHttpEquiv,
+    /// `<?xml … encoding="…"?>` (XML backend only).
+    XmlDeclaration,
+    /// Nothing was declared, but the input is valid UTF-8.
+    Utf8Heuristic,
+    /// Nothing was declared: windows-1252 for HTML, UTF-8 for XML.
+    Default,
+}
+
+/// The character encoding a byte input was decoded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectedEncoding {
+    pub encoding: &'static Encoding,
+    pub source: EncodingSource,
+}
+
+impl DetectedEncoding {
+    /// The WHATWG name of the encoding (`UTF-8`, `windows-1252`, `Shift_JIS`, …).
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+}
+
+impl Display for DetectedEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// DECODING
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Text decoded from bytes, ready to be parsed.
+#[derive(Debug, Clone)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: DetectedEncoding,
+    /// Unrecognized charset labels and malformed byte sequences. Spans point
+    /// into `text`, where each malformed sequence became a U+FFFD.
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+/// Sniffs the encoding of an HTML byte stream and decodes it. `transport_hint`
+/// is a charset label from outside the document (e.g. the `Content-Type`
+/// header); it is overridden only by a byte order mark.
+pub fn decode_html(bytes: &[u8], transport_hint: Option<&str>) -> DecodedText {
+    decode(bytes, transport_hint, Sniffer::Html)
+}
+
+/// Like [`decode_html`], but honors the `encoding` of an XML declaration
+/// instead of `<meta>` tags and defaults to UTF-8.
+pub fn decode_xml(bytes: &[u8], transport_hint: Option<&str>) -> DecodedText {
+    decode(bytes, transport_hint, Sniffer::Xml)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sniffer {
+    Html,
+    Xml,
+}
+
+fn decode(bytes: &[u8], transport_hint: Option<&str>, sniffer: Sniffer) -> DecodedText {
+    let mut diagnostics = Vec::new();
+    let (encoding, bom_length) = detect(bytes, transport_hint, sniffer, true, &mut diagnostics);
+    let mut decoder = encoding.encoding.new_decoder_without_bom_handling();
+    let mut text = String::with_capacity(bytes.len());
+    let mut malformed = Vec::new();
+    decode_chunk(&mut decoder, &bytes[bom_length..], true, &mut text, &mut malformed);
+    let index = LineIndex::new(&text);
+    for (position, bytes_range) in malformed {
+        let start = bytes_range.start + bom_length;
+        let end = bytes_range.end + bom_length;
+        let span = index.span(position..position + '\u{FFFD}'.len_utf8());
+        diagnostics.push(malformed_diagnostic(encoding, start..end).with_span(span));
+    }
+    DecodedText { text, encoding, diagnostics }
+}
+
+/// Determines the encoding of `bytes` (the whole input if `complete`, else
+/// its first [`PRESCAN_LENGTH`] bytes or more). Also returns the length of the
+/// byte order mark.
+fn detect(
+    bytes: &[u8],
+    transport_hint: Option<&str>,
+    sniffer: Sniffer,
+    complete: bool,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> (DetectedEncoding, usize) {
+    let mut unsupported = |label: &str, what: &str| {
+        let message = format!("Unsupported encoding {label:?} in {what}");
+        diagnostics.push(ParseDiagnostic::new(DiagnosticCode::UnsupportedEncoding, message).with_severity(Severity::Warning));
+    };
+    if let Some((encoding, length)) = Encoding::for_bom(bytes) {
+        return (DetectedEncoding { encoding, source: EncodingSource::ByteOrderMark }, length)
+    }
+    let from_transport = transport_hint.and_then(|label| {
+        let encoding = Encoding::for_label(label.trim().as_bytes());
+        if encoding.is_none() {
+            unsupported(label, "transport hint");
+        }
+        encoding
+    });
+    let detected = match from_transport {
+        Some(encoding) => DetectedEncoding { encoding, source: EncodingSource::TransportHint },
+        None => sniff(bytes, sniffer, complete, &mut unsupported),
+    };
+    (detected, 0)
+}
+
+/// Like [`decode_html`] (or [`decode_xml`] if `xml`), for input read in
+/// pieces: determines the encoding from `head`, the first [`PRESCAN_LENGTH`]
+/// bytes or more (all of them if the input is shorter). Returns a decoder for
+/// the input after the byte order mark, and its length.
+pub(crate) fn detect_stream(
+    head: &[u8],
+    transport_hint: Option<&str>,
+    xml: bool,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> (DetectedEncoding, Decoder, usize) {
+    let sniffer = if xml { Sniffer::Xml } else { Sniffer::Html };
+    let (encoding, bom_length) = detect(head, transport_hint, sniffer, false, diagnostics);
+    (encoding, encoding.encoding.new_decoder_without_bom_handling(), bom_length)
+}
+
+/// Decodes `bytes` onto `output`, replacing malformed sequences with U+FFFD.
+/// For each malformed sequence, adds its byte offset in `output` and its byte
+/// range in `bytes` to `malformed`. `last` finishes the input, flushing any
+/// incomplete sequence the decoder held back.
+pub(crate) fn decode_chunk(
+    decoder: &mut Decoder,
+    bytes: &[u8],
+    last: bool,
+    output: &mut String,
+    malformed: &mut Vec<(usize, Range<usize>)>,
+) {
+    let mut consumed = 0;
+    loop {
+        let remaining = &bytes[consumed..];
+        let needed = decoder.max_utf8_buffer_length_without_replacement(remaining.len()).unwrap_or(remaining.len() * 3);
+        output.reserve(needed.max(4));
+        let (result, read) = decoder.decode_to_string_without_replacement(remaining, output, last);
+        consumed += read;
+        match result {
+            encoding_rs::DecoderResult::InputEmpty => break,
+            encoding_rs::DecoderResult::OutputFull => continue,
+            encoding_rs::DecoderResult::Malformed(length, pending) => {
+                let end = consumed - pending as usize;
+                let start = end.saturating_sub(length as usize);
+                malformed.push((output.len(), start..end));
+                output.push('\u{FFFD}');
+            }
+        }
+    }
+}
+
+/// Reports the malformed byte sequence at `bytes` of the input.
+pub(crate) fn malformed_diagnostic(encoding: DetectedEncoding, bytes: Range<usize>) -> ParseDiagnostic {
+    let message = format!("Invalid {encoding} byte sequence at bytes {}..{}", bytes.start, bytes.end);
+    ParseDiagnostic::new(DiagnosticCode::InvalidByteSequence, message)
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SNIFFING
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Sniffs the encoding of `bytes`, the whole input if `complete`, else its beginning.
+fn sniff(bytes: &[u8], sniffer: Sniffer, complete: bool, unsupported: &mut impl FnMut(&str, &str)) -> DetectedEncoding {
+    let head = &bytes[..bytes.len().min(PRESCAN_LENGTH)];
+    let declared = match sniffer {
+        Sniffer::Html => prescan_meta(head),
+        Sniffer::Xml => xml_declaration_encoding(head).map(|label| (label, EncodingSource::XmlDeclaration)),
+    };
+    if let Some((label, source)) = declared {
+        match Encoding::for_label(label.as_bytes()) {
+            // A document that could be read as ASCII cannot really be UTF-16, and
+            // x-user-defined is only meaningful for XHR.
+            Some(encoding) if encoding == UTF_16LE || encoding == UTF_16BE => {
+                return DetectedEncoding { encoding: UTF_8, source }
+            }
+            Some(encoding) if encoding == X_USER_DEFINED => {
+                return DetectedEncoding { encoding: WINDOWS_1252, source }
+            }
+            Some(encoding) => return DetectedEncoding { encoding, source },
+            None => unsupported(&label, "the document"),
+        }
+    }
+    let is_ascii = bytes.is_ascii();
+    // The beginning of the input may cut the last character short.
+    let is_utf8 = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(error) => !complete && error.error_len().is_none(),
+    };
+    if !is_ascii && is_utf8 {
+        return DetectedEncoding { encoding: UTF_8, source: EncodingSource::Utf8Heuristic }
+    }
+    let encoding = match sniffer {
+        Sniffer::Html if !is_ascii => WINDOWS_1252,
+        _ => UTF_8,
+    };
+    DetectedEncoding { encoding, source: EncodingSource::Default }
+}
+
+/// A simplified version of the HTML "prescan a byte stream" algorithm: finds the
+/// first `<meta>` declaring a charset, skipping comments.
+fn prescan_meta(head: &[u8]) -> Option<(String, EncodingSource)> {
+    let mut ix = 0;
+    while ix < head.len() {
+        let rest = &head[ix..];
+        if rest.starts_with(b"<!--") {
+            ix += find_bytes(&rest[4..], b"-->").map(|x| x + 7).unwrap_or(rest.len());
+            continue
+        }
+        let is_meta = rest.len() > 5
+            && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && (rest[5].is_ascii_whitespace() || rest[5] == b'/');
+        if !is_meta {
+            ix += 1;
+            continue
+        }
+        let (attributes, end) = lex_attributes(head, ix + 5);
+        ix = end;
+        let get = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+        if let Some(charset) = get("charset") {
+            return Some((charset.trim().to_string(), EncodingSource::MetaCharset))
+        }
+        let is_content_type = get("http-equiv").is_some_and(|x| x.trim().eq_ignore_ascii_case("content-type"));
+        if is_content_type && let Some(charset) = get("content").and_then(charset_from_content_type) {
+            return Some((charset, EncodingSource::MetaHttpEquiv))
+        }
+    }
+    None
+}
+
+/// Lexes `name=value` pairs up to the closing `>`. Returns the pairs and the
+/// offset just past the tag.
+fn lex_attributes(bytes: &[u8], mut ix: usize) -> (Vec<(String, String)>, usize) {
+    let mut attributes = Vec::new();
+    loop {
+        while ix < bytes.len() && (bytes[ix].is_ascii_whitespace() || bytes[ix] == b'/') {
+            ix += 1;
+        }
+        if ix >= bytes.len() || bytes[ix] == b'>' {
+            return (attributes, ix + 1)
+        }
+        let name_start = ix;
+        while ix < bytes.len() && !matches!(bytes[ix], b'=' | b'>' | b'/') && !bytes[ix].is_ascii_whitespace() {
+            ix += 1;
+        }
+        let name = String::from_utf8_lossy(&bytes[name_start..ix]).into_owned();
+        while ix < bytes.len() && bytes[ix].is_ascii_whitespace() {
+            ix += 1;
+        }
+        if bytes.get(ix) != Some(&b'=') {
+            attributes.push((name, String::new()));
+            continue
+        }
+        ix += 1;
+        while ix < bytes.len() && bytes[ix].is_ascii_whitespace() {
+            ix += 1;
+        }
+        let value = match bytes.get(ix) {
+            Some(quote @ (b'"' | b'\'')) => {
+                let start = ix + 1;
+                let end = bytes[start..].iter().position(|x| x == quote).map(|x| x + start).unwrap_or(bytes.len());
+                ix = end + 1;
+                &bytes[start..end]
+            }
+            _ => {
+                let start = ix;
+                while ix < bytes.len() && bytes[ix] != b'>' && !bytes[ix].is_ascii_whitespace() {
+                    ix += 1;
+                }
+                &bytes[start..ix]
+            }
+        };
+        attributes.push((name, String::from_utf8_lossy(value).into_owned()));
+    }
+}
+
+/// Extracts the charset from a `Content-Type` value such as `text/html; charset=utf-8`.
+pub fn charset_from_content_type(content: &str) -> Option<String> {
+    let lowercase = content.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(ix) = lowercase[from..].find("charset").map(|x| x + from) {
+        from = ix + "charset".len();
+        let rest = content[from..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue
+        };
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next().unwrap_or_default(),
+            _ => rest.split(|c: char| c == ';' || c.is_whitespace()).next().unwrap_or_default(),
+        };
+        return Some(value.to_string()).filter(|x| !x.is_empty())
+    }
+    None
+}
+
+/// The `encoding` pseudo-attribute of a leading `<?xml …?>` declaration.
+fn xml_declaration_encoding(head: &[u8]) -> Option<String> {
+    let declaration = head.strip_prefix(b"<?xml")?;
+    let end = find_bytes(declaration, b"?>")?;
+    let declaration = String::from_utf8_lossy(&declaration[..end]).into_owned();
+    let ix = declaration.find("encoding")?;
+    let rest = declaration[ix + "encoding".len()..].trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    rest[1..].split(quote).next().map(str::to_string)
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|x| x == needle)
+}
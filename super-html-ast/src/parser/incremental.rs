@@ -0,0 +1,301 @@
+use std::ops::Range;
+use crate::{AttributeMap, LineIndex, Node, Span, TagBuf};
+use super::native::tokenizer::{TokenKind, Tokenizer};
+use super::{parse_document_with, parse_fragment_with, ParseDiagnostic, ParseLimits, ParseResult, ParserBackend, ParserOptions};
+
+// ————————————————————————————————————————————————————————————————————————————
+// TEXT EDITS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A change to the source text: `range` (byte offsets into the old text) is
+/// replaced by `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, replacement: impl Into<String>) -> Self {
+        Self { range, replacement: replacement.into() }
+    }
+    pub fn insert(offset: usize, text: impl Into<String>) -> Self {
+        Self::new(offset..offset, text)
+    }
+    pub fn delete(range: Range<usize>) -> Self {
+        Self::new(range, "")
+    }
+    /// `source` with the edit applied. Panics if the range is out of bounds or
+    /// not on character boundaries.
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.replacement.len());
+        edited.push_str(&source[..self.range.start]);
+        edited.push_str(&self.replacement);
+        edited.push_str(&source[self.range.end..]);
+        edited
+    }
+    /// Where an offset of the old text ends up in the new one. Offsets inside
+    /// the replaced range map to its end.
+    fn map_offset(&self, offset: usize) -> usize {
+        match offset {
+            _ if offset <= self.range.start => offset,
+            _ if offset < self.range.end => self.range.start + self.replacement.len(),
+            _ => offset - self.range.end + self.range.start + self.replacement.len(),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INCREMENTAL REPARSING
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The outcome of [`reparse_document`] or [`reparse_fragment`].
+#[derive(Debug, Clone)]
+pub struct Reparse {
+    /// The tree of the edited text, with the diagnostics of the edited text.
+    pub result: ParseResult<Node>,
+    /// The edited text.
+    pub source: String,
+    /// The paths (child indices from the root, see [`Reparse::get`]) of the
+    /// subtrees that were parsed anew. Every other node was moved over from
+    /// the previous tree, with its spans shifted. `[[]]` if the whole text had
+    /// to be parsed again.
+    pub changed: Vec<Vec<usize>>,
+}
+
+impl Reparse {
+    /// Whether the whole text had to be parsed again.
+    pub fn is_full(&self) -> bool {
+        self.changed.iter().any(|path| path.is_empty())
+    }
+    /// The node at `path` in the new tree.
+    pub fn get(&self, path: &[usize]) -> Option<&Node> {
+        path.iter().try_fold(&self.result.output, |node, ix| match node {
+            Node::Element(element) => element.children.get(*ix),
+            Node::Fragment(fragment) => fragment.get(*ix),
+            _ => None,
+        })
+    }
+}
+
+/// Applies `edit` to `source`, the text `previous` was parsed from by
+/// [`parse_document_with`], and parses the result — reparsing only the
+/// innermost element around the edit where that gives the same tree as a
+/// full parse would, for editors that reparse on every keystroke.
+///
+/// An element is reparsed on its own (in the context of its parent) only if
+/// it has an explicit end tag and its new source text still parses to a single
+/// element without errors; otherwise the next element out is tried, and in the
+/// end the whole text. The HTML5 backend additionally needs the previous parse
+/// to be free of errors up to the end of the element, as those can affect how
+/// what follows is parsed, and without [`ParserOptions::locate_spans`] has no
+/// spans to find the element by, so it is always parsed in full. XML, whose namespace declarations reach into every
+/// descendant, and parses with [`ParserOptions::limits`], which count across
+/// the whole document, are always parsed in full.
+///
+/// ```
+/// # use super_html_ast::Node;
+/// # use super_html_ast::parser::{parse_document_with, reparse_document, ParserOptions, TextEdit};
+/// # fn refresh_preview(_: &[usize], _: Option<&Node>) {}
+/// # let options = ParserOptions::native();
+/// # let mut source = String::from("<main><p>Hello, there</p></main>");
+/// # let previous = parse_document_with(&source, &options);
+/// let edit = TextEdit::new(16..21, "world");
+/// let reparse = reparse_document(previous, &source, &edit, &options);
+/// for path in reparse.changed.iter() {
+///     refresh_preview(path, reparse.get(path));
+/// }
+/// source = reparse.source;
+/// ```
+pub fn reparse_document(previous: ParseResult<Node>, source: &str, edit: &TextEdit, options: &ParserOptions) -> Reparse {
+    // The top level of an HTML5 document is the implied `<html>`, which has no
+    // context to be reparsed in.
+    let top_level = match options.backend {
+        ParserBackend::Html5 => None,
+        _ => Some(None),
+    };
+    reparse(previous, source, edit, options, top_level, |source| parse_document_with(source, options))
+}
+
+/// Like [`reparse_document`], for a tree parsed by [`parse_fragment_with`].
+pub fn reparse_fragment(previous: ParseResult<Node>, source: &str, edit: &TextEdit, options: &ParserOptions) -> Reparse {
+    let top_level = Some(options.fragment_context.clone());
+    reparse(previous, source, edit, options, top_level, |source| parse_fragment_with(source, options))
+}
+
+/// `top_level` is the context top-level elements are reparsed in, or `None`
+/// if they cannot be.
+fn reparse(
+    mut previous: ParseResult<Node>,
+    source: &str,
+    edit: &TextEdit,
+    options: &ParserOptions,
+    top_level: Option<Option<TagBuf>>,
+    parse: impl FnOnce(&str) -> ParseResult<Node>,
+) -> Reparse {
+    let edited = edit.apply(source);
+    let unlimited = ParseLimits { action: options.limits.action, ..ParseLimits::default() };
+    let reusable = options.backend != ParserBackend::Xml && options.limits == unlimited;
+    let (path, candidates) = match reusable {
+        true => candidates(&previous.output, &edit.range),
+        false => (Vec::new(), Vec::new()),
+    };
+    let index = LineIndex::new(&edited);
+    // Innermost first.
+    for (depth, (span, context)) in candidates.into_iter().enumerate().rev() {
+        let context = match context {
+            Some(parent) => Some(parent),
+            None => match top_level.as_ref() {
+                Some(context) => context.clone(),
+                None => continue,
+            },
+        };
+        if options.backend == ParserBackend::Html5
+            && previous.errors().any(|x| x.span.is_none_or(|x| x.start.offset < span.end.offset)) {
+            continue
+        }
+        let start = span.start.offset;
+        let range = start..edit.map_offset(span.end.offset);
+        let Some((mut node, reparsed)) = reparse_element(&edited[range], &context, options) else {
+            continue
+        };
+        let shift = |x: Span| index.span(x.start.offset + start..x.end.offset + start);
+        let shift_edited = |x: Span| index.span(edit.map_offset(x.start.offset)..edit.map_offset(x.end.offset));
+        map_spans(&mut node, 0, shift);
+        map_spans(&mut previous.output, edit.range.start, shift_edited);
+        let path = path[..=depth].to_vec();
+        *node_at(&mut previous.output, &path) = node;
+        let mut diagnostics = previous.diagnostics
+            .into_iter()
+            .filter(|x| x.span.is_none_or(|x| x.end.offset <= span.start.offset || x.start.offset >= span.end.offset))
+            .map(|x| ParseDiagnostic { span: x.span.map(shift_edited), ..x })
+            .chain(reparsed.into_iter().map(|x| ParseDiagnostic { span: x.span.map(shift), ..x }))
+            .collect::<Vec<_>>();
+        diagnostics.sort_by_key(|x| x.span.map(|x| x.start.offset));
+        let result = ParseResult { output: previous.output, diagnostics, encoding: previous.encoding };
+        return Reparse { result, source: edited, changed: vec![path] }
+    }
+    let result = parse(&edited);
+    Reparse { result, source: edited, changed: vec![Vec::new()] }
+}
+
+/// The path of the innermost element whose span contains `edit`, and the
+/// span and parent element tag of it and each of its ancestors, outermost
+/// first: the element at depth `d` is at `path[..=d]`. An edit touching
+/// either end of an element can extend or shorten it, so only edits strictly
+/// inside count.
+fn candidates(root: &Node, edit: &Range<usize>) -> (Vec<usize>, Vec<(Span, Option<TagBuf>)>) {
+    let mut candidates = Vec::new();
+    let mut path = Vec::new();
+    let mut parent = None::<&TagBuf>;
+    let mut children = match root {
+        Node::Fragment(fragment) => fragment.as_node_slice(),
+        _ => return (path, candidates),
+    };
+    loop {
+        let found = children.iter().enumerate().find_map(|(ix, child)| match child {
+            Node::Element(element) => element.span
+                .filter(|x| x.start.offset < edit.start && edit.end < x.end.offset)
+                .map(|span| (ix, element, span)),
+            _ => None,
+        });
+        let Some((ix, element, span)) = found else {
+            return (path, candidates)
+        };
+        path.push(ix);
+        candidates.push((span, parent.cloned()));
+        parent = Some(&element.tag);
+        children = element.children.as_node_slice();
+    }
+}
+
+/// Parses the source text of a single element in `context`, if it is
+/// complete and parses to that element alone, without errors. Returns the
+/// element with the warnings.
+fn reparse_element(source: &str, context: &Option<TagBuf>, options: &ParserOptions) -> Option<(Node, Vec<ParseDiagnostic>)> {
+    if !is_closed_element(source, options) {
+        return None
+    }
+    let options = ParserOptions { fragment_context: context.clone(), ..options.clone() };
+    let result = parse_fragment_with(source, &options);
+    if result.has_errors() {
+        return None
+    }
+    let Node::Fragment(mut fragment) = result.output else {
+        return None
+    };
+    if fragment.len() != 1 {
+        return None
+    }
+    let node = fragment.pop()?;
+    let covers = node.span().is_some_and(|x| x.range() == (0..source.len()));
+    (covers && node.as_element().is_some()).then_some((node, result.diagnostics))
+}
+
+/// Whether `source` starts with a start tag and ends with the matching end
+/// tag, as opposed to, say, running into an unterminated `<script>` or
+/// comment that would swallow whatever follows in the full text.
+fn is_closed_element(source: &str, options: &ParserOptions) -> bool {
+    let mut tokens = Tokenizer::new(source)
+        .with_placeholders(options.placeholders.clone())
+        .with_scripting(options.scripting_enabled);
+    let Some(TokenKind::StartTag(start)) = tokens.next().map(|x| x.kind) else {
+        return false
+    };
+    match tokens.last() {
+        Some(token) if token.range.end == source.len() => match token.kind {
+            TokenKind::EndTag(end) => end.name.eq_ignore_ascii_case(&start.name),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The node at `path`, which must exist.
+fn node_at<'a>(root: &'a mut Node, path: &[usize]) -> &'a mut Node {
+    path.iter().fold(root, |node, ix| match node {
+        Node::Element(element) => &mut element.children[*ix],
+        Node::Fragment(fragment) => &mut fragment[*ix],
+        _ => unreachable!("paths only lead through elements and fragments"),
+    })
+}
+
+/// Applies `map` to every span that ends after `from`. Elements ending before
+/// it are skipped with their contents.
+fn map_spans(root: &mut Node, from: usize, map: impl Fn(Span) -> Span) {
+    let map_span = |span: &mut Option<Span>| {
+        if let Some(x) = span.as_mut()
+            && x.end.offset > from {
+            *x = map(*x);
+        }
+    };
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        match node {
+            Node::Element(element) => {
+                if element.span.is_some_and(|x| x.end.offset <= from) {
+                    continue
+                }
+                map_span(&mut element.span);
+                for (_, placeholder) in element.placeholders.iter_mut() {
+                    map_span(&mut placeholder.span);
+                }
+                if element.attributes.iter().any(|(key, _)| key.span().is_some_and(|x| x.end.offset > from)) {
+                    element.attributes = std::mem::take(&mut element.attributes)
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let mut span = key.span();
+                            map_span(&mut span);
+                            (key.with_span(span), value)
+                        })
+                        .collect::<AttributeMap>();
+                }
+                stack.extend(element.children.iter_mut());
+            }
+            Node::Fragment(fragment) => stack.extend(fragment.iter_mut()),
+            Node::Text(text) => map_span(&mut text.span),
+            Node::Placeholder(placeholder) => map_span(&mut placeholder.span),
+            Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) => (),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::Display;
+use crate::Span;
+use super::{DiagnosticCode, ParseDiagnostic, Severity};
+
+// ————————————————————————————————————————————————————————————————————————————
+// LIMITS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// What to do once a [`ParseLimits`] limit is exceeded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitAction {
+    /// Drop whatever exceeds the limit, keep parsing, and report a warning.
+    #[default]
+    Truncate,
+    /// Stop parsing and report an error; the output holds what was built so far.
+    Fail,
+}
+
+/// Resource limits for parsing untrusted input. Every limit is off (`None`)
+/// by default; see [`ParseLimits::untrusted`] for a conservative preset.
+///
+/// ```
+/// # use super_html_ast::parser::{parse_document_with, LimitAction, ParseLimits, ParserOptions};
+/// # let source = "<p>hello</p>";
+/// let limits = ParseLimits::untrusted().with_max_depth(64).with_action(LimitAction::Fail);
+/// let result = parse_document_with(source, &ParserOptions::default().with_limits(limits));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Maximum size of the input in bytes.
+    pub max_input_bytes: Option<usize>,
+    /// Maximum element nesting depth. Deeper elements are dropped with their contents.
+    pub max_depth: Option<usize>,
+    /// Maximum number of nodes in the output.
+    pub max_nodes: Option<usize>,
+    /// Maximum number of attributes per element; the first ones are kept.
+    pub max_attributes: Option<usize>,
+    /// Maximum length in bytes of an attribute value.
+    pub max_attribute_length: Option<usize>,
+    /// Maximum length in bytes of a text node.
+    pub max_text_length: Option<usize>,
+    pub action: LimitAction,
+}
+
+impl ParseLimits {
+    /// Limits suited to user-submitted HTML: 10 MiB of input, 256 levels of
+    /// nesting, a million nodes, 256 attributes per element, 1 MiB attribute
+    /// values and text nodes.
+    pub fn untrusted() -> Self {
+        Self {
+            max_input_bytes: Some(10 * 1024 * 1024),
+            max_depth: Some(256),
+            max_nodes: Some(1_000_000),
+            max_attributes: Some(256),
+            max_attribute_length: Some(1024 * 1024),
+            max_text_length: Some(1024 * 1024),
+            action: LimitAction::Truncate,
+        }
+    }
+    pub fn with_max_input_bytes(mut self, limit: usize) -> Self {
+        self.max_input_bytes = Some(limit);
+        self
+    }
+    pub fn with_max_depth(mut self, limit: usize) -> Self {
+        self.max_depth = Some(limit);
+        self
+    }
+    pub fn with_max_nodes(mut self, limit: usize) -> Self {
+        self.max_nodes = Some(limit);
+        self
+    }
+    pub fn with_max_attributes(mut self, limit: usize) -> Self {
+        self.max_attributes = Some(limit);
+        self
+    }
+    pub fn with_max_attribute_length(mut self, limit: usize) -> Self {
+        self.max_attribute_length = Some(limit);
+        self
+    }
+    pub fn with_max_text_length(mut self, limit: usize) -> Self {
+        self.max_text_length = Some(limit);
+        self
+    }
+    pub fn with_action(mut self, action: LimitAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+/// The limit a [`DiagnosticCode::LimitExceeded`] diagnostic refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    InputBytes,
+    Depth,
+    Nodes,
+    Attributes,
+    AttributeLength,
+    TextLength,
+}
+
+impl Display for LimitKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InputBytes => f.write_str("input size"),
+            Self::Depth => f.write_str("nesting depth"),
+            Self::Nodes => f.write_str("node count"),
+            Self::Attributes => f.write_str("attributes per element"),
+            Self::AttributeLength => f.write_str("attribute length"),
+            Self::TextLength => f.write_str("text length"),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL — ENFORCEMENT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Enforces [`ParseLimits`] while a tree is built. Each kind of limit is
+/// reported once, at its first violation.
+pub(super) struct LimitTracker {
+    limits: ParseLimits,
+    nodes: usize,
+    /// Set once a limit is exceeded under [`LimitAction::Fail`].
+    stopped: bool,
+    reported: HashSet<LimitKind>,
+    pub(super) diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl LimitTracker {
+    pub(super) fn new(limits: &ParseLimits) -> Self {
+        Self {
+            limits: limits.clone(),
+            nodes: 0,
+            stopped: false,
+            reported: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+    /// Whether parsing should stop altogether.
+    pub(super) fn stopped(&self) -> bool {
+        self.stopped
+    }
+    fn exceeded(&mut self, kind: LimitKind, limit: usize, span: Option<Span>) {
+        if self.limits.action == LimitAction::Fail {
+            self.stopped = true;
+        }
+        if !self.reported.insert(kind) {
+            return
+        }
+        let (severity, outcome) = match self.limits.action {
+            LimitAction::Truncate => (Severity::Warning, "truncated"),
+            LimitAction::Fail => (Severity::Error, "parsing stopped"),
+        };
+        let message = format!("Exceeded the {kind} limit of {limit}; {outcome}");
+        let diagnostic = ParseDiagnostic::new(DiagnosticCode::LimitExceeded, message)
+            .with_severity(severity)
+            .with_span(span);
+        self.diagnostics.push(diagnostic);
+    }
+    /// Checks the input size. Returns how many bytes of input to parse, or
+    /// `None` if parsing should not start.
+    pub(super) fn admit_input_len(&mut self, len: usize) -> Option<usize> {
+        let Some(limit) = self.limits.max_input_bytes.filter(|limit| len > *limit) else {
+            return Some(len)
+        };
+        self.exceeded(LimitKind::InputBytes, limit, None);
+        if self.stopped {
+            return None
+        }
+        Some(limit)
+    }
+    /// [`LimitTracker::admit_input_len`] for text, truncated at a char boundary.
+    pub(super) fn admit_input<'a>(&mut self, source: &'a str) -> Option<&'a str> {
+        let len = self.admit_input_len(source.len())?;
+        Some(&source[..floor_char_boundary(source, len)])
+    }
+    /// Whether an element at `depth` (1 for top-level elements) may be kept.
+    pub(super) fn admit_depth(&mut self, depth: usize, span: Option<Span>) -> bool {
+        match self.limits.max_depth {
+            Some(limit) if depth > limit => {
+                self.exceeded(LimitKind::Depth, limit, span);
+                false
+            }
+            _ => !self.stopped,
+        }
+    }
+    /// Counts one more node; `false` if it must be dropped.
+    pub(super) fn admit_node(&mut self, span: Option<Span>) -> bool {
+        if self.stopped {
+            return false
+        }
+        match self.limits.max_nodes {
+            Some(limit) if self.nodes >= limit => {
+                self.exceeded(LimitKind::Nodes, limit, span);
+                false
+            }
+            _ => {
+                self.nodes += 1;
+                true
+            }
+        }
+    }
+    /// How many of `count` attributes may be kept.
+    pub(super) fn admit_attributes(&mut self, count: usize, span: Option<Span>) -> usize {
+        match self.limits.max_attributes {
+            Some(limit) if count > limit => {
+                self.exceeded(LimitKind::Attributes, limit, span);
+                limit
+            }
+            _ => count,
+        }
+    }
+    pub(super) fn truncate_attribute(&mut self, value: &mut impl Truncate, span: Option<Span>) {
+        if let Some(limit) = self.limits.max_attribute_length.filter(|limit| value.len() > *limit) {
+            self.exceeded(LimitKind::AttributeLength, limit, span);
+            value.truncate_to(floor_char_boundary(value, limit));
+        }
+    }
+    pub(super) fn truncate_text(&mut self, value: &mut impl Truncate, span: Option<Span>) {
+        if let Some(limit) = self.limits.max_text_length.filter(|limit| value.len() > *limit) {
+            self.exceeded(LimitKind::TextLength, limit, span);
+            value.truncate_to(floor_char_boundary(value, limit));
+        }
+    }
+}
+
+/// Text the limits can cut short, owned or borrowed.
+pub(super) trait Truncate: std::ops::Deref<Target = str> {
+    /// Keeps the first `len` bytes, `len` being a char boundary.
+    fn truncate_to(&mut self, len: usize);
+}
+
+impl Truncate for String {
+    fn truncate_to(&mut self, len: usize) {
+        self.truncate(len);
+    }
+}
+
+impl Truncate for Cow<'_, str> {
+    fn truncate_to(&mut self, len: usize) {
+        match self {
+            Cow::Borrowed(text) => *text = &text[..len],
+            Cow::Owned(text) => text.truncate(len),
+        }
+    }
+}
+
+/// The largest char boundary of `text` not after `index`.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
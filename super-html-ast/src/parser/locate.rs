@@ -0,0 +1,572 @@
+//! Recovers source spans for trees produced by the scraper/html5ever backend.
+//!
+//! html5ever does not report node positions, so we re-scan the source with a
+//! small, forgiving tag lexer and align its tokens against the converted tree
+//! in document order. Nodes the tree builder synthesized (implied `<html>`,
+//! `<tbody>`, adoption-agency clones, …) have no source tokens and are left
+//! without a span.
+//!
+//! In lossless mode the located tags, text and doctypes also get their source
+//! text (see [`crate::RawSource`]), and located text and attribute values get
+//! the character references they were resolved from, where the native
+//! tokenizer reads the same thing from the source that html5ever did.
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::ops::Range;
+use crate::{AttributeMap, AttributeValueBuf, Element, Fragment, LineIndex, Node, RawSource, RawTags, Span, Text};
+use super::ParserOptions;
+use super::native::tokenizer::{self, Tokenizer};
+
+// ————————————————————————————————————————————————————————————————————————————
+// SOURCE TOKENS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+enum TokenKind {
+    StartTag {
+        name: String,
+        attributes: Vec<(String, Range<usize>)>,
+        self_closing: bool,
+    },
+    EndTag { name: String },
+    Text,
+    Comment,
+    Doctype,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    range: Range<usize>,
+}
+
+/// Elements whose contents are not markup.
+const RAW_TEXT_TAGS: &[&str] = &[
+    "script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes",
+];
+
+/// Elements the HTML5 tree builder routinely inserts on its own; these only
+/// match a source tag that comes next in document order.
+const IMPLIED_TAGS: &[&str] = &["html", "head", "body", "tbody", "colgroup"];
+
+/// How many unclaimed start tags an element may skip over when looking for
+/// its own (tolerates foster parenting and similar reordering).
+const START_TAG_WINDOW: usize = 8;
+
+/// Lexes `source`; `<noscript>` contents are raw text if `scripting_enabled`.
+fn lex(source: &str, scripting_enabled: bool) -> Vec<Token> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::<Token>::new();
+    let mut text_start: Option<usize> = None;
+    let mut ix = 0;
+    let flush_text = |tokens: &mut Vec<Token>, text_start: &mut Option<usize>, end: usize| {
+        if let Some(start) = text_start.take() && start < end {
+            tokens.push(Token { kind: TokenKind::Text, range: start..end });
+        }
+    };
+    while ix < bytes.len() {
+        if bytes[ix] != b'<' {
+            text_start.get_or_insert(ix);
+            ix += 1;
+            continue
+        }
+        let rest = &bytes[ix..];
+        let next = rest.get(1).copied().unwrap_or(b' ');
+        if rest.starts_with(b"<!--") {
+            flush_text(&mut tokens, &mut text_start, ix);
+            let end = find(bytes, ix + 4, b"-->").map(|x| x + 3).unwrap_or(bytes.len());
+            tokens.push(Token { kind: TokenKind::Comment, range: ix..end });
+            ix = end;
+        } else if next == b'!' || next == b'?' {
+            flush_text(&mut tokens, &mut text_start, ix);
+            let end = find(bytes, ix + 2, b">").map(|x| x + 1).unwrap_or(bytes.len());
+            let is_doctype = rest.len() >= 9 && rest[2..9].eq_ignore_ascii_case(b"doctype");
+            let kind = if is_doctype { TokenKind::Doctype } else { TokenKind::Comment };
+            tokens.push(Token { kind, range: ix..end });
+            ix = end;
+        } else if next == b'/' && rest.get(2).is_some_and(u8::is_ascii_alphabetic) {
+            flush_text(&mut tokens, &mut text_start, ix);
+            let name_end = scan_name(bytes, ix + 2);
+            let name = source[ix + 2..name_end].to_ascii_lowercase();
+            let end = find(bytes, name_end, b">").map(|x| x + 1).unwrap_or(bytes.len());
+            tokens.push(Token { kind: TokenKind::EndTag { name }, range: ix..end });
+            ix = end;
+        } else if next.is_ascii_alphabetic() {
+            flush_text(&mut tokens, &mut text_start, ix);
+            let (token, end) = lex_start_tag(source, ix);
+            let raw_text = match &token.kind {
+                TokenKind::StartTag { name, self_closing: false, .. }
+                    if RAW_TEXT_TAGS.contains(&name.as_str()) || (scripting_enabled && name == "noscript") => {
+                    Some(name.clone())
+                }
+                _ => None,
+            };
+            tokens.push(token);
+            ix = end;
+            if let Some(name) = raw_text {
+                let close = find_end_tag(source, ix, &name).unwrap_or(bytes.len());
+                if ix < close {
+                    tokens.push(Token { kind: TokenKind::Text, range: ix..close });
+                }
+                ix = close;
+            }
+        } else {
+            text_start.get_or_insert(ix);
+            ix += 1;
+        }
+    }
+    flush_text(&mut tokens, &mut text_start, bytes.len());
+    tokens
+}
+
+fn lex_start_tag(source: &str, start: usize) -> (Token, usize) {
+    let bytes = source.as_bytes();
+    let name_end = scan_name(bytes, start + 1);
+    let name = source[start + 1..name_end].to_ascii_lowercase();
+    let mut attributes = Vec::new();
+    let mut ix = name_end;
+    let mut self_closing = false;
+    loop {
+        while ix < bytes.len() && (bytes[ix].is_ascii_whitespace() || bytes[ix] == b'/') {
+            self_closing = bytes[ix] == b'/';
+            ix += 1;
+        }
+        if ix >= bytes.len() {
+            break
+        }
+        if bytes[ix] == b'>' {
+            ix += 1;
+            break
+        }
+        self_closing = false;
+        let attr_start = ix;
+        while ix < bytes.len() && !matches!(bytes[ix], b'>' | b'/' | b'=') && !bytes[ix].is_ascii_whitespace() {
+            ix += 1;
+        }
+        // An attribute name may legitimately start with `=` or contain `/`.
+        if ix == attr_start {
+            ix += 1;
+        }
+        let attr_name = source[attr_start..ix].to_ascii_lowercase();
+        let mut attr_end = ix;
+        let mut lookahead = ix;
+        while lookahead < bytes.len() && bytes[lookahead].is_ascii_whitespace() {
+            lookahead += 1;
+        }
+        if lookahead < bytes.len() && bytes[lookahead] == b'=' {
+            ix = lookahead + 1;
+            while ix < bytes.len() && bytes[ix].is_ascii_whitespace() {
+                ix += 1;
+            }
+            match bytes.get(ix) {
+                Some(quote @ (b'"' | b'\'')) => {
+                    ix = find(bytes, ix + 1, &[*quote]).map(|x| x + 1).unwrap_or(bytes.len());
+                }
+                _ => {
+                    while ix < bytes.len() && bytes[ix] != b'>' && !bytes[ix].is_ascii_whitespace() {
+                        ix += 1;
+                    }
+                }
+            }
+            attr_end = ix;
+        }
+        attributes.push((attr_name, attr_start..attr_end));
+    }
+    let kind = TokenKind::StartTag { name, attributes, self_closing };
+    (Token { kind, range: start..ix }, ix)
+}
+
+fn scan_name(bytes: &[u8], start: usize) -> usize {
+    let mut ix = start;
+    while ix < bytes.len() && !matches!(bytes[ix], b'>' | b'/') && !bytes[ix].is_ascii_whitespace() {
+        ix += 1;
+    }
+    ix
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|ix| ix + from)
+}
+
+fn find_end_tag(source: &str, from: usize, name: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut ix = from;
+    while let Some(candidate) = find(bytes, ix, b"</") {
+        let name_start = candidate + 2;
+        let name_end = name_start + name.len();
+        if bytes.get(name_start..name_end).is_some_and(|x| x.eq_ignore_ascii_case(name.as_bytes())) {
+            let boundary = bytes.get(name_end).copied().unwrap_or(b'>');
+            if boundary == b'>' || boundary == b'/' || boundary.is_ascii_whitespace() {
+                return Some(candidate)
+            }
+        }
+        ix = name_start;
+    }
+    None
+}
+
+/// Entity-decoded, newline-normalized text (what html5ever would produce).
+fn normalize_text(raw: &str, decode: bool) -> String {
+    let raw = raw.replace("\r\n", "\n").replace('\r', "\n");
+    if decode {
+        html_escape::decode_html_entities(&raw).into_owned()
+    } else {
+        raw
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TREE ALIGNMENT
+// ————————————————————————————————————————————————————————————————————————————
+
+struct Locator<'a> {
+    index: LineIndex<'a>,
+    tokens: Vec<Token>,
+    /// For each token, a token at or before the first unclaimed one from there
+    /// on (`tokens.len()` if none), so claimed runs are skipped in one step.
+    next_unclaimed: Vec<Cell<usize>>,
+    /// The most recently claimed token; text and comments never match before it.
+    frontier: usize,
+    /// Every start tag before this index is claimed, waiting or given up on.
+    horizon: usize,
+    /// The unclaimed start tags before `horizon`, oldest first. At most
+    /// [`START_TAG_WINDOW`] are kept; older ones are given up on, as elements
+    /// could no longer skip over them to find their own.
+    waiting: VecDeque<usize>,
+    /// Whether to keep the source text of located nodes.
+    lossless: bool,
+    /// Whether to keep the character references of located text and values.
+    references: bool,
+}
+
+/// Attaches source spans to the elements, attributes and text of `node`,
+/// which must have been parsed from `source` with `options`.
+pub(super) fn locate(node: &mut Node, source: &str, options: &ParserOptions) {
+    let tokens = lex(source, options.scripting_enabled);
+    let next_unclaimed = (0..=tokens.len()).map(Cell::new).collect();
+    let mut locator = Locator {
+        index: LineIndex::new(source),
+        tokens,
+        next_unclaimed,
+        frontier: 0,
+        horizon: 0,
+        waiting: VecDeque::new(),
+        lossless: options.lossless,
+        references: options.character_references,
+    };
+    let root = std::mem::replace(node, Node::empty());
+    *node = locator.visit_tree(root);
+}
+
+/// An element or fragment whose children are being located.
+struct Frame {
+    /// The element or fragment, with its children moved to `pending`.
+    parent: Node,
+    /// The range of the element's start tag, if found.
+    start_tag: Option<Range<usize>>,
+    /// Where the element ends at the earliest, given its start tag and children.
+    end: usize,
+    pending: std::vec::IntoIter<Node>,
+    done: Vec<Node>,
+}
+
+impl<'a> Locator<'a> {
+    fn claim(&mut self, ix: usize) {
+        self.next_unclaimed[ix].set(ix + 1);
+        self.frontier = ix;
+        self.waiting.retain(|x| *x != ix);
+        if ix < self.horizon {
+            return
+        }
+        let passed = self.unclaimed_from(self.horizon)
+            .take_while(|(x, _)| *x < ix)
+            .filter(|(_, token)| matches!(token.kind, TokenKind::StartTag { .. }))
+            .map(|(x, _)| x)
+            .collect::<Vec<_>>();
+        self.waiting.extend(passed);
+        while self.waiting.len() > START_TAG_WINDOW {
+            self.waiting.pop_front();
+        }
+        self.horizon = ix + 1;
+    }
+    /// The first unclaimed token at or after `ix`, or `tokens.len()`.
+    fn first_unclaimed(&self, ix: usize) -> usize {
+        let mut ix = ix;
+        loop {
+            let next = self.next_unclaimed[ix].get();
+            if next == ix {
+                return ix
+            }
+            // Halve the path for the next lookup.
+            self.next_unclaimed[ix].set(self.next_unclaimed[next].get());
+            ix = next;
+        }
+    }
+    fn unclaimed_from(&self, ix: usize) -> impl Iterator<Item = (usize, &Token)> + Clone {
+        let unclaimed = |ix: usize| Some(self.first_unclaimed(ix)).filter(|x| *x < self.tokens.len());
+        std::iter::successors(unclaimed(ix), move |ix| unclaimed(ix + 1)).map(|ix| (ix, &self.tokens[ix]))
+    }
+    /// Unclaimed tokens after the most recently claimed one.
+    fn upcoming(&self) -> impl Iterator<Item = (usize, &Token)> + Clone {
+        self.unclaimed_from(self.frontier)
+    }
+    fn span(&self, range: Range<usize>) -> Span {
+        self.index.span(range)
+    }
+    /// Walks the tree in document order. Nodes are moved out of their parents
+    /// and back, with the open elements on an explicit stack, so that nesting
+    /// depth is not limited by the call stack.
+    fn visit_tree(&mut self, root: Node) -> Node {
+        let mut stack = Vec::new();
+        let mut visited = self.enter_node(root, &mut stack);
+        loop {
+            if let Some(node) = visited.take() {
+                let Some(frame) = stack.last_mut() else {
+                    return node
+                };
+                if let Some(span) = node.span() {
+                    frame.end = frame.end.max(span.end.offset);
+                }
+                frame.done.push(node);
+            }
+            let frame = stack.last_mut().expect("an open element or fragment");
+            match frame.pending.next() {
+                Some(child) => visited = self.enter_node(child, &mut stack),
+                None => {
+                    let frame = stack.pop().unwrap();
+                    visited = Some(self.leave(frame));
+                }
+            }
+        }
+    }
+    /// Locates a leaf, or opens a frame for an element or fragment (returning `None`).
+    fn enter_node(&mut self, node: Node, stack: &mut Vec<Frame>) -> Option<Node> {
+        match node {
+            Node::Element(mut element) => {
+                let start_tag = self.find_start_tag(element.tag.as_normalized()).map(|start_ix| {
+                    self.claim(start_ix);
+                    let start_token = self.tokens[start_ix].clone();
+                    if let TokenKind::StartTag { attributes, .. } = &start_token.kind {
+                        self.locate_attributes(&mut element.attributes, attributes);
+                    }
+                    self.keep_start_tag(&mut element, start_token.range.clone());
+                    start_token.range
+                });
+                let children = std::mem::take(&mut element.children);
+                let end = start_tag.as_ref().map_or(0, |range| range.end);
+                let parent = Node::Element(element);
+                stack.push(Frame { parent, start_tag, end, pending: children.into_iter(), done: Vec::new() });
+                None
+            }
+            Node::Fragment(fragment) => {
+                let parent = Node::Fragment(Fragment::default());
+                stack.push(Frame { parent, start_tag: None, end: 0, pending: fragment.into_iter(), done: Vec::new() });
+                None
+            }
+            Node::Text(mut text) => {
+                self.visit_text(&mut text);
+                Some(Node::Text(text))
+            }
+            Node::Comment(_) | Node::ProcessingInstruction(_) => {
+                self.claim_first(|kind| matches!(kind, TokenKind::Comment));
+                Some(node)
+            }
+            Node::Doctype(mut doctype) => {
+                let ix = self.claim_first(|kind| matches!(kind, TokenKind::Doctype));
+                if let Some(ix) = ix.filter(|_| self.lossless) {
+                    let raw = &self.index.source()[self.tokens[ix].range.clone()];
+                    doctype.raw = Some(RawSource::doctype(raw, &doctype));
+                }
+                Some(Node::Doctype(doctype))
+            }
+            // Placeholders are still marker comments while the tree is located.
+            Node::Placeholder(_) => Some(node),
+        }
+    }
+    fn claim_first(&mut self, predicate: impl Fn(&TokenKind) -> bool) -> Option<usize> {
+        let ix = self.upcoming()
+            .take_while(|(_, token)| !matches!(token.kind, TokenKind::StartTag { .. }))
+            .find(|(_, token)| predicate(&token.kind))
+            .map(|(ix, _)| ix)?;
+        self.claim(ix);
+        Some(ix)
+    }
+    /// The first unclaimed start tags, waiting ones first, are candidates.
+    fn find_start_tag(&self, tag: &str) -> Option<usize> {
+        let window = if IMPLIED_TAGS.contains(&tag) { 1 } else { START_TAG_WINDOW };
+        let ahead = self.unclaimed_from(self.horizon)
+            .filter(|(_, token)| matches!(token.kind, TokenKind::StartTag { .. }))
+            .map(|(ix, _)| ix);
+        self.waiting
+            .iter()
+            .copied()
+            .chain(ahead)
+            .take(window)
+            .find(|ix| matches!(&self.tokens[*ix].kind, TokenKind::StartTag { name, .. } if name == tag))
+    }
+    /// Puts a frame's children back and finds the element's end tag.
+    fn leave(&mut self, frame: Frame) -> Node {
+        let children = Fragment::from_nodes(frame.done);
+        let mut element = match frame.parent {
+            Node::Element(element) => Element { children, ..element },
+            _ => return Node::Fragment(children),
+        };
+        let Some(start_tag) = frame.start_tag else {
+            return Node::Element(element)
+        };
+        let mut end = frame.end;
+        if !crate::constants::is_void_tag(&element.tag) {
+            let tag = element.tag.as_normalized();
+            let end_tag = self.upcoming()
+                .skip_while(|(_, token)| token.range.start < end)
+                .take_while(|(_, token)| !matches!(token.kind, TokenKind::StartTag { .. }))
+                .find(|(_, token)| matches!(&token.kind, TokenKind::EndTag { name } if name == tag))
+                .map(|(ix, token)| (ix, token.range.end));
+            match end_tag {
+                Some((ix, token_end)) => {
+                    self.claim(ix);
+                    if let Some(raw) = element.raw.as_mut() {
+                        let text = &self.index.source()[self.tokens[ix].range.clone()];
+                        raw.end = Some(RawSource::end_tag(text, element.tag.as_original()));
+                    }
+                    end = token_end;
+                }
+                // Leaving out an end tag html5ever implied could change what
+                // the element ends up containing; only an empty, self-closed
+                // element keeps its source text without one.
+                None => {
+                    let closed = element.children.is_empty()
+                        && element.raw.as_ref().is_some_and(|raw| raw.start.as_str().ends_with("/>"));
+                    if !closed {
+                        element.raw = None;
+                    }
+                }
+            }
+        }
+        element.span = Some(self.span(start_tag.start..end));
+        Node::Element(element)
+    }
+    fn locate_attributes(&self, attributes: &mut AttributeMap, source: &[(String, Range<usize>)]) {
+        let located = std::mem::take(attributes)
+            .into_iter()
+            .map(|(key, value)| {
+                let name = key.as_str().to_ascii_lowercase();
+                let range = source
+                    .iter()
+                    .find(|(x, _)| *x == name || x.rsplit(':').next() == Some(name.as_str()))
+                    .map(|(_, range)| range.clone());
+                match range {
+                    Some(range) => (key.with_span(self.span(range)), value),
+                    None => (key, value),
+                }
+            })
+            .collect::<AttributeMap>();
+        *attributes = located;
+    }
+    /// Keeps the character references of the attribute values the native
+    /// tokenizer reads from an element's start tag as the element has them,
+    /// and the source text of the tag if it reads all of its attributes (in
+    /// any order, as scraper sorts attributes by name).
+    fn keep_start_tag(&self, element: &mut Element, range: Range<usize>) {
+        if !self.lossless && !self.references {
+            return
+        }
+        let text = &self.index.source()[range];
+        let tokens = Tokenizer::new(text).with_character_references(self.references).next();
+        let Some(tokenizer::Token { kind: tokenizer::TokenKind::StartTag(tag), .. }) = tokens else {
+            return
+        };
+        if self.references {
+            for (key, value) in element.attributes.iter_mut() {
+                let token = tag.attributes.iter().find(|token| {
+                    token.name.eq_ignore_ascii_case(key.as_str()) && token.value == value.as_str()
+                });
+                if let Some(token) = token.filter(|token| !token.references.is_empty()) {
+                    *value = AttributeValueBuf::referenced(token.value.as_ref(), token.references.clone());
+                }
+            }
+        }
+        if !self.lossless {
+            return
+        }
+        let agrees = tag.name.eq_ignore_ascii_case(element.tag.as_original())
+            && tag.attributes.len() == element.attributes.len()
+            && tag.attributes.iter().all(|token| {
+                element.attributes.iter().any(|(key, value)| {
+                    token.name.eq_ignore_ascii_case(key.as_str()) && token.value == value.as_str()
+                })
+            });
+        if !agrees {
+            return
+        }
+        let attributes = element.attributes.iter().map(|(key, value)| (key.as_str(), value.as_str()));
+        let start = RawSource::start_tag(text, element.tag.as_original(), attributes, []);
+        element.raw = Some(RawTags { start, end: None });
+    }
+    /// Matches text against the following text tokens, which html5ever may
+    /// have joined (across end tags and the like). The tokens are walked
+    /// lazily, and the search stops at the first token that cannot start the
+    /// text and is not whitespace html5ever could have dropped.
+    fn visit_text(&mut self, text: &mut Text) {
+        let source = self.index.source();
+        let target = text.value.as_str();
+        let mut candidates = self.upcoming()
+            .take_while(|(_, token)| !matches!(token.kind, TokenKind::StartTag { .. }))
+            .filter(|(_, token)| matches!(token.kind, TokenKind::Text))
+            .map(|(ix, token)| (ix, token.range.clone()));
+        let mut matched = Vec::new();
+        'search: while let Some(first) = candidates.next() {
+            let mut accumulated = String::new();
+            let mut run = Vec::new();
+            for (ix, range) in std::iter::once(first.clone()).chain(candidates.clone()) {
+                let raw = &source[range.clone()];
+                let is_first = run.is_empty();
+                let decoded = normalize_text(raw, true);
+                let decoded = if is_first && !target.starts_with('\n') {
+                    decoded.strip_prefix('\n').map(ToOwned::to_owned).unwrap_or(decoded)
+                } else {
+                    decoded
+                };
+                if decoded.is_empty() && is_first {
+                    break
+                }
+                accumulated.push_str(&decoded);
+                run.push((ix, range));
+                if accumulated == target || (is_first && normalize_text(raw, false) == target) {
+                    matched = run;
+                    break 'search
+                }
+                if !target.starts_with(accumulated.as_str()) {
+                    break
+                }
+            }
+            if !source[first.1].trim().is_empty() {
+                break
+            }
+        }
+        drop(candidates);
+        let (Some((_, first)), Some((_, last))) = (matched.first(), matched.last()) else {
+            return
+        };
+        text.span = Some(self.span(first.start..last.end));
+        // Text html5ever joined across tokens has no source text of its own.
+        if matched.len() == 1 {
+            let raw = &source[first.clone()];
+            if self.lossless {
+                text.raw = Some(RawSource::text(raw, &text.value, text.cdata));
+            }
+            if self.references
+                && let Some(tokenizer::Token { kind: tokenizer::TokenKind::Text(value, references), .. }) =
+                    Tokenizer::new(raw).with_character_references(true).next()
+                && value == text.value.as_str() {
+                text.references = references;
+            }
+        }
+        for (ix, _) in matched {
+            self.claim(ix);
+        }
+    }
+}
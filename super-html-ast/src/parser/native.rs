@@ -0,0 +1,634 @@
+//! A lenient, dependency-free HTML/XML parser.
+//!
+//! Unlike the HTML5 backend, the native parser builds the tree exactly as it
+//! is written: no `<html>`/`<head>`/`<body>`/`<tbody>` is ever inserted, nothing
+//! is moved out of tables, unknown and custom tags keep their original case,
+//! and a self-closing slash closes any element. The only structural repairs it
+//! makes are the implied end tags HTML allows to be omitted (`<li>`, `<p>`,
+//! `<td>`, …) and closing whatever is left open by a mismatched end tag or the
+//! end of input; both are reported as diagnostics where they indicate an error.
+//!
+//! The same machinery parses XML (see [`parse_xml`]), where nothing is implied
+//! at all and every well-formedness problem is an error.
+use std::borrow::Cow;
+use std::ops::Range;
+use crate::{Atom, AttributeKeyBuf, AttributeValueBuf, CharacterReference, Doctype, Element, Fragment, LineIndex, Node, Placeholder, ProcessingInstruction, RawSource, RawTags, Span, TagBuf, Text};
+use crate::constants::{is_void_tag, HTML_NAMESPACE, MATHML_NAMESPACE, SVG_NAMESPACE, XLINK_NAMESPACE, XMLNS_NAMESPACE, XML_NAMESPACE};
+use super::{DiagnosticCode, LimitTracker, ParseDiagnostic, ParseLimits, ParseResult, ParserOptions};
+
+pub mod tokenizer;
+
+use tokenizer::{AttributeToken, Syntax, TagToken, Token, TokenKind, Tokenizer};
+
+/// Parses `source` with the native parser. The output is always a
+/// [`Node::Fragment`] of the top-level nodes, in source order.
+pub fn parse(source: impl AsRef<str>) -> ParseResult<Node> {
+    parse_with(source.as_ref(), Syntax::Html, None, LimitTracker::new(&ParseLimits::default()), &ParserOptions::default())
+}
+
+/// Like [`parse`], reading `source` as the contents of `context`: raw text
+/// contexts (`script`, `textarea`, …) take the input verbatim, and inside `svg`
+/// or `math` the top-level elements get the SVG or MathML namespace.
+pub fn parse_in_context(source: impl AsRef<str>, context: Option<&TagBuf>) -> ParseResult<Node> {
+    parse_with(source.as_ref(), Syntax::Html, context, LimitTracker::new(&ParseLimits::default()), &ParserOptions::default())
+}
+
+/// Parses `source` as XML. Names keep their case, `<x/>` closes any element,
+/// CDATA sections become [`Text::cdata`] nodes, prefixes are resolved against
+/// the `xmlns` declarations in scope (see [`TagBuf::namespace`] and
+/// [`AttributeKeyBuf::namespace`]), and well-formedness violations are
+/// reported as errors. The output is a [`Node::Fragment`] holding the root
+/// element along with any surrounding declarations, comments and processing
+/// instructions.
+pub fn parse_xml(source: impl AsRef<str>) -> ParseResult<Node> {
+    parse_with(source.as_ref(), Syntax::Xml, None, LimitTracker::new(&ParseLimits::default()), &ParserOptions::default())
+}
+
+pub(super) fn parse_with(
+    source: &str,
+    syntax: Syntax,
+    context: Option<&TagBuf>,
+    limits: LimitTracker,
+    options: &ParserOptions,
+) -> ParseResult<Node> {
+    build(source, syntax, context, limits, options, OwnedNodes { lossless: options.lossless })
+}
+
+/// Tokenizes `source` and assembles the tokens into a tree of `nodes`.
+pub(super) fn build<'a, N: TreeNodes<'a>>(
+    source: &'a str,
+    syntax: Syntax,
+    context: Option<&TagBuf>,
+    limits: LimitTracker,
+    options: &ParserOptions,
+    nodes: N,
+) -> ParseResult<N::Node> {
+    let tokenizer = match syntax {
+        Syntax::Html => Tokenizer::new(source),
+        Syntax::Xml => Tokenizer::xml(source),
+    };
+    let mut tokenizer = tokenizer
+        .with_character_references(options.character_references)
+        .with_placeholders(options.placeholders.clone())
+        .with_scripting(options.scripting_enabled)
+        .with_max_attributes(options.limits.max_attributes);
+    let mut builder = Builder::new(source, syntax, nodes);
+    builder.limits = limits;
+    if let Some(context) = context {
+        tokenizer = tokenizer.in_raw_text_context(context.as_normalized());
+        let context = match context.namespace() {
+            Some(_) => context.clone(),
+            None => context.clone().with_namespace(Atom::new(element_namespace(context, None))),
+        };
+        builder.context = Some(context);
+    }
+    for token in tokenizer.by_ref() {
+        if builder.limits.stopped() {
+            break
+        }
+        builder.push_token(token);
+    }
+    builder.extend_diagnostics(tokenizer.take_diagnostics());
+    builder.finish()
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TREE BUILDER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Assembles tokens into a [`Node`] tree using a stack of open elements.
+pub struct TreeBuilder<'a>(Builder<'a, OwnedNodes>);
+
+impl<'a> TreeBuilder<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self(Builder::new(source, Syntax::Html, OwnedNodes { lossless: false }))
+    }
+    pub fn xml(source: &'a str) -> Self {
+        Self(Builder::new(source, Syntax::Xml, OwnedNodes { lossless: false }))
+    }
+    pub fn with_context(mut self, context: TagBuf) -> Self {
+        self.0.context = Some(context);
+        self
+    }
+    /// Keeps the source text of text nodes, tags and doctypes.
+    pub fn with_lossless(mut self, lossless: bool) -> Self {
+        self.0.nodes.lossless = lossless;
+        self
+    }
+    pub fn push_token(&mut self, token: Token<'a>) {
+        self.0.push_token(token);
+    }
+    /// Adds tokenizer diagnostics (reported as byte ranges).
+    pub fn extend_diagnostics(&mut self, diagnostics: impl IntoIterator<Item = (ParseDiagnostic, Range<usize>)>) {
+        self.0.extend_diagnostics(diagnostics);
+    }
+    /// Closes everything still open and returns the tree.
+    pub fn finish(self) -> ParseResult<Node> {
+        self.0.finish()
+    }
+}
+
+/// The nodes a [`Builder`] assembles: [`Node`]s, or the [`crate::borrowed`]
+/// nodes of [`super::parse_borrowed`]. The builder makes every structural
+/// decision; these only construct and attach nodes.
+pub(super) trait TreeNodes<'a> {
+    type Node;
+    type Element;
+    type Fragment: Default;
+    fn push(fragment: &mut Self::Fragment, node: Self::Node);
+    fn has_element(fragment: &Self::Fragment) -> bool;
+    fn children(element: &mut Self::Element) -> &mut Self::Fragment;
+    fn fragment(fragment: Self::Fragment) -> Self::Node;
+    /// An element without attributes or children, for `tag` (with its
+    /// namespace) read from the start tag `token`, written as `raw`.
+    fn element(&self, tag: TagBuf, token: &TagToken<'a>, raw: &'a str) -> Self::Element;
+    fn attribute(element: &mut Self::Element, attribute: AttributeToken<'a>, namespace: Option<String>, span: Span);
+    /// A placeholder among the attributes, after `position` of them.
+    fn placeholder_attribute(element: &mut Self::Element, position: usize, placeholder: Placeholder);
+    /// The end tag of `element`, written as `raw`.
+    fn end_tag(&self, element: &mut Self::Element, raw: &'a str);
+    fn close(element: Self::Element, span: Span) -> Self::Node;
+    /// Appends `text` to `children`, or to the text node it follows.
+    fn text(&self, children: &mut Self::Fragment, text: TextRun<'a>, limits: &mut LimitTracker);
+    fn comment(comment: Cow<'a, str>) -> Self::Node;
+    fn doctype(&self, doctype: Doctype, raw: &'a str) -> Self::Node;
+    fn processing_instruction(pi: ProcessingInstruction) -> Self::Node;
+    fn placeholder(placeholder: Placeholder) -> Self::Node;
+}
+
+/// A text or CDATA token, for [`TreeNodes::text`].
+pub(super) struct TextRun<'a> {
+    pub value: Cow<'a, str>,
+    pub references: Vec<CharacterReference>,
+    /// A CDATA section in XML.
+    pub cdata: bool,
+    /// The source text.
+    pub raw: &'a str,
+    pub span: Span,
+}
+
+pub(super) struct Builder<'a, N: TreeNodes<'a>> {
+    index: LineIndex<'a>,
+    syntax: Syntax,
+    nodes: N,
+    root: N::Fragment,
+    /// The element the parsed nodes will be inserted into, if known.
+    pub(super) context: Option<TagBuf>,
+    stack: Vec<OpenElement<N::Element>>,
+    /// End offset of the most recently consumed token.
+    last_end: usize,
+    pub(super) limits: LimitTracker,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+struct OpenElement<E> {
+    /// The tag, for the structural decisions made on it.
+    tag: TagBuf,
+    /// `None` if dropped for exceeding a limit: consumes its end tag and contents without output.
+    element: Option<E>,
+    start: usize,
+    /// `(prefix, uri)` pairs declared on this element; the default namespace has an empty prefix.
+    namespaces: Vec<(String, String)>,
+}
+
+impl<'a, N: TreeNodes<'a>> Builder<'a, N> {
+    pub(super) fn new(source: &'a str, syntax: Syntax, nodes: N) -> Self {
+        Self {
+            index: LineIndex::new(source),
+            syntax,
+            nodes,
+            root: N::Fragment::default(),
+            context: None,
+            stack: Vec::new(),
+            last_end: 0,
+            limits: LimitTracker::new(&ParseLimits::default()),
+            diagnostics: Vec::new(),
+        }
+    }
+    pub(super) fn push_token(&mut self, token: Token<'a>) {
+        let range = token.range.clone();
+        match token.kind {
+            TokenKind::StartTag(tag) => self.start_tag(tag, range.clone()),
+            TokenKind::EndTag(tag) => self.end_tag(tag, range.clone()),
+            TokenKind::Text(text, references) => self.text(text, references, false, range.clone()),
+            TokenKind::CData(text) => self.text(text, Vec::new(), self.syntax == Syntax::Xml, range.clone()),
+            TokenKind::Comment(comment) => self.append(N::comment(comment), range.clone()),
+            TokenKind::Doctype(doctype) => {
+                let node = self.nodes.doctype(doctype, self.raw(&range));
+                self.append(node, range.clone())
+            }
+            TokenKind::ProcessingInstruction(pi) => self.append(N::processing_instruction(pi), range.clone()),
+            TokenKind::Placeholder(placeholder) => {
+                let span = self.index.span(range.clone());
+                self.append(N::placeholder(placeholder.with_span(span)), range.clone())
+            }
+        }
+        self.last_end = range.end;
+    }
+    /// Adds tokenizer diagnostics (reported as byte ranges).
+    pub(super) fn extend_diagnostics(&mut self, diagnostics: impl IntoIterator<Item = (ParseDiagnostic, Range<usize>)>) {
+        for (diagnostic, range) in diagnostics {
+            let span = self.index.span(range);
+            self.diagnostics.push(diagnostic.with_span(span));
+        }
+    }
+    /// Closes everything still open and returns the tree.
+    pub(super) fn finish(mut self) -> ParseResult<N::Node> {
+        let end = self.index.source().len();
+        let stopped = self.limits.stopped();
+        while let Some(open) = self.stack.pop() {
+            let is_optional = self.syntax == Syntax::Html && has_optional_end_tag(&open.tag);
+            if !stopped && open.element.is_some() && !is_optional {
+                let message = format!("Unclosed <{}> element at end of input", open.tag);
+                self.report(DiagnosticCode::UnclosedElement, message, open.start..end);
+            }
+            self.close(open, end);
+        }
+        if self.syntax == Syntax::Xml && !N::has_element(&self.root) && !stopped {
+            self.report(DiagnosticCode::UnexpectedEof, "Document has no root element".to_string(), end..end);
+        }
+        self.diagnostics.append(&mut self.limits.diagnostics);
+        self.diagnostics.sort_by_key(|x| x.span.map(|span| span.start.offset));
+        ParseResult { output: N::fragment(self.root), diagnostics: self.diagnostics, encoding: None }
+    }
+    /// The source text of `range`.
+    fn raw(&self, range: &Range<usize>) -> &'a str {
+        &self.index.source()[range.clone()]
+    }
+    fn report(&mut self, code: DiagnosticCode, message: String, range: Range<usize>) {
+        let span = self.index.span(range);
+        self.diagnostics.push(ParseDiagnostic::new(code, message).with_span(span));
+    }
+    fn current_children(&mut self) -> &mut N::Fragment {
+        match self.stack.last_mut().and_then(|x| x.element.as_mut()) {
+            Some(element) => N::children(element),
+            None => &mut self.root,
+        }
+    }
+    /// Whether content at the current position is being dropped.
+    fn discarding(&self) -> bool {
+        self.stack.last().is_some_and(|x| x.element.is_none())
+    }
+    fn append(&mut self, node: N::Node, range: Range<usize>) {
+        if self.discarding() || !self.limits.admit_node(Some(self.index.span(range))) {
+            return
+        }
+        N::push(self.current_children(), node);
+    }
+    fn text(&mut self, value: Cow<'a, str>, references: Vec<CharacterReference>, cdata: bool, range: Range<usize>) {
+        if self.discarding() {
+            return
+        }
+        if self.syntax == Syntax::Xml && self.stack.is_empty() && !value.trim().is_empty() {
+            self.report(DiagnosticCode::UnexpectedToken, "Text outside the root element".to_string(), range.clone());
+        }
+        let text = TextRun { value, references, cdata, raw: self.raw(&range), span: self.index.span(range) };
+        let children = match self.stack.last_mut().and_then(|x| x.element.as_mut()) {
+            Some(element) => N::children(element),
+            None => &mut self.root,
+        };
+        self.nodes.text(children, text, &mut self.limits);
+    }
+    fn start_tag(&mut self, tag: TagToken<'a>, range: Range<usize>) {
+        let namespaces = match self.syntax {
+            Syntax::Html => Vec::new(),
+            Syntax::Xml => namespace_declarations(&tag),
+        };
+        let mut tag_buf = TagBuf::new(&tag.name);
+        match self.syntax {
+            Syntax::Html => {
+                while let Some(open) = self.stack.last() {
+                    if !implicitly_closes(&tag_buf, &open.tag) {
+                        break
+                    }
+                    let open = self.stack.pop().unwrap();
+                    self.close(open, self.last_end);
+                }
+            }
+            Syntax::Xml => {
+                if self.stack.is_empty() && N::has_element(&self.root) {
+                    let message = format!("Element <{tag_buf}> after the root element");
+                    self.report(DiagnosticCode::UnexpectedToken, message, range.clone());
+                }
+            }
+        }
+        let tag_span = Some(self.index.span(range.clone()));
+        let discarded = self.discarding()
+            || !self.limits.admit_depth(self.stack.len() + 1, tag_span)
+            || !self.limits.admit_node(tag_span);
+        if discarded {
+            let is_void = self.syntax == Syntax::Html && is_void_tag(&tag_buf);
+            if !tag.self_closing && !is_void {
+                self.stack.push(OpenElement { tag: tag_buf, element: None, start: range.start, namespaces: Vec::new() });
+            }
+            return
+        }
+        let keep = self.limits.admit_attributes(tag.attributes.len() + tag.skipped_attributes, tag_span);
+        let attribute_namespaces = tag.attributes[..keep]
+            .iter()
+            .map(|attribute| {
+                let range = attribute.range.clone();
+                attribute_namespace(self.syntax, &attribute.name, |prefix| self.resolve_prefix(prefix, &namespaces, range))
+            })
+            .collect::<Vec<_>>();
+        let namespace = match self.syntax {
+            Syntax::Html => {
+                let parent = self.stack.last().map(|x| &x.tag).or(self.context.as_ref());
+                Some(Atom::new(element_namespace(&tag_buf, parent)))
+            }
+            Syntax::Xml => {
+                let prefix = tag_buf.prefix().unwrap_or_default().to_string();
+                self.resolve_prefix(&prefix, &namespaces, range.clone()).map(Atom::from)
+            }
+        };
+        tag_buf = tag_buf.with_namespace(namespace);
+        let mut element = self.nodes.element(tag_buf.clone(), &tag, self.raw(&range));
+        for (mut attribute, namespace) in tag.attributes.into_iter().zip(attribute_namespaces) {
+            let span = self.index.span(attribute.range.clone());
+            self.limits.truncate_attribute(&mut attribute.value, Some(span));
+            attribute.references.retain(|x| x.applies_to(&attribute.value));
+            N::attribute(&mut element, attribute, namespace, span);
+        }
+        for (position, placeholder, range) in tag.placeholders {
+            N::placeholder_attribute(&mut element, position, placeholder.with_span(self.index.span(range)));
+        }
+        let open = OpenElement { tag: tag_buf, element: Some(element), start: range.start, namespaces };
+        let is_void = self.syntax == Syntax::Html && is_void_tag(&open.tag);
+        if tag.self_closing || is_void {
+            self.close(open, range.end);
+            return
+        }
+        self.stack.push(open);
+    }
+    /// Looks `prefix` up in `declared` (the element being opened) and then the
+    /// open elements, innermost first. An empty prefix is the default namespace.
+    fn resolve_prefix(&mut self, prefix: &str, declared: &[(String, String)], range: Range<usize>) -> Option<String> {
+        let in_scope = declared.iter().chain(self.stack.iter().rev().flat_map(|x| x.namespaces.iter()));
+        match lookup_prefix(prefix, in_scope) {
+            Ok(namespace) => namespace,
+            Err(message) => {
+                self.report(DiagnosticCode::UndeclaredNamespacePrefix, message, range);
+                None
+            }
+        }
+    }
+    fn end_tag(&mut self, tag: TagToken<'a>, range: Range<usize>) {
+        let tag_buf = TagBuf::new(&tag.name);
+        let matches = |open: &OpenElement<N::Element>| match self.syntax {
+            Syntax::Html => open.tag.matches(&tag_buf),
+            Syntax::Xml => open.tag.as_original() == tag_buf.as_original(),
+        };
+        let Some(position) = self.stack.iter().rposition(matches) else {
+            let message = format!("End tag </{tag_buf}> without a matching start tag");
+            self.report(DiagnosticCode::UnmatchedEndTag, message, range);
+            return
+        };
+        if self.syntax == Syntax::Xml && position + 1 < self.stack.len() {
+            let expected = self.stack.last().unwrap().tag.to_string();
+            let message = format!("Expected </{expected}>, found </{tag_buf}>");
+            self.report(DiagnosticCode::MisnestedTags, message, range.clone());
+        }
+        while self.stack.len() > position + 1 {
+            let open = self.stack.pop().unwrap();
+            if self.syntax == Syntax::Html && open.element.is_some() && !has_optional_end_tag(&open.tag) {
+                let message = format!("Unclosed <{}> element closed by </{tag_buf}>", open.tag);
+                self.report(DiagnosticCode::UnclosedElement, message, open.start..range.start);
+            }
+            self.close(open, range.start);
+        }
+        let mut open = self.stack.pop().unwrap();
+        if let Some(element) = open.element.as_mut() {
+            self.nodes.end_tag(element, self.raw(&range));
+        }
+        self.close(open, range.end);
+    }
+    /// Finalizes an open element and attaches it to its parent.
+    fn close(&mut self, open: OpenElement<N::Element>, end: usize) {
+        let Some(element) = open.element else {
+            return
+        };
+        let span = self.index.span(open.start..end.max(open.start));
+        N::push(self.current_children(), N::close(element, span));
+    }
+}
+
+/// Builds [`Node`]s.
+pub(super) struct OwnedNodes {
+    /// Whether to keep the raw source of nodes (see [`crate::RawSource`]).
+    lossless: bool,
+}
+
+impl<'a> TreeNodes<'a> for OwnedNodes {
+    type Node = Node;
+    type Element = Element;
+    type Fragment = Fragment;
+    fn push(fragment: &mut Fragment, node: Node) {
+        fragment.push(node);
+    }
+    fn has_element(fragment: &Fragment) -> bool {
+        fragment.iter().any(|x| x.as_element().is_some())
+    }
+    fn children(element: &mut Element) -> &mut Fragment {
+        &mut element.children
+    }
+    fn fragment(fragment: Fragment) -> Node {
+        Node::Fragment(fragment)
+    }
+    fn element(&self, tag: TagBuf, token: &TagToken<'a>, raw: &'a str) -> Element {
+        // Raw text with attributes the tree lacks would put them back.
+        let raw = (self.lossless && token.skipped_attributes == 0).then(|| {
+            let attributes = token.attributes.iter().map(|x| (x.name.as_ref(), x.value.as_ref()));
+            let placeholders = token.placeholders.iter().map(|(position, placeholder, _)| (*position, placeholder));
+            RawTags { start: RawSource::start_tag(raw, &token.name, attributes, placeholders), end: None }
+        });
+        let mut element = Element { raw, ..Element::new(tag) };
+        // An XML element without a namespace is not HTML.
+        element.template_contents &= element.tag.namespace().is_some();
+        element
+    }
+    fn attribute(element: &mut Element, attribute: AttributeToken<'a>, namespace: Option<String>, span: Span) {
+        let key = AttributeKeyBuf::new(attribute.name).with_span(span).with_namespace(namespace.map(Atom::from));
+        element.attributes.insert(key, AttributeValueBuf::referenced(attribute.value, attribute.references));
+    }
+    fn placeholder_attribute(element: &mut Element, position: usize, placeholder: Placeholder) {
+        element.placeholders.push((position, placeholder));
+    }
+    fn end_tag(&self, element: &mut Element, raw: &'a str) {
+        if let Some(tags) = element.raw.as_mut() {
+            tags.end = Some(RawSource::end_tag(raw, element.tag.as_original()));
+        }
+    }
+    fn close(element: Element, span: Span) -> Node {
+        Node::Element(element.with_span(span))
+    }
+    fn text(&self, children: &mut Fragment, text: TextRun<'a>, limits: &mut LimitTracker) {
+        let TextRun { value, references, cdata, raw, span } = text;
+        let raw = self.lossless.then_some(raw);
+        let last = children.len().checked_sub(1);
+        if let Some(Node::Text(previous)) = last.and_then(|ix| children.get_mut(ix))
+            && !previous.cdata
+            && !cdata {
+            let offset = previous.value.len();
+            let references = references.into_iter().map(|x| CharacterReference { offset: x.offset + offset, ..x });
+            previous.references.extend(references);
+            previous.value.push_str(&value);
+            previous.span = previous.span.map(|x| x.merge(&span));
+            previous.raw = previous.raw.take().zip(raw).map(|(previous_raw, raw)| {
+                RawSource::text(format!("{}{raw}", previous_raw.as_str()), &previous.value, false)
+            });
+            let len = previous.value.len();
+            limits.truncate_text(&mut previous.value, Some(span));
+            if previous.value.len() != len {
+                previous.raw = None;
+                previous.references.retain(|x| x.applies_to(&previous.value));
+            }
+            return
+        }
+        if !limits.admit_node(Some(span)) {
+            return
+        }
+        let mut text = match cdata {
+            true => Text::cdata(value),
+            false => Text::new(value).with_references(references),
+        };
+        text.raw = raw.map(|raw| RawSource::text(raw, &text.value, text.cdata));
+        let len = text.value.len();
+        limits.truncate_text(&mut text.value, Some(span));
+        if text.value.len() != len {
+            text.raw = None;
+            text.references.retain(|x| x.applies_to(&text.value));
+        }
+        children.push(Node::Text(text.with_span(span)));
+    }
+    fn comment(comment: Cow<'a, str>) -> Node {
+        Node::Comment(comment.into_owned())
+    }
+    fn doctype(&self, mut doctype: Doctype, raw: &'a str) -> Node {
+        if self.lossless {
+            doctype.raw = Some(RawSource::doctype(raw, &doctype));
+        }
+        Node::Doctype(doctype)
+    }
+    fn processing_instruction(pi: ProcessingInstruction) -> Node {
+        Node::ProcessingInstruction(pi)
+    }
+    fn placeholder(placeholder: Placeholder) -> Node {
+        Node::Placeholder(placeholder)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL HELPERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Elements whose end tag HTML allows to be left out.
+static OPTIONAL_END_TAGS: &[&str] = &[
+    "li", "dt", "dd", "p", "rt", "rp", "optgroup", "option", "colgroup", "caption",
+    "thead", "tbody", "tfoot", "tr", "td", "th", "html", "head", "body",
+];
+
+/// Start tags that implicitly close an open `<p>`.
+static CLOSES_P: &[&str] = &[
+    "address", "article", "aside", "blockquote", "details", "dialog", "div", "dl", "fieldset",
+    "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header",
+    "hgroup", "hr", "main", "menu", "nav", "ol", "p", "pre", "section", "table", "ul",
+];
+
+pub(super) fn has_optional_end_tag(tag: &TagBuf) -> bool {
+    OPTIONAL_END_TAGS.contains(&tag.as_normalized())
+}
+
+/// The `xmlns` and `xmlns:prefix` declarations of a start tag.
+pub(super) fn namespace_declarations(tag: &TagToken) -> Vec<(String, String)> {
+    tag.attributes
+        .iter()
+        .filter_map(|attribute| {
+            let prefix = match attribute.name.as_ref() {
+                "xmlns" => "",
+                name => name.strip_prefix("xmlns:")?,
+            };
+            Some((prefix.to_string(), attribute.value.to_string()))
+        })
+        .collect()
+}
+
+/// The namespace of an attribute called `name`. `resolve` looks up XML
+/// prefixes (see [`lookup_prefix`]).
+pub(super) fn attribute_namespace(
+    syntax: Syntax,
+    name: &str,
+    resolve: impl FnOnce(&str) -> Option<String>,
+) -> Option<String> {
+    match (syntax, name.split_once(':').map(|(prefix, _)| prefix)) {
+        _ if name == "xmlns" => Some(XMLNS_NAMESPACE.to_string()),
+        (_, Some("xmlns")) => Some(XMLNS_NAMESPACE.to_string()),
+        // Unprefixed attributes are in no namespace, whatever the default.
+        (_, None) => None,
+        (Syntax::Xml, Some(prefix)) => resolve(prefix),
+        // HTML only knows the fixed prefixes of foreign attributes.
+        (Syntax::Html, Some("xlink")) => Some(XLINK_NAMESPACE.to_string()),
+        (Syntax::Html, Some("xml")) => Some(XML_NAMESPACE.to_string()),
+        (Syntax::Html, Some(_)) => None,
+    }
+}
+
+/// Looks an XML `prefix` up among the `(prefix, uri)` declarations `in_scope`,
+/// innermost first. An empty prefix is the default namespace. Fails with a
+/// message if the prefix is not declared.
+pub(super) fn lookup_prefix<'a>(
+    prefix: &str,
+    mut in_scope: impl Iterator<Item = &'a (String, String)>,
+) -> Result<Option<String>, String> {
+    if prefix == "xml" {
+        return Ok(Some(XML_NAMESPACE.to_string()))
+    }
+    match in_scope.find(|(declared, _)| declared == prefix) {
+        // `xmlns=""` undeclares the default namespace.
+        Some((_, uri)) if uri.is_empty() => Ok(None),
+        Some((_, uri)) => Ok(Some(uri.clone())),
+        None if prefix.is_empty() => Ok(None),
+        None => Err(format!("Namespace prefix {prefix:?} is not declared")),
+    }
+}
+
+/// The namespace of an element in HTML, as the HTML5 backend assigns it:
+/// `<svg>` and `<math>` open SVG and MathML content, which their descendants
+/// inherit up to an HTML integration point (`<foreignObject>`,
+/// `<annotation-xml>`); everything else is HTML.
+pub(super) fn element_namespace(tag: &TagBuf, parent: Option<&TagBuf>) -> &'static str {
+    match tag.as_normalized() {
+        "svg" => return SVG_NAMESPACE,
+        "math" => return MATHML_NAMESPACE,
+        _ => (),
+    }
+    let Some(parent) = parent else {
+        return HTML_NAMESPACE
+    };
+    if matches!(parent.as_normalized(), "foreignobject" | "annotation-xml") {
+        return HTML_NAMESPACE
+    }
+    match parent.namespace() {
+        Some(SVG_NAMESPACE) => SVG_NAMESPACE,
+        Some(MATHML_NAMESPACE) => MATHML_NAMESPACE,
+        _ => HTML_NAMESPACE,
+    }
+}
+
+/// Whether a `new` start tag ends the currently `open` element.
+pub(super) fn implicitly_closes(new: &TagBuf, open: &TagBuf) -> bool {
+    let new = new.as_normalized();
+    match open.as_normalized() {
+        "p" => CLOSES_P.contains(&new),
+        "li" => new == "li",
+        "dt" | "dd" => matches!(new, "dt" | "dd"),
+        "rt" | "rp" => matches!(new, "rt" | "rp"),
+        "option" => matches!(new, "option" | "optgroup"),
+        "optgroup" => new == "optgroup",
+        "td" | "th" => matches!(new, "td" | "th" | "tr" | "thead" | "tbody" | "tfoot"),
+        "tr" => matches!(new, "tr" | "thead" | "tbody" | "tfoot"),
+        "thead" | "tbody" | "tfoot" => matches!(new, "thead" | "tbody" | "tfoot"),
+        "colgroup" => !matches!(new, "col" | "template"),
+        "caption" => matches!(new, "colgroup" | "thead" | "tbody" | "tfoot" | "tr"),
+        _ => false,
+    }
+}
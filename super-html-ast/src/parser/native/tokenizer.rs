@@ -0,0 +1,798 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ops::Range;
+use crate::{CharacterReference, Doctype, Placeholder, ProcessingInstruction};
+use crate::parser::{DiagnosticCode, ParseDiagnostic, PlaceholderSearch, PlaceholderSyntax};
+
+// ————————————————————————————————————————————————————————————————————————————
+// TOKENS
+// ————————————————————————————————————————————————————————————————————————————
+
+#[derive(Debug, Clone)]
+pub struct AttributeToken<'a> {
+    /// The attribute name exactly as written.
+    pub name: Cow<'a, str>,
+    /// The value with character references resolved (empty for bare attributes).
+    pub value: Cow<'a, str>,
+    /// The references resolved in `value`, if kept.
+    pub references: Vec<CharacterReference>,
+    /// From the start of the name to the end of the (possibly quoted) value.
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TagToken<'a> {
+    /// The tag name exactly as written.
+    pub name: Cow<'a, str>,
+    pub attributes: Vec<AttributeToken<'a>>,
+    /// Template placeholders among the attributes, each with the number of
+    /// attributes before it and where it was written.
+    pub placeholders: Vec<(usize, Placeholder, Range<usize>)>,
+    /// Whether the tag ends in `/>`.
+    pub self_closing: bool,
+    /// Attributes past the limit (see [`Tokenizer::with_max_attributes`]),
+    /// read over but neither kept nor checked for duplicates.
+    pub skipped_attributes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum TokenKind<'a> {
+    StartTag(TagToken<'a>),
+    EndTag(TagToken<'a>),
+    /// Text with character references resolved, and the references if kept.
+    Text(Cow<'a, str>, Vec<CharacterReference>),
+    Comment(Cow<'a, str>),
+    CData(Cow<'a, str>),
+    Doctype(Doctype),
+    ProcessingInstruction(ProcessingInstruction),
+    Placeholder(Placeholder),
+}
+
+/// A token and where it was read from. Names, text and values are slices of
+/// the source unless resolving character references changed them.
+#[derive(Debug, Clone)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub range: Range<usize>,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TOKENIZER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A forgiving markup tokenizer.
+///
+/// Anything that does not look like markup (a stray `<`, an unterminated
+/// quote, …) is kept as text rather than rejected. Problems are collected as
+/// diagnostics carrying byte ranges; the tree builder turns them into spans.
+pub struct Tokenizer<'a> {
+    source: &'a str,
+    syntax: Syntax,
+    position: usize,
+    /// Elements whose contents are read verbatim up to the matching end tag.
+    raw_text_tags: &'static [&'static str],
+    /// Elements whose verbatim contents still resolve character references.
+    escapable_raw_text_tags: &'static [&'static str],
+    /// Set after a raw text start tag.
+    raw_text: Option<RawText>,
+    /// Whether to record the character references resolved in text and attribute values.
+    keep_references: bool,
+    placeholders: Option<Placeholders>,
+    max_attributes: Option<usize>,
+    diagnostics: Vec<(ParseDiagnostic, Range<usize>)>,
+}
+
+/// Pending raw text contents.
+struct RawText {
+    /// The (lowercase) name whose end tag ends the raw text; `None` reads to the end of input.
+    end_tag: Option<String>,
+    /// Whether character references are resolved.
+    escapable: bool,
+}
+
+/// The markup language being tokenized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Lenient HTML: raw text elements, all HTML named character references,
+    /// case-insensitive duplicate attribute detection.
+    Html,
+    /// XML: no raw text elements, only the predefined entities, exact-case
+    /// names, and well-formedness problems are reported as errors.
+    Xml,
+}
+
+/// `<script>`-like elements of HTML.
+pub const HTML_RAW_TEXT_TAGS: &[&str] = &[
+    "script", "style", "xmp", "iframe", "noembed", "noframes", "textarea", "title", "plaintext",
+];
+
+/// [`HTML_RAW_TEXT_TAGS`] with `<noscript>`, which is raw text when scripting is enabled.
+pub const HTML_SCRIPTING_RAW_TEXT_TAGS: &[&str] = &[
+    "script", "style", "xmp", "iframe", "noembed", "noframes", "noscript", "textarea", "title", "plaintext",
+];
+
+/// Raw text elements whose contents still resolve character references.
+pub const HTML_ESCAPABLE_RAW_TEXT_TAGS: &[&str] = &["textarea", "title"];
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            syntax: Syntax::Html,
+            position: 0,
+            raw_text_tags: HTML_SCRIPTING_RAW_TEXT_TAGS,
+            escapable_raw_text_tags: HTML_ESCAPABLE_RAW_TEXT_TAGS,
+            raw_text: None,
+            keep_references: false,
+            placeholders: None,
+            max_attributes: None,
+            diagnostics: Vec::new(),
+        }
+    }
+    /// A tokenizer for XML, where `<script>` is an ordinary element.
+    pub fn xml(source: &'a str) -> Self {
+        Self {
+            syntax: Syntax::Xml,
+            raw_text_tags: &[],
+            escapable_raw_text_tags: &[],
+            ..Self::new(source)
+        }
+    }
+    /// Starts out reading raw text if `name` is a raw text element (for
+    /// fragments parsed as the contents of one).
+    pub fn in_raw_text_context(mut self, name: &str) -> Self {
+        // The context's end tag is not part of the input: read through to the end.
+        self.raw_text = self.raw_text_for(name).map(|x| RawText { end_tag: None, ..x });
+        self
+    }
+    /// Starts out reading the contents of an element whose start tag `name`
+    /// was just read, raw text if it is a raw text element (for input
+    /// tokenized in pieces).
+    pub fn after_start_tag(mut self, name: &str) -> Self {
+        self.raw_text = self.raw_text_for(name);
+        self
+    }
+    /// Reads `<noscript>` as raw text (the default for HTML) or, without
+    /// scripting, as markup.
+    pub fn with_scripting(mut self, enabled: bool) -> Self {
+        if self.syntax == Syntax::Html {
+            self.raw_text_tags = if enabled { HTML_SCRIPTING_RAW_TEXT_TAGS } else { HTML_RAW_TEXT_TAGS };
+        }
+        self
+    }
+    /// Records the character references resolved in text and attribute values
+    /// (see [`CharacterReference`]).
+    pub fn with_character_references(mut self, keep: bool) -> Self {
+        self.keep_references = keep;
+        self
+    }
+    /// Keeps at most `limit` attributes per tag, so that a hostile tag costs no
+    /// more than reading over it.
+    pub fn with_max_attributes(mut self, limit: Option<usize>) -> Self {
+        self.max_attributes = limit;
+        self
+    }
+    /// Recognizes template placeholders (see [`Placeholder`]).
+    pub fn with_placeholders(mut self, syntax: Option<PlaceholderSyntax>) -> Self {
+        self.placeholders = syntax.map(Placeholders::new);
+        self
+    }
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+    /// Diagnostics collected so far, with the byte range each one refers to.
+    pub fn take_diagnostics(&mut self) -> Vec<(ParseDiagnostic, Range<usize>)> {
+        std::mem::take(&mut self.diagnostics)
+    }
+    /// How the contents of an element named `name` are read, if it is a raw text element.
+    fn raw_text_for(&self, name: &str) -> Option<RawText> {
+        let name = name.to_ascii_lowercase();
+        if !self.raw_text_tags.contains(&name.as_str()) {
+            return None
+        }
+        let escapable = self.escapable_raw_text_tags.contains(&name.as_str());
+        // Nothing ends `<plaintext>`.
+        let end_tag = Some(name).filter(|x| x != "plaintext");
+        Some(RawText { end_tag, escapable })
+    }
+    fn report(&mut self, code: DiagnosticCode, message: impl Into<String>, range: Range<usize>) {
+        self.diagnostics.push((ParseDiagnostic::new(code, message), range));
+    }
+    fn bytes(&self) -> &'a [u8] {
+        self.source.as_bytes()
+    }
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+    fn find_from(&self, from: usize, needle: &str) -> Option<usize> {
+        self.source.get(from..)?.find(needle).map(|ix| ix + from)
+    }
+    /// The end of the placeholder starting at `ix`, if one does.
+    fn placeholder_at(&mut self, ix: usize) -> Option<usize> {
+        let source = self.source;
+        self.placeholders.as_mut()?.next(source, ix).filter(|x| x.start == ix).map(|x| x.end)
+    }
+    /// The next placeholder starting at or after `from`.
+    fn next_placeholder(&mut self, from: usize) -> Option<Range<usize>> {
+        let source = self.source;
+        self.placeholders.as_mut()?.next(source, from)
+    }
+    /// The first `needle` byte at or after `from` that is not inside a placeholder.
+    fn find_outside_placeholders(&mut self, from: usize, needle: impl Fn(u8) -> bool) -> Option<usize> {
+        let mut ix = from;
+        while ix < self.source.len() {
+            if let Some(end) = self.placeholder_at(ix) {
+                ix = end;
+                continue
+            }
+            if needle(self.bytes()[ix]) {
+                return Some(ix)
+            }
+            ix += 1;
+        }
+        None
+    }
+    /// Does a `<` at `ix` begin markup (as opposed to being literal text)?
+    fn starts_markup(&self, ix: usize) -> bool {
+        let bytes = self.bytes();
+        match bytes.get(ix + 1) {
+            Some(b'!') | Some(b'?') => true,
+            Some(b'/') => bytes.get(ix + 2).is_some_and(u8::is_ascii_alphabetic),
+            Some(x) => x.is_ascii_alphabetic(),
+            None => false,
+        }
+    }
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        if self.position >= self.source.len() {
+            return None
+        }
+        if let Some(raw_text) = self.raw_text.take()
+            && let Some(token) = self.lex_raw_text(raw_text) {
+            return Some(token)
+        }
+        if let Some(end) = self.placeholder_at(self.position) {
+            return Some(self.lex_placeholder(end))
+        }
+        let rest = self.rest();
+        if rest.starts_with('<') && self.starts_markup(self.position) {
+            if rest.starts_with("<!--") {
+                return Some(self.lex_comment())
+            }
+            if rest.starts_with("<![CDATA[") {
+                return Some(self.lex_cdata())
+            }
+            if rest.starts_with("<!") && rest.get(2..9).is_some_and(|x| x.eq_ignore_ascii_case("doctype")) {
+                return Some(self.lex_doctype())
+            }
+            if rest.starts_with("<!") {
+                return Some(self.lex_bogus_comment())
+            }
+            if rest.starts_with("<?") {
+                return Some(self.lex_processing_instruction())
+            }
+            if rest.starts_with("</") {
+                return Some(self.lex_tag(true))
+            }
+            return Some(self.lex_tag(false))
+        }
+        Some(self.lex_text())
+    }
+    fn lex_text(&mut self) -> Token<'a> {
+        let start = self.position;
+        let limit = self.next_placeholder(start).map_or(self.source.len(), |x| x.start);
+        let mut from = start;
+        let end = loop {
+            match self.find_from(from, "<") {
+                Some(ix) if ix >= limit => break limit,
+                Some(ix) if ix > start && self.starts_markup(ix) => break ix,
+                Some(ix) => {
+                    if self.syntax == Syntax::Xml {
+                        self.report(DiagnosticCode::UnexpectedCharacter, "Unescaped '<' in text", ix..ix + 1);
+                    }
+                    from = ix + 1;
+                }
+                None => break limit,
+            }
+        };
+        self.position = end;
+        let (text, references) = self.decode(start..end);
+        Token { kind: TokenKind::Text(text, references), range: start..end }
+    }
+    fn lex_raw_text(&mut self, RawText { end_tag, escapable }: RawText) -> Option<Token<'a>> {
+        let start = self.position;
+        let end = match end_tag {
+            None => self.source.len(),
+            Some(name) => find_end_tag(self.source, start, &name).unwrap_or_else(|| {
+                let message = format!("Unclosed <{name}> element");
+                self.report(DiagnosticCode::UnexpectedEof, message, start..self.source.len());
+                self.source.len()
+            }),
+        };
+        self.position = end;
+        if start == end {
+            return None
+        }
+        let (text, references) = if escapable {
+            self.decode(start..end)
+        } else {
+            (Cow::Borrowed(&self.source[start..end]), Vec::new())
+        };
+        Some(Token { kind: TokenKind::Text(text, references), range: start..end })
+    }
+    fn lex_placeholder(&mut self, end: usize) -> Token<'a> {
+        let start = self.position;
+        self.position = end;
+        let placeholder = self.placeholder(start..end);
+        Token { kind: TokenKind::Placeholder(placeholder), range: start..end }
+    }
+    /// The placeholder found at `range`.
+    fn placeholder(&self, range: Range<usize>) -> Placeholder {
+        let text = &self.source[range];
+        let syntax = &self.placeholders.as_ref().expect("placeholders are enabled").syntax;
+        let (open, close, _) = syntax.match_at(text).expect("a placeholder");
+        Placeholder::new(open, &text[open.len()..text.len() - close.len()], close)
+    }
+    fn lex_comment(&mut self) -> Token<'a> {
+        let start = self.position;
+        // `<!-->` and `<!--->` are empty comments, closed too early.
+        let rest = &self.source[start + 4..];
+        if let Some(length) = [">", "->"].into_iter().find(|x| rest.starts_with(x)).map(str::len) {
+            let end = start + 4 + length;
+            self.report(DiagnosticCode::UnexpectedToken, "Abruptly closed empty comment", start..end);
+            self.position = end;
+            return Token { kind: TokenKind::Comment(Cow::Borrowed("")), range: start..end }
+        }
+        let (content_end, end) = match self.find_from(start + 4, "-->") {
+            Some(ix) => (ix, ix + 3),
+            None => {
+                self.report(DiagnosticCode::UnexpectedEof, "Unterminated comment", start..self.source.len());
+                (self.source.len(), self.source.len())
+            }
+        };
+        self.position = end;
+        let content = &self.source[start + 4..content_end.max(start + 4)];
+        Token { kind: TokenKind::Comment(Cow::Borrowed(content)), range: start..end }
+    }
+    fn lex_cdata(&mut self) -> Token<'a> {
+        let start = self.position;
+        let content_start = start + "<![CDATA[".len();
+        let (content_end, end) = match self.find_from(content_start, "]]>") {
+            Some(ix) => (ix, ix + 3),
+            None => {
+                self.report(DiagnosticCode::UnexpectedEof, "Unterminated CDATA section", start..self.source.len());
+                (self.source.len(), self.source.len())
+            }
+        };
+        self.position = end;
+        let content = &self.source[content_start..content_end];
+        Token { kind: TokenKind::CData(Cow::Borrowed(content)), range: start..end }
+    }
+    fn lex_bogus_comment(&mut self) -> Token<'a> {
+        let start = self.position;
+        let (content_end, end) = self.until_gt(start + 2);
+        self.position = end;
+        let content = &self.source[start + 2..content_end];
+        Token { kind: TokenKind::Comment(Cow::Borrowed(content)), range: start..end }
+    }
+    /// Returns `(content_end, token_end)` for a construct closed by the next `>`.
+    fn until_gt(&mut self, from: usize) -> (usize, usize) {
+        match self.find_from(from, ">") {
+            Some(ix) => (ix, ix + 1),
+            None => {
+                let start = self.position;
+                self.report(DiagnosticCode::UnexpectedEof, "Unterminated markup declaration", start..self.source.len());
+                (self.source.len(), self.source.len())
+            }
+        }
+    }
+    fn lex_processing_instruction(&mut self) -> Token<'a> {
+        let start = self.position;
+        let (content_end, end) = match self.find_from(start + 2, "?>") {
+            Some(ix) => (ix, ix + 2),
+            None => self.until_gt(start + 2),
+        };
+        self.position = end;
+        let content = &self.source[start + 2..content_end];
+        let (target, data) = match content.find(|c: char| c.is_whitespace()) {
+            Some(ix) => (&content[..ix], content[ix..].trim()),
+            None => (content, ""),
+        };
+        let pi = ProcessingInstruction { target: target.to_string(), data: data.to_string() };
+        Token { kind: TokenKind::ProcessingInstruction(pi), range: start..end }
+    }
+    fn lex_doctype(&mut self) -> Token<'a> {
+        let start = self.position;
+        let (content_end, end) = self.until_gt(start + 9);
+        self.position = end;
+        let mut words = DoctypeWords { text: &self.source[start + 9..content_end] };
+        let name = words.next_word().unwrap_or_default();
+        let mut doctype = Doctype { name, public_id: String::new(), system_id: String::new(), raw: None };
+        match words.next_word().map(|x| x.to_ascii_uppercase()).as_deref() {
+            Some("PUBLIC") => {
+                doctype.public_id = words.next_word().unwrap_or_default();
+                doctype.system_id = words.next_word().unwrap_or_default();
+            }
+            Some("SYSTEM") => {
+                doctype.system_id = words.next_word().unwrap_or_default();
+            }
+            Some(_) => {
+                self.report(DiagnosticCode::BadDoctype, "Bad DOCTYPE", start..end);
+            }
+            None => (),
+        }
+        Token { kind: TokenKind::Doctype(doctype), range: start..end }
+    }
+    fn lex_tag(&mut self, is_end_tag: bool) -> Token<'a> {
+        let start = self.position;
+        let bytes = self.bytes();
+        let name_start = start + if is_end_tag { 2 } else { 1 };
+        let mut ix = name_start;
+        while ix < bytes.len() && !matches!(bytes[ix], b'>' | b'/') && !bytes[ix].is_ascii_whitespace() {
+            ix += 1;
+        }
+        let name = Cow::Borrowed(&self.source[name_start..ix]);
+        let mut attributes = Vec::<AttributeToken>::new();
+        let mut seen = HashSet::new();
+        let mut skipped_attributes = 0;
+        let mut placeholders = Vec::new();
+        let mut self_closing = false;
+        let mut terminated = false;
+        loop {
+            while ix < bytes.len() && (bytes[ix].is_ascii_whitespace() || bytes[ix] == b'/') {
+                self_closing = bytes[ix] == b'/';
+                ix += 1;
+            }
+            if ix >= bytes.len() {
+                break
+            }
+            if bytes[ix] == b'>' {
+                ix += 1;
+                terminated = true;
+                break
+            }
+            self_closing = false;
+            // A placeholder stands for attributes (`{% if x %}checked{% endif %}`)
+            // unless it begins the name of one (`{{ prefix }}-id="…"`).
+            if let Some(end) = self.placeholder_at(ix)
+                && !self.starts_named_attribute(ix) {
+                placeholders.push((attributes.len(), self.placeholder(ix..end), ix..end));
+                ix = end;
+                continue
+            }
+            let (attribute, next) = self.lex_attribute(ix);
+            ix = next;
+            if self.max_attributes.is_some_and(|limit| attributes.len() >= limit) {
+                skipped_attributes += 1;
+                continue
+            }
+            // HTML names compare ASCII case-insensitively.
+            let key = match self.syntax {
+                Syntax::Html if attribute.name.bytes().any(|x| x.is_ascii_uppercase()) => {
+                    Cow::Owned(attribute.name.to_ascii_lowercase())
+                }
+                _ => attribute.name.clone(),
+            };
+            if !seen.insert(key) {
+                let message = format!("Duplicate attribute {:?}", attribute.name);
+                self.report(DiagnosticCode::DuplicateAttribute, message, attribute.range.clone());
+                continue
+            }
+            attributes.push(attribute);
+        }
+        self.position = ix;
+        if !terminated {
+            let message = format!("Unterminated tag <{}{name}", if is_end_tag { "/" } else { "" });
+            self.report(DiagnosticCode::UnexpectedEof, message, start..ix);
+        }
+        let tag = TagToken { name, attributes, placeholders, self_closing, skipped_attributes };
+        if is_end_tag {
+            if !tag.attributes.is_empty() {
+                let message = format!("Attributes on end tag </{}>", tag.name);
+                self.report(DiagnosticCode::EndTagWithAttributes, message, start..ix);
+            }
+            return Token { kind: TokenKind::EndTag(tag), range: start..ix }
+        }
+        if !tag.self_closing {
+            self.raw_text = self.raw_text_for(&tag.name);
+        }
+        Token { kind: TokenKind::StartTag(tag), range: start..ix }
+    }
+    /// Whether an attribute name with a value (`name=`) starts at `start`.
+    fn starts_named_attribute(&mut self, start: usize) -> bool {
+        let name_end = self
+            .find_outside_placeholders(start, |x| matches!(x, b'>' | b'/' | b'=') || x.is_ascii_whitespace())
+            .unwrap_or(self.source.len());
+        let rest = self.source[name_end..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+        rest.starts_with('=')
+    }
+    fn lex_attribute(&mut self, start: usize) -> (AttributeToken<'a>, usize) {
+        let bytes = self.bytes();
+        // The first character may be anything (even `=`), the rest stop at the
+        // usual delimiters. Placeholders are part of the name of an attribute
+        // with a value (`data-{{ key }}="…"`), and end the name of one without
+        // (`checked{% endif %}`).
+        let mut ix = match self.placeholder_at(start) {
+            Some(end) => end,
+            None => start + self.source[start..].chars().next().map(char::len_utf8).unwrap_or(1),
+        };
+        if self.starts_named_attribute(start) {
+            ix = self
+                .find_outside_placeholders(ix, |x| matches!(x, b'>' | b'/' | b'=') || x.is_ascii_whitespace())
+                .unwrap_or(bytes.len());
+        } else {
+            while ix < bytes.len() && !matches!(bytes[ix], b'>' | b'/' | b'=') && !bytes[ix].is_ascii_whitespace()
+                && self.placeholder_at(ix).is_none() {
+                ix += 1;
+            }
+        }
+        let name = Cow::Borrowed(&self.source[start..ix]);
+        let mut lookahead = ix;
+        while lookahead < bytes.len() && bytes[lookahead].is_ascii_whitespace() {
+            lookahead += 1;
+        }
+        if bytes.get(lookahead) != Some(&b'=') {
+            if self.syntax == Syntax::Xml {
+                let message = format!("Attribute {name:?} has no value");
+                self.report(DiagnosticCode::UnexpectedCharacter, message, start..ix);
+            }
+            let attribute = AttributeToken { name, value: Cow::Borrowed(""), references: Vec::new(), range: start..ix };
+            return (attribute, ix)
+        }
+        ix = lookahead + 1;
+        while ix < bytes.len() && bytes[ix].is_ascii_whitespace() {
+            ix += 1;
+        }
+        let (value_range, end) = match bytes.get(ix) {
+            Some(quote @ (b'"' | b'\'')) => {
+                let quote = *quote;
+                match self.find_outside_placeholders(ix + 1, |x| x == quote) {
+                    Some(close) => (ix + 1..close, close + 1),
+                    None => {
+                        self.report(DiagnosticCode::UnexpectedEof, "Unterminated attribute value", start..bytes.len());
+                        (ix + 1..bytes.len(), bytes.len())
+                    }
+                }
+            }
+            _ => {
+                let value_start = ix;
+                ix = self
+                    .find_outside_placeholders(ix, |x| x == b'>' || x.is_ascii_whitespace())
+                    .unwrap_or(bytes.len());
+                if self.syntax == Syntax::Xml {
+                    let message = format!("Unquoted value for attribute {name:?}");
+                    self.report(DiagnosticCode::UnexpectedCharacter, message, start..ix);
+                }
+                (value_start..ix, ix)
+            }
+        };
+        let (value, references) = self.decode(value_range);
+        (AttributeToken { name, value, references, range: start..end }, end)
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Resolves character references in `source[range]` (see
+    /// [`Tokenizer::decode_references`]), keeping placeholders verbatim.
+    fn decode(&mut self, range: Range<usize>) -> (Cow<'a, str>, Vec<CharacterReference>) {
+        let mut output = String::new();
+        let mut references = Vec::new();
+        let mut from = range.start;
+        while let Some(placeholder) = self.next_placeholder(from).filter(|x| x.end <= range.end) {
+            let (text, found) = self.decode_references(from..placeholder.start);
+            references.extend(found.into_iter().map(|x| CharacterReference { offset: x.offset + output.len(), ..x }));
+            output.push_str(&text);
+            output.push_str(&self.source[placeholder.clone()]);
+            from = placeholder.end;
+        }
+        if output.is_empty() {
+            return self.decode_references(from..range.end)
+        }
+        let (text, found) = self.decode_references(from..range.end);
+        references.extend(found.into_iter().map(|x| CharacterReference { offset: x.offset + output.len(), ..x }));
+        output.push_str(&text);
+        (Cow::Owned(output), references)
+    }
+    /// Resolves character references in `source[range]`, recording them if
+    /// asked to. In XML only the predefined entities and numeric references
+    /// exist; anything else is reported and kept literally.
+    fn decode_references(&mut self, range: Range<usize>) -> (Cow<'a, str>, Vec<CharacterReference>) {
+        let raw = &self.source[range.clone()];
+        if !raw.contains('&') {
+            return (Cow::Borrowed(raw), Vec::new())
+        }
+        if self.syntax == Syntax::Html && !self.keep_references {
+            return (html_escape::decode_html_entities(raw), Vec::new())
+        }
+        if self.syntax == Syntax::Html {
+            let (output, references) = decode_html_references(raw);
+            return (Cow::Owned(output), references)
+        }
+        let mut output = String::with_capacity(raw.len());
+        let mut references = Vec::new();
+        let mut rest = raw;
+        while let Some(ix) = rest.find('&') {
+            output.push_str(&rest[..ix]);
+            let reference = &rest[ix..];
+            let resolved = reference
+                .find(';')
+                .filter(|end| *end > 1)
+                .and_then(|end| Some((decode_xml_reference(&reference[1..end])?, end + 1)));
+            match resolved {
+                Some((c, len)) => {
+                    if self.keep_references {
+                        references.push(CharacterReference::new(output.len(), &reference[..len], c));
+                    }
+                    output.push(c);
+                    rest = &reference[len..];
+                }
+                None => {
+                    let offset = range.start + (raw.len() - reference.len());
+                    let len = reference.find(';').map(|x| x + 1).unwrap_or(1);
+                    let message = format!("Invalid character reference {:?}", &reference[..len]);
+                    self.report(DiagnosticCode::InvalidCharacterReference, message, offset..offset + len);
+                    output.push('&');
+                    rest = &reference[1..];
+                }
+            }
+        }
+        output.push_str(rest);
+        (Cow::Owned(output), references)
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL HELPERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Finds template placeholders in the source on demand.
+struct Placeholders {
+    syntax: PlaceholderSyntax,
+    search: PlaceholderSearch,
+    /// The last search: where it started and what it found.
+    last: Option<(usize, Option<Range<usize>>)>,
+}
+
+impl Placeholders {
+    fn new(syntax: PlaceholderSyntax) -> Self {
+        let search = PlaceholderSearch::new(&syntax);
+        Self { syntax, search, last: None }
+    }
+    /// The first complete placeholder starting at or after `from`.
+    fn next(&mut self, source: &str, from: usize) -> Option<Range<usize>> {
+        if let Some((start, found)) = &self.last
+            && *start <= from
+            && found.as_ref().is_none_or(|x| from <= x.start) {
+            return found.clone()
+        }
+        let found = self.search.next(&self.syntax, source, from);
+        self.last = Some((from, found.clone()));
+        found
+    }
+}
+
+/// Splits the inside of a `<!DOCTYPE …>` into whitespace separated, optionally quoted words.
+struct DoctypeWords<'a> {
+    text: &'a str,
+}
+
+impl DoctypeWords<'_> {
+    fn next_word(&mut self) -> Option<String> {
+        let text = self.text.trim_start();
+        let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let (word, rest) = match quote {
+            Some(quote) => {
+                let body = &text[1..];
+                match body.find(quote) {
+                    Some(ix) => (&body[..ix], &body[ix + 1..]),
+                    None => (body, ""),
+                }
+            }
+            None => {
+                let ix = text.find(|c: char| c.is_whitespace()).unwrap_or(text.len());
+                (&text[..ix], &text[ix..])
+            }
+        };
+        self.text = rest;
+        if word.is_empty() && quote.is_none() {
+            return None
+        }
+        Some(word.to_string())
+    }
+}
+
+/// Resolves HTML character references, recording the named ones ending in `;`
+/// and all numeric ones. Anything else (legacy names without `;`, …) is left
+/// to the ordinary decoder and not recorded.
+fn decode_html_references(raw: &str) -> (String, Vec<CharacterReference>) {
+    let mut output = String::with_capacity(raw.len());
+    let mut references = Vec::new();
+    let mut done = 0;
+    let mut from = 0;
+    while let Some(ix) = raw[from..].find('&').map(|x| x + from) {
+        from = ix + 1;
+        let Some(len) = html_reference_len(&raw[ix..]) else {
+            continue
+        };
+        let source = &raw[ix..ix + len];
+        let resolved = html_escape::decode_html_entities(source);
+        if resolved == source {
+            continue
+        }
+        output.push_str(&html_escape::decode_html_entities(&raw[done..ix]));
+        references.push(CharacterReference::new(output.len(), source, resolved.as_ref()));
+        output.push_str(&resolved);
+        done = ix + len;
+        from = done;
+    }
+    output.push_str(&html_escape::decode_html_entities(&raw[done..]));
+    (output, references)
+}
+
+/// The length of the `&name;`, `&#digits;` or `&#xhex;` reference `text`
+/// starts with (the `;` is optional for numeric references).
+fn html_reference_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let (start, is_digit): (usize, fn(&u8) -> bool) = match bytes.get(1..3) {
+        Some([b'#', b'x' | b'X']) => (3, u8::is_ascii_hexdigit),
+        Some([b'#', _]) => (2, u8::is_ascii_digit),
+        _ => (1, u8::is_ascii_alphanumeric),
+    };
+    let end = start + bytes[start.min(bytes.len())..].iter().take_while(|x| is_digit(x)).count();
+    if end == start {
+        return None
+    }
+    match bytes.get(end) {
+        Some(b';') => Some(end + 1),
+        _ if start > 1 => Some(end),
+        _ => None,
+    }
+}
+
+/// Resolves the inside of an XML `&…;` reference.
+fn decode_xml_reference(name: &str) -> Option<char> {
+    match name {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "apos" => Some('\''),
+        "quot" => Some('"'),
+        _ => {
+            let code = match name.strip_prefix('#')? {
+                hex if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok()?,
+                decimal => decimal.parse::<u32>().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Finds the start of `</name` (ASCII case-insensitive) followed by a tag boundary.
+pub(crate) fn find_end_tag(source: &str, from: usize, name: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut ix = from;
+    while let Some(candidate) = source.get(ix..)?.find("</").map(|x| x + ix) {
+        let name_start = candidate + 2;
+        let name_end = name_start + name.len();
+        let matches_name = bytes
+            .get(name_start..name_end)
+            .is_some_and(|x| x.eq_ignore_ascii_case(name.as_bytes()));
+        if matches_name {
+            let boundary = bytes.get(name_end).copied().unwrap_or(b'>');
+            if boundary == b'>' || boundary == b'/' || boundary.is_ascii_whitespace() {
+                return Some(candidate)
+            }
+        }
+        ix = name_start;
+    }
+    None
+}
@@ -0,0 +1,255 @@
+use std::ops::Range;
+use crate::TagBuf;
+use super::ParseLimits;
+
+// ————————————————————————————————————————————————————————————————————————————
+// PARSER OPTIONS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Which parser implementation to run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParserBackend {
+    /// Browser-grade parsing via scraper/html5ever (the HTML5 tree construction
+    /// algorithm, including implied `<html>`/`<head>`/`<body>`/`<tbody>` and
+    /// reparenting of misplaced content).
+    #[default]
+    Html5,
+    /// The home-grown lenient parser: builds the tree exactly as written and
+    /// never inserts or moves elements (see [`crate::parser::native`]).
+    Native,
+    /// Strict, namespace-aware XML (see [`crate::parser::native::parse_xml`]).
+    Xml,
+}
+
+/// Settings shared by the parser entry points.
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    pub backend: ParserBackend,
+    /// The element fragments are parsed as the contents of, like assigning to
+    /// its `innerHTML` (`tbody` for `<tr>` snippets, `select` for `<option>`s,
+    /// `svg` for SVG shapes, …). Defaults to `<body>`.
+    pub fragment_context: Option<TagBuf>,
+    /// The charset declared outside the document (e.g. by a `Content-Type`
+    /// header) for the byte-oriented entry points. Only a byte order mark
+    /// overrides it.
+    pub transport_charset: Option<String>,
+    /// Resource limits; unlimited by default.
+    pub limits: ParseLimits,
+    /// Keep the source text of every text node, tag and doctype (see
+    /// [`crate::RawSource`]) so that [`crate::Node::format_lossless`] can
+    /// reproduce the input exactly wherever the tree is left unchanged. The
+    /// HTML5 backend keeps it only for the nodes it can locate in the source
+    /// (see [`ParserOptions::locate_spans`]) and that html5ever left as
+    /// written; what it inserted or joined, and elements whose end tag it
+    /// implied, are written fresh.
+    pub lossless: bool,
+    /// Record the character references (`&nbsp;`, `&#8203;`, …) resolved in
+    /// text and attribute values (see [`crate::CharacterReference`]) so the
+    /// serializer can write them back as written instead of as literal
+    /// characters. The HTML5 backend records them for the text and attributes
+    /// it can locate in the source (see [`ParserOptions::locate_spans`]), but
+    /// not for text it joined from several places.
+    pub character_references: bool,
+    /// Recognize template placeholders (see [`crate::Placeholder`]) and keep
+    /// them in place, opaque and verbatim. Off by default.
+    pub placeholders: Option<PlaceholderSyntax>,
+    /// Parse as a browser with scripting enabled would (the default), reading
+    /// the contents of `<noscript>` as raw text. Without scripting they are
+    /// parsed as markup (and, in the HTML5 backend, a `<noscript>` in the
+    /// `<head>` may only contain `<link>`, `<meta>` and `<style>`).
+    pub scripting_enabled: bool,
+    /// Give the elements, text and attributes the HTML5 backend parses source
+    /// spans (see [`crate::Node::span`]). html5ever does not report positions,
+    /// so this takes a second, linear pass over the source; off by default, but
+    /// on for [`crate::parser::parse_from_document`] and the other entry points
+    /// without options. The native and XML backends always record spans, and
+    /// the HTML5 backend also locates them when recognizing placeholders.
+    pub locate_spans: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            backend: ParserBackend::default(),
+            fragment_context: None,
+            transport_charset: None,
+            limits: ParseLimits::default(),
+            lossless: false,
+            character_references: false,
+            placeholders: None,
+            scripting_enabled: true,
+            locate_spans: false,
+        }
+    }
+}
+
+impl ParserOptions {
+    pub fn html5() -> Self {
+        Self::default().with_backend(ParserBackend::Html5)
+    }
+    pub fn native() -> Self {
+        Self::default().with_backend(ParserBackend::Native)
+    }
+    pub fn xml() -> Self {
+        Self::default().with_backend(ParserBackend::Xml)
+    }
+    pub fn with_backend(mut self, backend: ParserBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+    pub fn with_fragment_context(mut self, context: impl Into<TagBuf>) -> Self {
+        self.fragment_context = Some(context.into());
+        self
+    }
+    pub fn with_lossless(mut self, lossless: bool) -> Self {
+        self.lossless = lossless;
+        self
+    }
+    pub fn with_character_references(mut self, keep: bool) -> Self {
+        self.character_references = keep;
+        self
+    }
+    pub fn with_placeholders(mut self, syntax: PlaceholderSyntax) -> Self {
+        self.placeholders = Some(syntax);
+        self
+    }
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+    pub fn with_transport_charset(mut self, label: impl Into<String>) -> Self {
+        self.transport_charset = Some(label.into());
+        self
+    }
+    pub fn with_scripting_enabled(mut self, enabled: bool) -> Self {
+        self.scripting_enabled = enabled;
+        self
+    }
+    pub fn with_locate_spans(mut self, locate: bool) -> Self {
+        self.locate_spans = locate;
+        self
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TEMPLATE PLACEHOLDERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The delimiters of template placeholders. The default recognizes the
+/// Jinja/Tera/Liquid style `{{ … }}`, `{% … %}` and `{# … #}`.
+///
+/// Placeholders are recognized in text, between the attributes of a start tag,
+/// and inside attribute names and values; the contents of raw text elements
+/// (`<script>`, `<style>`, …) are left alone. An opening delimiter without a
+/// closing one is ordinary text.
+///
+/// ```
+/// # use super_html_ast::parser::{parse_fragment_with, ParserOptions, PlaceholderSyntax};
+/// # let source = "<p>{{{ body }}} by {{ author }}</p>";
+/// let handlebars = PlaceholderSyntax::empty().with_delimiters("{{{", "}}}").with_delimiters("{{", "}}");
+/// let result = parse_fragment_with(source, &ParserOptions::native().with_placeholders(handlebars));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaceholderSyntax {
+    /// `(open, close)` delimiter pairs, tried in order (so list longer
+    /// opening delimiters first).
+    pub delimiters: Vec<(String, String)>,
+}
+
+impl Default for PlaceholderSyntax {
+    fn default() -> Self {
+        Self::empty()
+            .with_delimiters("{{", "}}")
+            .with_delimiters("{%", "%}")
+            .with_delimiters("{#", "#}")
+    }
+}
+
+impl PlaceholderSyntax {
+    /// No delimiters at all; add some with [`PlaceholderSyntax::with_delimiters`].
+    pub fn empty() -> Self {
+        Self { delimiters: Vec::new() }
+    }
+    pub fn with_delimiters(mut self, open: impl Into<String>, close: impl Into<String>) -> Self {
+        self.delimiters.push((open.into(), close.into()));
+        self
+    }
+    /// The placeholder `text` starts with, as `(open, close, length)`.
+    pub(crate) fn match_at<'a>(&'a self, text: &str) -> Option<(&'a str, &'a str, usize)> {
+        self.delimiters
+            .iter()
+            .filter(|(open, close)| !open.is_empty() && !close.is_empty())
+            .find_map(|(open, close)| {
+                let rest = text.strip_prefix(open.as_str())?;
+                let end = rest.find(close.as_str())?;
+                Some((open.as_str(), close.as_str(), open.len() + end + close.len()))
+            })
+    }
+    /// The byte ranges of the complete placeholders in `text`, left to right.
+    pub(crate) fn find_iter<'s>(&'s self, text: &'s str) -> impl Iterator<Item = Range<usize>> + 's {
+        let mut search = PlaceholderSearch::new(self);
+        let mut from = 0;
+        std::iter::from_fn(move || {
+            let found = search.next(self, text, from)?;
+            from = found.end;
+            Some(found)
+        })
+    }
+}
+
+/// Searches one text for placeholders, remembering what earlier searches
+/// learned so that a run of unterminated placeholders is read over once.
+#[derive(Debug, Clone)]
+pub(crate) struct PlaceholderSearch {
+    /// For each delimiter pair, the offset its opening delimiter was last
+    /// searched from and the first offset it was found at.
+    opens: Vec<Option<(usize, Option<usize>)>>,
+    /// For each delimiter pair, the first offset from which its closing
+    /// delimiter no longer occurs.
+    unclosed_from: Vec<Option<usize>>,
+}
+
+impl PlaceholderSearch {
+    pub(crate) fn new(syntax: &PlaceholderSyntax) -> Self {
+        let count = syntax.delimiters.len();
+        Self { opens: vec![None; count], unclosed_from: vec![None; count] }
+    }
+    /// The first complete placeholder starting at or after `from`. `syntax`
+    /// and `text` must be the same for every search.
+    pub(crate) fn next(&mut self, syntax: &PlaceholderSyntax, text: &str, from: usize) -> Option<Range<usize>> {
+        let mut ix = from;
+        loop {
+            let mut start = None::<usize>;
+            for (index, (open, close)) in syntax.delimiters.iter().enumerate() {
+                if open.is_empty() || close.is_empty() {
+                    continue
+                }
+                let found = match self.opens[index] {
+                    Some((searched, found)) if searched <= ix && found.is_none_or(|x| ix <= x) => found,
+                    _ => {
+                        let found = text.get(ix..).and_then(|rest| rest.find(open.as_str())).map(|x| x + ix);
+                        self.opens[index] = Some((ix, found));
+                        found
+                    }
+                };
+                if let Some(found) = found.filter(|x| self.unclosed_from[index].is_none_or(|unclosed| *x < unclosed)) {
+                    start = Some(start.map_or(found, |start| start.min(found)));
+                }
+            }
+            let start = start?;
+            for (index, (open, close)) in syntax.delimiters.iter().enumerate() {
+                if open.is_empty() || close.is_empty() || !text[start..].starts_with(open.as_str()) {
+                    continue
+                }
+                if self.unclosed_from[index].is_some_and(|x| start >= x) {
+                    continue
+                }
+                match text[start + open.len()..].find(close.as_str()) {
+                    Some(end) => return Some(start..start + open.len() + end + close.len()),
+                    None => self.unclosed_from[index] = Some(start),
+                }
+            }
+            ix = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Template placeholder support for the HTML5 backend.
+//!
+//! html5ever knows nothing of placeholders: it would foster-parent them out of
+//! tables and split those in tags into bogus attributes. So they are swapped
+//! for markers before parsing — placeholders in content become marker comments,
+//! which the tree builder leaves where they are, and those in tags become
+//! marker names — and swapped back afterwards, with every span mapped back
+//! onto the original source.
+use std::ops::Range;
+use crate::{Atom, AttributeKeyBuf, AttributeMap, LineIndex, Node, Placeholder, Span};
+use super::native::tokenizer::{TokenKind, Tokenizer};
+use super::{ParseResult, PlaceholderSyntax};
+
+/// Delimits the index of a placeholder in a marker. Noncharacters, which no
+/// real document contains.
+const MARKER_START: char = '\u{FDD0}';
+const MARKER_END: char = '\u{FDD1}';
+
+/// Parses `source` with `parse` (an HTML5 backend entry point), keeping the
+/// placeholders `syntax` recognizes in place. `scripting_enabled` tells whether
+/// `<noscript>` contents are raw text.
+pub(super) fn parse_html5(
+    source: &str,
+    syntax: &PlaceholderSyntax,
+    scripting_enabled: bool,
+    parse: impl FnOnce(&str) -> ParseResult<Node>,
+) -> ParseResult<Node> {
+    let substitution = Substitution::new(source, syntax, scripting_enabled);
+    if substitution.placeholders.is_empty() {
+        return parse(source)
+    }
+    let mut result = parse(&substitution.source);
+    let index = LineIndex::new(source);
+    substitution.restore(&mut result.output, &index);
+    for diagnostic in result.diagnostics.iter_mut() {
+        diagnostic.span = diagnostic.span.map(|span| substitution.map_span(span, &index));
+    }
+    result
+}
+
+/// The source with its placeholders swapped for markers.
+struct Substitution {
+    source: String,
+    placeholders: Vec<Placeholder>,
+    /// The `(substituted, original)` ranges of each marker, in order.
+    shifts: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl Substitution {
+    fn new(source: &str, syntax: &PlaceholderSyntax, scripting_enabled: bool) -> Self {
+        let mut substitution = Self { source: String::with_capacity(source.len()), placeholders: Vec::new(), shifts: Vec::new() };
+        let index = LineIndex::new(source);
+        let mut copied = 0;
+        let tokenizer = Tokenizer::new(source)
+            .with_placeholders(Some(syntax.clone()))
+            .with_scripting(scripting_enabled);
+        for token in tokenizer {
+            match token.kind {
+                TokenKind::Placeholder(placeholder) => {
+                    substitution.source.push_str(&source[copied..token.range.start]);
+                    let placeholder = placeholder.with_span(index.span(token.range.clone()));
+                    substitution.push_marker(placeholder, token.range.clone(), "<!--", "-->");
+                    copied = token.range.end;
+                }
+                TokenKind::StartTag(tag) => {
+                    let offset = token.range.start;
+                    for found in syntax.find_iter(&source[token.range.clone()]) {
+                        let range = offset + found.start..offset + found.end;
+                        let Some((open, close, _)) = syntax.match_at(&source[range.clone()]) else {
+                            break
+                        };
+                        let expression = &source[range.start + open.len()..range.end - close.len()];
+                        let placeholder = Placeholder::new(open, expression, close).with_span(index.span(range.clone()));
+                        substitution.source.push_str(&source[copied..range.start]);
+                        // Placeholders between attributes become attribute names of their own.
+                        let padding = if tag.placeholders.iter().any(|(_, _, x)| *x == range) { " " } else { "" };
+                        substitution.push_marker(placeholder, range.clone(), padding, padding);
+                        copied = range.end;
+                    }
+                }
+                _ => (),
+            }
+        }
+        substitution.source.push_str(&source[copied..]);
+        substitution
+    }
+    fn push_marker(&mut self, placeholder: Placeholder, original: Range<usize>, before: &str, after: &str) {
+        let start = self.source.len();
+        self.source.push_str(before);
+        self.source.push_str(&marker(self.placeholders.len()));
+        self.source.push_str(after);
+        self.shifts.push((start..self.source.len(), original));
+        self.placeholders.push(placeholder);
+    }
+    /// The offset in the original source of `offset` in the substituted one.
+    fn map_offset(&self, offset: usize) -> usize {
+        let ix = self.shifts.partition_point(|(substituted, _)| substituted.start <= offset);
+        let Some((substituted, original)) = ix.checked_sub(1).map(|ix| &self.shifts[ix]) else {
+            return offset
+        };
+        match offset {
+            _ if offset >= substituted.end => offset - substituted.end + original.end,
+            _ if offset == substituted.start => original.start,
+            _ => original.end,
+        }
+    }
+    fn map_span(&self, span: Span, index: &LineIndex) -> Span {
+        index.span(self.map_offset(span.start.offset)..self.map_offset(span.end.offset))
+    }
+    /// The placeholder a marker stands for, if `text` is exactly one.
+    fn placeholder(&self, text: &str) -> Option<&Placeholder> {
+        let index = text.strip_prefix(MARKER_START)?.strip_suffix(MARKER_END)?;
+        self.placeholders.get(index.parse::<usize>().ok()?)
+    }
+    /// `text` with its markers swapped back.
+    fn unmark(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(MARKER_START) {
+            output.push_str(&rest[..start]);
+            let marked = rest[start..].find(MARKER_END).and_then(|end| {
+                let placeholder = self.placeholder(&rest[start..start + end + MARKER_END.len_utf8()])?;
+                Some((placeholder, start + end + MARKER_END.len_utf8()))
+            });
+            match marked {
+                Some((placeholder, end)) => {
+                    output.push_str(&placeholder.to_string());
+                    rest = &rest[end..];
+                }
+                None => {
+                    output.push(MARKER_START);
+                    rest = &rest[start + MARKER_START.len_utf8()..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+    /// Swaps the markers in `root` back for placeholders and maps its spans
+    /// onto the original source.
+    fn restore(&self, root: &mut Node, index: &LineIndex) {
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if let Node::Comment(comment) = node
+                && let Some(placeholder) = self.placeholder(comment) {
+                *node = Node::Placeholder(placeholder.clone());
+                continue
+            }
+            match node {
+                Node::Text(text) => {
+                    text.span = text.span.map(|span| self.map_span(span, index));
+                }
+                Node::Element(element) => {
+                    element.span = element.span.map(|span| self.map_span(span, index));
+                    // Source text with markers in it is not the source's.
+                    element.raw = element.raw.take().filter(|raw| !raw.start.as_str().contains(MARKER_START));
+                    // scraper sorts attributes by name; put them back in source order.
+                    let mut located = std::mem::take(&mut element.attributes).into_iter().collect::<Vec<_>>();
+                    located.sort_by_key(|(key, _)| key.span().map_or(usize::MAX, |span| span.start.offset));
+                    let mut attributes = Vec::with_capacity(located.len());
+                    for (key, mut value) in located {
+                        if let Some(placeholder) = self.placeholder(key.as_str()) {
+                            element.placeholders.push((attributes.len(), placeholder.clone()));
+                            continue
+                        }
+                        let restored = AttributeKeyBuf::new(self.unmark(key.as_str()))
+                            .with_namespace(key.namespace().map(Atom::new))
+                            .with_span(key.span().map(|span| self.map_span(span, index)));
+                        *value.as_mut_string() = self.unmark(value.as_str());
+                        attributes.push((restored, value));
+                    }
+                    element.attributes = attributes.into_iter().collect::<AttributeMap>();
+                    stack.extend(element.children.iter_mut());
+                }
+                Node::Fragment(fragment) => stack.extend(fragment.iter_mut()),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// The marker of the placeholder at `index`.
+fn marker(index: usize) -> String {
+    format!("{MARKER_START}{index}{MARKER_END}")
+}
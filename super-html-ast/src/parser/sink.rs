@@ -0,0 +1,120 @@
+//! A thin `TreeSink` wrapper around scraper's sink that records the source
+//! line of every parse error (scraper itself only keeps the message).
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use html5ever::tendril::StrTendril;
+use html5ever::tree_builder::{ElementFlags, NextParserState, NodeOrText, QuirksMode, TreeSink};
+use html5ever::{Attribute, QualName};
+use scraper::{Html, HtmlTreeSink};
+
+/// A parse error as reported by html5ever, with the 1-based line it was raised on.
+#[derive(Debug, Clone)]
+pub(super) struct RawParseError {
+    pub message: Cow<'static, str>,
+    pub line: u64,
+}
+
+pub(super) struct LocatingSink {
+    inner: HtmlTreeSink,
+    line: Cell<u64>,
+    errors: RefCell<Vec<RawParseError>>,
+}
+
+impl LocatingSink {
+    pub fn new(html: Html) -> Self {
+        Self { inner: HtmlTreeSink::new(html), line: Cell::new(1), errors: RefCell::default() }
+    }
+}
+
+impl TreeSink for LocatingSink {
+    type Output = (Html, Vec<RawParseError>);
+    type Handle = <HtmlTreeSink as TreeSink>::Handle;
+    type ElemName<'a> = <HtmlTreeSink as TreeSink>::ElemName<'a>;
+
+    fn finish(self) -> Self::Output {
+        (self.inner.finish(), self.errors.into_inner())
+    }
+    fn parse_error(&self, message: Cow<'static, str>) {
+        let line = self.line.get();
+        self.errors.borrow_mut().push(RawParseError { message, line });
+    }
+    fn set_current_line(&self, line_number: u64) {
+        self.line.set(line_number);
+    }
+    fn get_document(&self) -> Self::Handle {
+        self.inner.get_document()
+    }
+    fn elem_name<'a>(&'a self, target: &'a Self::Handle) -> Self::ElemName<'a> {
+        self.inner.elem_name(target)
+    }
+    fn create_element(&self, name: QualName, attrs: Vec<Attribute>, flags: ElementFlags) -> Self::Handle {
+        self.inner.create_element(name, attrs, flags)
+    }
+    fn create_comment(&self, text: StrTendril) -> Self::Handle {
+        self.inner.create_comment(text)
+    }
+    fn create_pi(&self, target: StrTendril, data: StrTendril) -> Self::Handle {
+        self.inner.create_pi(target, data)
+    }
+    fn append(&self, parent: &Self::Handle, child: NodeOrText<Self::Handle>) {
+        self.inner.append(parent, child)
+    }
+    fn append_based_on_parent_node(
+        &self,
+        element: &Self::Handle,
+        prev_element: &Self::Handle,
+        child: NodeOrText<Self::Handle>,
+    ) {
+        self.inner.append_based_on_parent_node(element, prev_element, child)
+    }
+    fn append_doctype_to_document(&self, name: StrTendril, public_id: StrTendril, system_id: StrTendril) {
+        self.inner.append_doctype_to_document(name, public_id, system_id)
+    }
+    fn mark_script_already_started(&self, node: &Self::Handle) {
+        self.inner.mark_script_already_started(node)
+    }
+    fn pop(&self, node: &Self::Handle) {
+        self.inner.pop(node)
+    }
+    fn get_template_contents(&self, target: &Self::Handle) -> Self::Handle {
+        self.inner.get_template_contents(target)
+    }
+    fn same_node(&self, x: &Self::Handle, y: &Self::Handle) -> bool {
+        self.inner.same_node(x, y)
+    }
+    fn set_quirks_mode(&self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+    fn append_before_sibling(&self, sibling: &Self::Handle, new_node: NodeOrText<Self::Handle>) {
+        self.inner.append_before_sibling(sibling, new_node)
+    }
+    fn add_attrs_if_missing(&self, target: &Self::Handle, attrs: Vec<Attribute>) {
+        self.inner.add_attrs_if_missing(target, attrs)
+    }
+    fn associate_with_form(
+        &self,
+        target: &Self::Handle,
+        form: &Self::Handle,
+        nodes: (&Self::Handle, Option<&Self::Handle>),
+    ) {
+        self.inner.associate_with_form(target, form, nodes)
+    }
+    fn remove_from_parent(&self, target: &Self::Handle) {
+        self.inner.remove_from_parent(target)
+    }
+    fn reparent_children(&self, node: &Self::Handle, new_parent: &Self::Handle) {
+        self.inner.reparent_children(node, new_parent)
+    }
+    fn is_mathml_annotation_xml_integration_point(&self, handle: &Self::Handle) -> bool {
+        self.inner.is_mathml_annotation_xml_integration_point(handle)
+    }
+    fn complete_script(&self, node: &Self::Handle) -> NextParserState {
+        self.inner.complete_script(node)
+    }
+    /// A `<template shadowrootmode>` would otherwise be handed to
+    /// `attach_declarative_shadow`, which scraper does not implement, and then
+    /// dropped. Declined, it stays an ordinary template whose contents we keep.
+    fn allow_declarative_shadow_roots(&self, _intended_parent: &Self::Handle) -> bool {
+        false
+    }
+}
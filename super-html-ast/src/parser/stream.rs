@@ -0,0 +1,521 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::ops::Range;
+use encoding_rs::{Decoder, UTF_8};
+use crate::constants::is_void_tag;
+use crate::{Atom, AttributeKeyBuf, AttributeMap, AttributeValueBuf, EndTag, Event, LineIndex, SourcePosition, Span, StartTag, TagBuf, Text};
+use super::encoding::{decode_chunk, detect_stream, malformed_diagnostic, PRESCAN_LENGTH};
+use super::native::{attribute_namespace, element_namespace, has_optional_end_tag, implicitly_closes, lookup_prefix, namespace_declarations};
+use super::native::tokenizer::{Syntax, TagToken, Token, TokenKind, Tokenizer};
+use super::{DetectedEncoding, DiagnosticCode, ParseDiagnostic, ParserBackend, ParserOptions};
+
+/// How many bytes are read at a time.
+const CHUNK_LENGTH: usize = 64 * 1024;
+
+// ————————————————————————————————————————————————————————————————————————————
+// EVENT READER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Reads text and parses it as balanced [`Event`]s, for processing documents
+/// too large to hold as a tree (see [`EventParser`], which it feeds).
+///
+/// ```no_run
+/// # use std::fs::File;
+/// # use std::io::{BufReader, BufWriter};
+/// # use super_html_ast::{parser, Event};
+/// # use super_html_ast::format::EventWriter;
+/// # use super_html_ast::parser::ParserOptions;
+/// # fn main() -> std::io::Result<()> {
+/// let file = BufReader::new(File::open("export.html")?);
+/// let mut writer = EventWriter::new(BufWriter::new(File::create("filtered.html")?));
+/// for event in parser::read_events(file, &ParserOptions::native()) {
+///     match event? {
+///         Event::Comment(_) => (),
+///         event => writer.write(&event)?,
+///     }
+/// }
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct EventReader<R> {
+    input: R,
+    parser: EventParser,
+    chunk: Vec<u8>,
+}
+
+/// Parses `source` as events; see [`EventReader`]. Reading never fails.
+pub fn parse_events<'a>(source: &'a str, options: &ParserOptions) -> EventReader<&'a [u8]> {
+    EventReader { input: source.as_bytes(), parser: EventParser::for_text(options), chunk: Vec::new() }
+}
+
+/// Decodes and parses the bytes of `input` as events; see [`EventReader`].
+/// The encoding is sniffed as by [`decode_html`](super::decode_html) (or
+/// [`decode_xml`](super::decode_xml)), from the first kilobyte.
+pub fn read_events<R: Read>(input: R, options: &ParserOptions) -> EventReader<R> {
+    EventReader { input, parser: EventParser::new(options), chunk: Vec::new() }
+}
+
+impl<R: Read> EventReader<R> {
+    /// The next event, or `None` at the end of input.
+    pub fn next_event(&mut self) -> std::io::Result<Option<Event>> {
+        loop {
+            if let Some(event) = self.parser.next_event() {
+                return Ok(Some(event))
+            }
+            if self.parser.is_finished() {
+                return Ok(None)
+            }
+            self.chunk.resize(CHUNK_LENGTH, 0);
+            let length = loop {
+                match self.input.read(&mut self.chunk) {
+                    Ok(length) => break length,
+                    Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Err(error),
+                }
+            };
+            match length {
+                0 => self.parser.end(),
+                _ => self.parser.feed(&self.chunk[..length]),
+            }
+        }
+    }
+    /// The encoding byte input was decoded with, once known; `None` when
+    /// parsing text.
+    pub fn encoding(&self) -> Option<DetectedEncoding> {
+        self.parser.encoding()
+    }
+    /// Diagnostics of the input parsed so far.
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        self.parser.diagnostics()
+    }
+    pub fn take_diagnostics(&mut self) -> Vec<ParseDiagnostic> {
+        self.parser.take_diagnostics()
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = std::io::Result<Event>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// EVENT PARSER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Parses input pushed to it in chunks as balanced [`Event`]s, for input that
+/// arrives piece by piece (network responses, …) rather than from a reader.
+///
+/// The events follow the native parser (see [`crate::parser::native`]): the
+/// tags are as written, plus the end tags HTML allows to be omitted and those
+/// of elements left open by a mismatched end tag or the end of input. The HTML5
+/// backend is read the same way; [`ParserBackend::Xml`] reads XML. The fragment
+/// context, placeholders, character references and transport charset of the
+/// [`ParserOptions`] are honored; limits and lossless mode are not. The
+/// encoding is sniffed from the first kilobyte.
+///
+/// Only the open elements and the markup not yet parsed are held in memory —
+/// at least one whole token, so a single huge text node is held whole. Events
+/// are available as soon as no more input can change them. Diagnostics
+/// accumulate until taken with [`EventParser::take_diagnostics`].
+///
+/// ```
+/// # use super_html_ast::format::EventWriter;
+/// # use super_html_ast::parser::{EventParser, ParserOptions};
+/// # fn main() -> std::io::Result<()> {
+/// # let response = [b"<p>Hel".as_slice(), b"lo</p>"];
+/// # let mut writer = EventWriter::new(Vec::new());
+/// let mut parser = EventParser::new(&ParserOptions::native());
+/// for chunk in response {
+///     parser.feed(chunk);
+///     while let Some(event) = parser.next_event() {
+///         writer.write(&event)?;
+///     }
+/// }
+/// parser.end();
+/// while let Some(event) = parser.next_event() {
+///     writer.write(&event)?;
+/// }
+/// # assert_eq!(writer.finish()?, b"<p>Hello</p>");
+/// # Ok(())
+/// # }
+/// ```
+pub struct EventParser {
+    /// Set once the encoding is known.
+    decoder: Option<Decoder>,
+    encoding: Option<DetectedEncoding>,
+    transport_charset: Option<String>,
+    /// Input fed before the encoding was known.
+    head: Vec<u8>,
+    /// How many bytes were decoded.
+    bytes_decoded: usize,
+    eof: bool,
+    /// Decoded text not yet tokenized.
+    text: String,
+    /// How long `text` must grow before it is tokenized again, after an
+    /// attempt that found no complete token (so that a token spanning many
+    /// chunks is not tokenized over and over).
+    retry_length: usize,
+    /// The position of the start of `text` in the whole input.
+    base: SourcePosition,
+    /// The start tag the tokenized input ended with, whose contents come next.
+    after_start_tag: Option<String>,
+    tokenizer_options: ParserOptions,
+    builder: EventBuilder,
+    queue: VecDeque<Event>,
+    finished: bool,
+}
+
+impl EventParser {
+    pub fn new(options: &ParserOptions) -> Self {
+        let syntax = match options.backend {
+            ParserBackend::Html5 | ParserBackend::Native => Syntax::Html,
+            ParserBackend::Xml => Syntax::Xml,
+        };
+        let context = options.fragment_context.clone().filter(|_| syntax == Syntax::Html);
+        Self {
+            decoder: None,
+            encoding: None,
+            transport_charset: options.transport_charset.clone(),
+            head: Vec::new(),
+            bytes_decoded: 0,
+            eof: false,
+            text: String::new(),
+            retry_length: 0,
+            base: SourcePosition { offset: 0, line: 1, column: 1 },
+            after_start_tag: None,
+            tokenizer_options: options.clone(),
+            builder: EventBuilder::new(syntax, context),
+            queue: VecDeque::new(),
+            finished: false,
+        }
+    }
+    /// A parser for UTF-8 text, which is not sniffed.
+    pub fn for_text(options: &ParserOptions) -> Self {
+        Self { decoder: Some(UTF_8.new_decoder_without_bom_handling()), ..Self::new(options) }
+    }
+    /// Parses the next chunk of input.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        if self.eof {
+            return
+        }
+        self.decode(bytes);
+        if self.decoder.is_some() && self.text.len() >= self.retry_length {
+            self.tokenize();
+        }
+    }
+    /// Parses the rest of the input, closing the elements still open.
+    pub fn end(&mut self) {
+        if self.eof {
+            return
+        }
+        self.eof = true;
+        self.decode(&[]);
+        self.tokenize();
+        self.builder.finish(self.base, &mut self.queue);
+        self.finished = true;
+    }
+    /// The next event parsed, if any.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.queue.pop_front()
+    }
+    /// Whether the input ended and all its events were taken.
+    pub fn is_finished(&self) -> bool {
+        self.finished && self.queue.is_empty()
+    }
+    /// The encoding byte input was decoded with, once known; `None` when
+    /// parsing text.
+    pub fn encoding(&self) -> Option<DetectedEncoding> {
+        self.encoding
+    }
+    /// Diagnostics of the input parsed so far.
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        &self.builder.diagnostics
+    }
+    pub fn take_diagnostics(&mut self) -> Vec<ParseDiagnostic> {
+        std::mem::take(&mut self.builder.diagnostics)
+    }
+    /// Decodes `bytes` onto the text not yet tokenized, once enough input is
+    /// known to determine its encoding.
+    fn decode(&mut self, bytes: &[u8]) {
+        let mut chunk = bytes;
+        let head;
+        if self.decoder.is_none() {
+            self.head.extend_from_slice(bytes);
+            if self.head.len() < PRESCAN_LENGTH && !self.eof {
+                return
+            }
+            let xml = self.builder.syntax == Syntax::Xml;
+            let transport_charset = self.transport_charset.as_deref();
+            let (encoding, decoder, bom_length) = detect_stream(&self.head, transport_charset, xml, &mut self.builder.diagnostics);
+            self.encoding = Some(encoding);
+            self.decoder = Some(decoder);
+            self.bytes_decoded = bom_length;
+            head = std::mem::take(&mut self.head);
+            chunk = &head[bom_length..];
+        }
+        let decoder = self.decoder.as_mut().expect("the encoding is known");
+        let mut malformed = Vec::new();
+        decode_chunk(decoder, chunk, self.eof, &mut self.text, &mut malformed);
+        if let Some(encoding) = self.encoding
+            && !malformed.is_empty() {
+            let index = LineIndex::new(&self.text);
+            for (position, range) in malformed {
+                let range = range.start + self.bytes_decoded..range.end + self.bytes_decoded;
+                let span = locate(&index, self.base, position..position + '\u{FFFD}'.len_utf8());
+                self.builder.diagnostics.push(malformed_diagnostic(encoding, range).with_span(span));
+            }
+        }
+        self.bytes_decoded += chunk.len();
+    }
+    /// Parses the complete tokens of the decoded text, keeping back the last
+    /// ones, which more input could still change (a text node or an
+    /// unterminated tag running up to the end, raw text whose end tag is cut
+    /// short, …).
+    fn tokenize(&mut self) {
+        let syntax = self.builder.syntax;
+        let mut tokenizer = match syntax {
+            Syntax::Html => Tokenizer::new(&self.text),
+            Syntax::Xml => Tokenizer::xml(&self.text),
+        };
+        tokenizer = tokenizer
+            .with_character_references(self.tokenizer_options.character_references)
+            .with_placeholders(self.tokenizer_options.placeholders.clone())
+            .with_scripting(self.tokenizer_options.scripting_enabled);
+        match (self.after_start_tag.as_deref(), self.base.offset, self.builder.context.as_ref()) {
+            (Some(name), _, _) => tokenizer = tokenizer.after_start_tag(name),
+            (None, 0, Some(context)) => tokenizer = tokenizer.in_raw_text_context(context.as_normalized()),
+            _ => (),
+        }
+        let mut tokens = tokenizer.by_ref().collect::<Vec<_>>();
+        if !self.eof {
+            tokens.truncate(tokens.len().saturating_sub(2));
+        }
+        let consumed = match (self.eof, tokens.last()) {
+            (true, _) => self.text.len(),
+            (false, Some(token)) => token.range.end,
+            (false, None) => {
+                self.retry_length = self.text.len() * 2;
+                return
+            }
+        };
+        self.retry_length = 0;
+        let index = LineIndex::new(&self.text);
+        for (diagnostic, range) in tokenizer.take_diagnostics() {
+            if range.end <= consumed {
+                let span = locate(&index, self.base, range);
+                self.builder.diagnostics.push(diagnostic.with_span(span));
+            }
+        }
+        for token in tokens {
+            self.after_start_tag = match &token.kind {
+                TokenKind::StartTag(tag) if !tag.self_closing => Some(tag.name.to_string()),
+                _ => None,
+            };
+            self.builder.push(token, &|range| locate(&index, self.base, range), &mut self.queue);
+        }
+        self.base = locate(&index, self.base, consumed..consumed).start;
+        self.text.drain(..consumed);
+    }
+}
+
+/// The span of `range` in a piece of input starting at `base`, indexed by `index`.
+fn locate(index: &LineIndex, base: SourcePosition, range: Range<usize>) -> Span {
+    let position = |offset: usize| {
+        let local = index.position(offset);
+        let column = match local.line {
+            1 => base.column + local.column - 1,
+            _ => local.column,
+        };
+        SourcePosition { offset: base.offset + local.offset, line: base.line + local.line - 1, column }
+    };
+    Span::new(position(range.start), position(range.end))
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// EVENT BUILDER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Balances tokens into events with a stack of open elements, making the same
+/// repairs as the native tree builder.
+struct EventBuilder {
+    syntax: Syntax,
+    /// The element the parsed nodes will be inserted into, if known.
+    context: Option<TagBuf>,
+    stack: Vec<OpenTag>,
+    /// Whether an XML root element was seen.
+    has_root: bool,
+    /// End of the most recently consumed token.
+    last_end: SourcePosition,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+struct OpenTag {
+    tag: TagBuf,
+    start: SourcePosition,
+    /// `(prefix, uri)` pairs declared on this element; the default namespace has an empty prefix.
+    namespaces: Vec<(String, String)>,
+}
+
+impl EventBuilder {
+    fn new(syntax: Syntax, context: Option<TagBuf>) -> Self {
+        let context = context.map(|context| match context.namespace() {
+            Some(_) => context,
+            None => {
+                let namespace = Atom::new(element_namespace(&context, None));
+                context.with_namespace(namespace)
+            }
+        });
+        Self {
+            syntax,
+            context,
+            stack: Vec::new(),
+            has_root: false,
+            last_end: SourcePosition { offset: 0, line: 1, column: 1 },
+            diagnostics: Vec::new(),
+        }
+    }
+    /// Adds the events of `token`; `locate` gives the spans of byte ranges.
+    fn push(&mut self, token: Token<'_>, locate: &dyn Fn(Range<usize>) -> Span, events: &mut VecDeque<Event>) {
+        let span = locate(token.range);
+        match token.kind {
+            TokenKind::StartTag(tag) => self.start_tag(tag, span, locate, events),
+            TokenKind::EndTag(tag) => self.end_tag(tag, span, events),
+            TokenKind::Text(text, references) => {
+                let text = Text::new(text).with_references(references);
+                self.text(text.with_span(span), events)
+            }
+            TokenKind::CData(text) if self.syntax == Syntax::Xml => self.text(Text::cdata(text).with_span(span), events),
+            TokenKind::CData(text) => self.text(Text::new(text).with_span(span), events),
+            TokenKind::Comment(comment) => events.push_back(Event::Comment(comment.into_owned())),
+            TokenKind::Doctype(doctype) => events.push_back(Event::Doctype(doctype)),
+            TokenKind::ProcessingInstruction(pi) => events.push_back(Event::ProcessingInstruction(pi)),
+            TokenKind::Placeholder(placeholder) => events.push_back(Event::Placeholder(placeholder.with_span(span))),
+        }
+        self.last_end = span.end;
+    }
+    /// Closes everything still open at `end`, the end of input.
+    fn finish(&mut self, end: SourcePosition, events: &mut VecDeque<Event>) {
+        while let Some(open) = self.stack.pop() {
+            if self.syntax == Syntax::Xml || !has_optional_end_tag(&open.tag) {
+                let message = format!("Unclosed <{}> element at end of input", open.tag);
+                self.report(DiagnosticCode::UnclosedElement, message, Span::new(open.start, end));
+            }
+            events.push_back(Event::EndTag(EndTag::new(open.tag).with_span(Span::new(end, end))));
+        }
+        if self.syntax == Syntax::Xml && !self.has_root {
+            let message = "Document has no root element".to_string();
+            self.report(DiagnosticCode::UnexpectedEof, message, Span::new(end, end));
+        }
+    }
+    fn report(&mut self, code: DiagnosticCode, message: String, span: Span) {
+        self.diagnostics.push(ParseDiagnostic::new(code, message).with_span(span));
+    }
+    fn text(&mut self, text: Text, events: &mut VecDeque<Event>) {
+        let is_blank = text.trim().is_empty();
+        if self.syntax == Syntax::Xml && self.stack.is_empty() && !is_blank {
+            let span = text.span.unwrap_or_default();
+            self.report(DiagnosticCode::UnexpectedToken, "Text outside the root element".to_string(), span);
+        }
+        events.push_back(Event::Text(text));
+    }
+    fn start_tag(&mut self, tag: TagToken<'_>, span: Span, locate: &dyn Fn(Range<usize>) -> Span, events: &mut VecDeque<Event>) {
+        let namespaces = match self.syntax {
+            Syntax::Html => Vec::new(),
+            Syntax::Xml => namespace_declarations(&tag),
+        };
+        let mut tag_buf = TagBuf::new(tag.name);
+        match self.syntax {
+            Syntax::Html => {
+                while let Some(open) = self.stack.last() {
+                    if !implicitly_closes(&tag_buf, &open.tag) {
+                        break
+                    }
+                    let open = self.stack.pop().unwrap();
+                    let end = Span::new(self.last_end, self.last_end);
+                    events.push_back(Event::EndTag(EndTag::new(open.tag).with_span(end)));
+                }
+            }
+            Syntax::Xml => {
+                if self.stack.is_empty() && self.has_root {
+                    let message = format!("Element <{tag_buf}> after the root element");
+                    self.report(DiagnosticCode::UnexpectedToken, message, span);
+                }
+                self.has_root = true;
+            }
+        }
+        let mut attributes = AttributeMap::default();
+        for attribute in tag.attributes {
+            let attribute_span = locate(attribute.range);
+            let mut key = AttributeKeyBuf::new(attribute.name).with_span(attribute_span);
+            let namespace = attribute_namespace(self.syntax, key.as_str(), |prefix| {
+                self.resolve_prefix(prefix, &namespaces, attribute_span)
+            });
+            key = key.with_namespace(namespace.map(Atom::from));
+            attributes.insert(key, AttributeValueBuf::referenced(attribute.value, attribute.references));
+        }
+        let namespace = match self.syntax {
+            Syntax::Html => {
+                let parent = self.stack.last().map(|x| &x.tag).or(self.context.as_ref());
+                Some(Atom::new(element_namespace(&tag_buf, parent)))
+            }
+            Syntax::Xml => {
+                let prefix = tag_buf.prefix().unwrap_or_default().to_string();
+                self.resolve_prefix(&prefix, &namespaces, span).map(Atom::from)
+            }
+        };
+        tag_buf = tag_buf.with_namespace(namespace);
+        let placeholders = tag
+            .placeholders
+            .into_iter()
+            .map(|(position, placeholder, range)| (position, placeholder.with_span(locate(range))))
+            .collect();
+        let start = StartTag { tag: tag_buf.clone(), attributes, placeholders, span: Some(span) };
+        events.push_back(Event::StartTag(start));
+        let is_void = self.syntax == Syntax::Html && is_void_tag(&tag_buf);
+        if tag.self_closing || is_void {
+            events.push_back(Event::EndTag(EndTag::new(tag_buf).with_span(Span::new(span.end, span.end))));
+            return
+        }
+        self.stack.push(OpenTag { tag: tag_buf, start: span.start, namespaces });
+    }
+    fn resolve_prefix(&mut self, prefix: &str, declared: &[(String, String)], span: Span) -> Option<String> {
+        let in_scope = declared.iter().chain(self.stack.iter().rev().flat_map(|x| x.namespaces.iter()));
+        match lookup_prefix(prefix, in_scope) {
+            Ok(namespace) => namespace,
+            Err(message) => {
+                self.report(DiagnosticCode::UndeclaredNamespacePrefix, message, span);
+                None
+            }
+        }
+    }
+    fn end_tag(&mut self, tag: TagToken<'_>, span: Span, events: &mut VecDeque<Event>) {
+        let tag_buf = TagBuf::new(tag.name);
+        let matches = |open: &OpenTag| match self.syntax {
+            Syntax::Html => open.tag.matches(&tag_buf),
+            Syntax::Xml => open.tag.as_original() == tag_buf.as_original(),
+        };
+        let Some(position) = self.stack.iter().rposition(matches) else {
+            let message = format!("End tag </{tag_buf}> without a matching start tag");
+            self.report(DiagnosticCode::UnmatchedEndTag, message, span);
+            return
+        };
+        if self.syntax == Syntax::Xml && position + 1 < self.stack.len() {
+            let expected = self.stack.last().unwrap().tag.to_string();
+            let message = format!("Expected </{expected}>, found </{tag_buf}>");
+            self.report(DiagnosticCode::MisnestedTags, message, span);
+        }
+        while self.stack.len() > position + 1 {
+            let open = self.stack.pop().unwrap();
+            if self.syntax == Syntax::Html && !has_optional_end_tag(&open.tag) {
+                let message = format!("Unclosed <{}> element closed by </{tag_buf}>", open.tag);
+                self.report(DiagnosticCode::UnclosedElement, message, Span::new(open.start, span.start));
+            }
+            events.push_back(Event::EndTag(EndTag::new(open.tag).with_span(Span::new(span.start, span.start))));
+        }
+        let open = self.stack.pop().unwrap();
+        events.push_back(Event::EndTag(EndTag::new(open.tag).with_span(span)));
+    }
+}
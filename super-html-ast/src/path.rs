@@ -0,0 +1,235 @@
+use std::fmt::Display;
+use std::iter::Enumerate;
+use std::slice::Iter;
+use std::str::FromStr;
+use crate::{Fragment, Node};
+
+// ————————————————————————————————————————————————————————————————————————————
+// NODE PATHS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Where a node is in a tree, as the child indices leading to it from the
+/// root: `/1/0/2` is the third child of the first child of the second child.
+/// The root itself is `/`. Children are those of elements and fragments.
+///
+/// A path refers to the same node for as long as the tree is not restructured
+/// above it, and can be written down and parsed back. Paths order like the
+/// nodes they refer to in document order, ancestors first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodePath(Vec<usize>);
+
+impl NodePath {
+    pub fn root() -> Self {
+        Self::default()
+    }
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// How many steps lead from the root.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn as_slice(&self) -> &[usize] {
+        &self.0
+    }
+    /// The path of the child at `index` of this node.
+    pub fn child(&self, index: usize) -> Self {
+        let mut path = self.clone();
+        path.push(index);
+        path
+    }
+    /// The path of the parent; `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.0.split_last()?;
+        Some(Self(parent.to_vec()))
+    }
+    /// Whether this path leads to `other` or one of its ancestors.
+    pub fn is_ancestor_of(&self, other: &NodePath) -> bool {
+        other.0.starts_with(&self.0)
+    }
+    pub fn push(&mut self, index: usize) {
+        self.0.push(index);
+    }
+    pub fn pop(&mut self) -> Option<usize> {
+        self.0.pop()
+    }
+}
+
+impl From<Vec<usize>> for NodePath {
+    fn from(indices: Vec<usize>) -> Self {
+        Self(indices)
+    }
+}
+
+impl From<NodePath> for Vec<usize> {
+    fn from(path: NodePath) -> Self {
+        path.0
+    }
+}
+
+impl FromIterator<usize> for NodePath {
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl AsRef<[usize]> for NodePath {
+    fn as_ref(&self) -> &[usize] {
+        &self.0
+    }
+}
+
+impl Display for NodePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("/")
+        }
+        self.0.iter().try_for_each(|index| write!(f, "/{index}"))
+    }
+}
+
+impl FromStr for NodePath {
+    type Err = NodePathError;
+    /// Parses a path as displayed: `/`, or indices each preceded by `/`.
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let error = |message: &str, offset: usize| NodePathError { message: message.to_string(), offset };
+        let Some(rest) = source.strip_prefix('/') else {
+            return Err(error("Expected `/`", 0))
+        };
+        if rest.is_empty() {
+            return Ok(Self::root())
+        }
+        let mut offset = 1;
+        let mut indices = Vec::new();
+        for step in rest.split('/') {
+            if step.is_empty() || !step.bytes().all(|x| x.is_ascii_digit()) {
+                return Err(error("Expected a child index", offset))
+            }
+            indices.push(step.parse().map_err(|_| error("Child index out of range", offset))?);
+            offset += step.len() + 1;
+        }
+        Ok(Self(indices))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// ERRORS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A path that could not be parsed.
+#[derive(Debug, Clone)]
+pub struct NodePathError {
+    pub message: String,
+    /// The byte offset in the path where parsing failed.
+    pub offset: usize,
+}
+
+impl Display for NodePathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for NodePathError {}
+
+// ————————————————————————————————————————————————————————————————————————————
+// LOOKUP
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Node {
+    /// The node at `path` below this one (see [`NodePath`]).
+    pub fn get_by_path(&self, path: impl AsRef<[usize]>) -> Option<&Node> {
+        get_by_path(self, path.as_ref())
+    }
+    pub fn get_by_path_mut(&mut self, path: impl AsRef<[usize]>) -> Option<&mut Node> {
+        get_by_path_mut(self, path.as_ref())
+    }
+    /// This node and its descendants in document order, each with its path.
+    pub fn iter_with_paths(&self) -> WithPaths<'_> {
+        WithPaths::new(self)
+    }
+}
+
+/// A tree the path functions work on: [`Node`] or [`crate::borrowed::Node`].
+pub(crate) trait Tree: Sized {
+    /// The children of an element or fragment.
+    fn child_nodes(&self) -> Option<&[Self]>;
+    fn child_nodes_mut(&mut self) -> Option<&mut [Self]>;
+}
+
+impl Tree for Node {
+    fn child_nodes(&self) -> Option<&[Self]> {
+        children(self).map(Fragment::as_node_slice)
+    }
+    fn child_nodes_mut(&mut self) -> Option<&mut [Self]> {
+        children_mut(self).map(|x| x.as_mut())
+    }
+}
+
+pub(crate) fn get_by_path<'a, N: Tree>(mut node: &'a N, path: &[usize]) -> Option<&'a N> {
+    for index in path {
+        node = node.child_nodes()?.get(*index)?;
+    }
+    Some(node)
+}
+
+pub(crate) fn get_by_path_mut<'a, N: Tree>(mut node: &'a mut N, path: &[usize]) -> Option<&'a mut N> {
+    for index in path {
+        node = node.child_nodes_mut()?.get_mut(*index)?;
+    }
+    Some(node)
+}
+
+/// See [`Node::iter_with_paths`].
+pub struct WithPaths<'a, N = Node> {
+    root: Option<&'a N>,
+    /// The children of each node on the path being visited.
+    stack: Vec<Enumerate<Iter<'a, N>>>,
+    path: Vec<usize>,
+}
+
+impl<'a, N> WithPaths<'a, N> {
+    pub(crate) fn new(root: &'a N) -> Self {
+        Self { root: Some(root), stack: Vec::new(), path: Vec::new() }
+    }
+}
+
+impl<'a, N: Tree> Iterator for WithPaths<'a, N> {
+    type Item = (NodePath, &'a N);
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.stack.extend(root.child_nodes().map(|x| x.iter().enumerate()));
+            return Some((NodePath::root(), root))
+        }
+        loop {
+            let depth = self.stack.len();
+            let Some((index, node)) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue
+            };
+            self.path.truncate(depth - 1);
+            self.path.push(index);
+            self.stack.extend(node.child_nodes().map(|x| x.iter().enumerate()));
+            return Some((NodePath(self.path.clone()), node))
+        }
+    }
+}
+
+pub(crate) fn children(node: &Node) -> Option<&Fragment> {
+    match node {
+        Node::Element(element) => Some(&element.children),
+        Node::Fragment(fragment) => Some(fragment),
+        _ => None,
+    }
+}
+
+pub(crate) fn children_mut(node: &mut Node) -> Option<&mut Fragment> {
+    match node {
+        Node::Element(element) => Some(&mut element.children),
+        Node::Fragment(fragment) => Some(fragment),
+        _ => None,
+    }
+}
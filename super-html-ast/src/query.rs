@@ -1,12 +1,22 @@
-use crate::{Element, Fragment, Node, TagBuf};
+use std::iter::Enumerate;
+use std::slice::Iter;
+use crate::{Element, Fragment, Node, NodePath, Selector, SelectorElement, TagBuf};
+use crate::path::Tree;
 
 impl Node {
     pub fn find_first(&self, target: &TagBuf) -> Option<Node> {
-        match self {
-            Self::Element(element) => element.find_first(target),
-            Self::Fragment(fragment) => fragment.find_first(target),
-            Self::Text(_) => None,
-        }
+        find_first_in(std::slice::from_ref(self), target)
+    }
+    /// Where [`Node::find_first`] finds its match, below this node.
+    pub fn find_first_path(&self, target: &TagBuf) -> Option<NodePath> {
+        self.iter_with_paths()
+            .find(|(_, node)| node.as_element().is_some_and(|x| x.tag.matches(target)))
+            .map(|(path, _)| path)
+    }
+    /// Where the elements matching `selector` are below this node, in document
+    /// order. Elements are matched against their ancestors up to this node.
+    pub fn select_paths(&self, selector: &Selector) -> Vec<NodePath> {
+        select_paths(self, selector, |node| node.as_element().map(|x| (&x.tag, &x.attributes)))
     }
 }
 
@@ -21,7 +31,79 @@ impl Element {
 
 impl Fragment {
     pub fn find_first(&self, target: &TagBuf) -> Option<Node> {
-        self.iter()
-            .find_map(|x| x.find_first(target))
+        find_first_in(self.as_node_slice(), target)
+    }
+}
+
+/// Depth-first search in document order, keeping the unvisited siblings of
+/// each level on an explicit stack rather than recursing.
+fn find_first_in(nodes: &[Node], target: &TagBuf) -> Option<Node> {
+    let mut stack = vec![nodes.iter()];
+    while let Some(siblings) = stack.last_mut() {
+        let Some(node) = siblings.next() else {
+            stack.pop();
+            continue
+        };
+        match node {
+            Node::Element(element) if element.tag.matches(target) => return Some(node.clone()),
+            Node::Element(element) => stack.push(element.children.iter()),
+            Node::Fragment(fragment) => stack.push(fragment.iter()),
+            Node::Text(_) => (),
+            Node::Comment(_) | Node::Doctype(_) | Node::ProcessingInstruction(_) | Node::Placeholder(_) => (),
+        }
     }
-}
\ No newline at end of file
+    None
+}
+
+/// See [`Node::select_paths`]; `element` gives what selectors match of the
+/// nodes that are elements.
+pub(crate) fn select_paths<'a, N: Tree, E: SelectorElement>(
+    root: &'a N,
+    selector: &Selector,
+    element: impl Fn(&'a N) -> Option<E>,
+) -> Vec<NodePath> {
+    let mut matches = Vec::new();
+    let mut path = Vec::new();
+    // The elements on the path.
+    let mut elements: Vec<E> = Vec::new();
+    // The children of each node on the path, and whether it is an element.
+    let mut stack: Vec<(Enumerate<Iter<N>>, bool)> = Vec::new();
+    let mut next = Some(root);
+    loop {
+        if let Some(node) = next.take() {
+            let is_element = match element(node) {
+                Some(element) => {
+                    elements.push(element);
+                    if selector.matches_elements(&elements) {
+                        matches.push(NodePath::from(path.clone()));
+                    }
+                    true
+                }
+                None => false,
+            };
+            match node.child_nodes() {
+                Some(children) => stack.push((children.iter().enumerate(), is_element)),
+                None if is_element => drop(elements.pop()),
+                None => (),
+            }
+            continue
+        }
+        let depth = stack.len();
+        let Some((siblings, is_element)) = stack.last_mut() else {
+            return matches
+        };
+        match siblings.next() {
+            Some((index, node)) => {
+                path.truncate(depth - 1);
+                path.push(index);
+                next = Some(node);
+            }
+            None => {
+                if *is_element {
+                    elements.pop();
+                }
+                stack.pop();
+            }
+        }
+    }
+}
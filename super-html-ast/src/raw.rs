@@ -0,0 +1,161 @@
+use std::fmt::Debug;
+use crate::{Doctype, Element, Placeholder};
+
+// ————————————————————————————————————————————————————————————————————————————
+// RAW SOURCE TEXT
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The exact source text a node (or tag) was parsed from, kept when parsing
+/// with [`crate::parser::ParserOptions::with_lossless`].
+///
+/// It remembers a fingerprint of what was parsed from it, so the serializer
+/// (see [`crate::format::FormatSettings::lossless`]) can tell whether the node
+/// was changed since and reuse the text verbatim only if it was not.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawSource {
+    text: String,
+    fingerprint: u64,
+}
+
+impl RawSource {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+    pub(crate) fn text(text: impl Into<String>, value: &str, cdata: bool) -> Self {
+        Self { text: text.into(), fingerprint: text_fingerprint(value, cdata) }
+    }
+    pub(crate) fn start_tag<'a>(
+        text: impl Into<String>,
+        tag: &str,
+        attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+        placeholders: impl IntoIterator<Item = (usize, &'a Placeholder)>,
+    ) -> Self {
+        Self { text: text.into(), fingerprint: start_tag_fingerprint(tag, attributes, placeholders) }
+    }
+    pub(crate) fn end_tag(text: impl Into<String>, tag: &str) -> Self {
+        Self { text: text.into(), fingerprint: end_tag_fingerprint(tag) }
+    }
+    pub(crate) fn doctype(text: impl Into<String>, doctype: &Doctype) -> Self {
+        Self { text: text.into(), fingerprint: doctype_fingerprint(doctype) }
+    }
+    /// The source text, if it still represents a text node with this content.
+    pub(crate) fn for_text(&self, value: &str, cdata: bool) -> Option<&str> {
+        self.reusable(text_fingerprint(value, cdata))
+    }
+    /// The source text, if it still represents the start tag of `element`.
+    pub(crate) fn for_start_tag(&self, element: &Element) -> Option<&str> {
+        let attributes = element.attributes.iter().map(|(key, value)| (key.as_str(), value.as_str()));
+        let placeholders = element.placeholders.iter().map(|(position, placeholder)| (*position, placeholder));
+        self.reusable(start_tag_fingerprint(element.tag.as_original(), attributes, placeholders))
+    }
+    /// The source text, if it still represents the end tag of `element`.
+    pub(crate) fn for_end_tag(&self, element: &Element) -> Option<&str> {
+        self.reusable(end_tag_fingerprint(element.tag.as_original()))
+    }
+    /// The source text, if it still represents this doctype.
+    pub(crate) fn for_doctype(&self, doctype: &Doctype) -> Option<&str> {
+        self.reusable(doctype_fingerprint(doctype))
+    }
+    /// What the text is checked against before reuse; stored by [`crate::Node::to_bytes`].
+    pub(crate) fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+    /// A source read back by [`crate::Node::from_bytes`].
+    pub(crate) fn from_parts(text: impl Into<String>, fingerprint: u64) -> Self {
+        Self { text: text.into(), fingerprint }
+    }
+    fn reusable(&self, fingerprint: u64) -> Option<&str> {
+        (self.fingerprint == fingerprint).then_some(self.text.as_str())
+    }
+}
+
+impl Debug for RawSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.text, f)
+    }
+}
+
+/// The raw start and end tags of an element. `end` is `None` when the
+/// source has no end tag (void, self-closing, implied or unclosed elements).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawTags {
+    pub start: RawSource,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub end: Option<RawSource>,
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL HELPERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// An FNV-1a hash of the parts of a node that lossless text stands for. The
+/// bytes fed to it are fixed here, not by [`std::hash::Hash`], so
+/// fingerprints stay the same across Rust versions and platforms.
+struct Fingerprint(u64);
+
+impl Fingerprint {
+    fn new(kind: &str) -> Self {
+        let mut fingerprint = Self(0xcbf2_9ce4_8422_2325);
+        fingerprint.str(kind);
+        fingerprint
+    }
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn number(&mut self, number: u64) {
+        self.bytes(&number.to_le_bytes());
+    }
+    /// Length-prefixed, so consecutive strings cannot run into each other.
+    fn str(&mut self, value: &str) {
+        self.number(value.len() as u64);
+        self.bytes(value.as_bytes());
+    }
+}
+
+fn text_fingerprint(value: &str, cdata: bool) -> u64 {
+    let mut fingerprint = Fingerprint::new("text");
+    fingerprint.str(value);
+    fingerprint.number(cdata.into());
+    fingerprint.0
+}
+
+fn start_tag_fingerprint<'a>(
+    tag: &str,
+    attributes: impl IntoIterator<Item = (&'a str, &'a str)>,
+    placeholders: impl IntoIterator<Item = (usize, &'a Placeholder)>,
+) -> u64 {
+    let mut fingerprint = Fingerprint::new("start-tag");
+    fingerprint.str(tag);
+    for (key, value) in attributes {
+        fingerprint.str(key);
+        fingerprint.str(value);
+    }
+    // Attributes and placeholders are told apart by the marker, which no
+    // length prefix can be mistaken for.
+    for (position, placeholder) in placeholders {
+        fingerprint.number(u64::MAX);
+        fingerprint.number(position as u64);
+        fingerprint.str(&placeholder.open);
+        fingerprint.str(&placeholder.expression);
+        fingerprint.str(&placeholder.close);
+    }
+    fingerprint.0
+}
+
+fn end_tag_fingerprint(tag: &str) -> u64 {
+    let mut fingerprint = Fingerprint::new("end-tag");
+    fingerprint.str(tag);
+    fingerprint.0
+}
+
+fn doctype_fingerprint(doctype: &Doctype) -> u64 {
+    let mut fingerprint = Fingerprint::new("doctype");
+    fingerprint.str(&doctype.name);
+    fingerprint.str(&doctype.public_id);
+    fingerprint.str(&doctype.system_id);
+    fingerprint.0
+}
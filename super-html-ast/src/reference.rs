@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+
+// ————————————————————————————————————————————————————————————————————————————
+// CHARACTER REFERENCES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A character reference (`&nbsp;`, `&#8203;`, `&#x2014;`, …) as it was
+/// written in the source, kept when parsing with
+/// [`crate::parser::ParserOptions::with_character_references`].
+///
+/// Text and attribute values are still stored resolved; the reference records
+/// where its resolved text starts so the serializer can write it back the way
+/// it was written, or as chosen by [`crate::format::ReferenceStyle`]. A
+/// reference whose text was edited away since no longer applies and is
+/// ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharacterReference {
+    /// Byte offset of the resolved text within the value.
+    pub offset: usize,
+    /// The reference as written, e.g. `&nbsp;`.
+    pub source: String,
+    /// The text it resolved to.
+    pub resolved: String,
+}
+
+impl CharacterReference {
+    pub fn new(offset: usize, source: impl Into<String>, resolved: impl Into<String>) -> Self {
+        Self { offset, source: source.into(), resolved: resolved.into() }
+    }
+    /// The entity name of a named reference (`nbsp` for `&nbsp;`).
+    pub fn name(&self) -> Option<&str> {
+        let name = self.source.strip_prefix('&')?;
+        let name = name.strip_suffix(';').unwrap_or(name);
+        (!name.starts_with('#')).then_some(name)
+    }
+    pub fn is_numeric(&self) -> bool {
+        self.source.starts_with("&#")
+    }
+    /// Whether the reference still stands for the text at its offset in `value`.
+    pub fn applies_to(&self, value: &str) -> bool {
+        !self.resolved.is_empty() && value.get(self.offset..).is_some_and(|rest| rest.starts_with(&self.resolved))
+    }
+    /// The reference as a named one: the written name if it had one, else the
+    /// shortest HTML name for the resolved text, if there is any.
+    pub fn to_named(&self) -> Option<String> {
+        if self.name().is_some() && self.source.ends_with(';') {
+            return Some(self.source.clone())
+        }
+        NAMED_REFERENCES.get(self.resolved.as_str()).map(|name| format!("&{name}"))
+    }
+    /// The reference as decimal numeric references, one per code point. A
+    /// numeric reference keeps its written form.
+    pub fn to_numeric(&self) -> String {
+        if self.is_numeric() && self.source.ends_with(';') {
+            return self.source.clone()
+        }
+        self.resolved.chars().map(|c| format!("&#{};", c as u32)).collect()
+    }
+}
+
+/// A piece of a value split by [`segments`].
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    Reference(&'a CharacterReference),
+}
+
+/// Splits `value` into plain text and the text of the `references` that still
+/// apply to it. Stale and overlapping references are skipped.
+pub(crate) fn segments<'a>(value: &'a str, references: &'a [CharacterReference]) -> Vec<Segment<'a>> {
+    let mut segments = Vec::with_capacity(references.len() * 2 + 1);
+    let mut position = 0;
+    for reference in references {
+        if reference.offset < position || !reference.applies_to(value) {
+            continue
+        }
+        if reference.offset > position {
+            segments.push(Segment::Text(&value[position..reference.offset]));
+        }
+        segments.push(Segment::Reference(reference));
+        position = reference.offset + reference.resolved.len();
+    }
+    if position < value.len() {
+        segments.push(Segment::Text(&value[position..]));
+    }
+    segments
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL HELPERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The preferred HTML entity name (with its `;`) for each text one resolves
+/// to: the shortest, lowercase before uppercase (`amp;` over `AMP;`).
+static NAMED_REFERENCES: Lazy<HashMap<String, &'static str>> = Lazy::new(|| {
+    let mut names = HashMap::<String, &'static str>::new();
+    for (name, (first, second)) in html5ever::data::NAMED_ENTITIES.entries() {
+        // The table also holds every prefix of a name (mapped to nothing) and
+        // the legacy names without `;`.
+        if *first == 0 || !name.ends_with(';') {
+            continue
+        }
+        let resolved = [*first, *second]
+            .into_iter()
+            .filter(|x| *x != 0)
+            .filter_map(char::from_u32)
+            .collect::<String>();
+        let rank = |name: &str| (name.len(), name.bytes().any(|x| x.is_ascii_uppercase()), name.to_string());
+        match names.get(&resolved) {
+            Some(current) if rank(current) <= rank(name) => (),
+            _ => {
+                names.insert(resolved, name);
+            }
+        }
+    }
+    names
+});
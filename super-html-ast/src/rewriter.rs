@@ -0,0 +1,413 @@
+//! Streaming, selector-based rewrites of markup, without building a tree.
+use std::io::{Read, Write};
+use crate::constants::is_void_tag;
+use crate::format::{EventWriter, FormatSettings};
+use crate::parser::{EventParser, ParseDiagnostic, ParserOptions};
+use crate::{AttributeKeyBuf, AttributeMap, AttributeValueBuf, EndTag, Event, Fragment, Node, Selector, StartTag, TagBuf};
+
+/// How many bytes [`StreamRewriter::rewrite`] reads at a time.
+const CHUNK_LENGTH: usize = 64 * 1024;
+
+// ————————————————————————————————————————————————————————————————————————————
+// STREAM REWRITER
+// ————————————————————————————————————————————————————————————————————————————
+
+/// Rewrites markup as it streams through, calling handlers registered against
+/// [`Selector`]s for the elements, text and comments they match.
+///
+/// Input is parsed as [`Event`]s (see [`EventParser`]) and written back with an
+/// [`EventWriter`] as soon as it is parsed, so memory is bounded by the open
+/// elements and the largest token rather than the document. Handlers only see
+/// an element's start tag and ancestors — edits to its contents are queued
+/// and made as the contents pass. Selectors match the markup as written, not
+/// as edited. Output is UTF-8, whatever the input encoding.
+///
+/// ```
+/// # use super_html_ast::Selector;
+/// # use super_html_ast::parser::ParserOptions;
+/// # use super_html_ast::rewriter::StreamRewriter;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let response_body = Vec::new();
+/// # let upstream = [b"<a href=x>l</a><scr".as_slice(), b"ipt>x()</script>"];
+/// let mut rewriter = StreamRewriter::new(response_body, &ParserOptions::native())
+///     .on_element(Selector::parse("a[href]")?, |element| {
+///         element.set_attribute("rel", "nofollow");
+///     })
+///     .on_element(Selector::parse("script")?, |element| element.remove());
+/// for chunk in upstream {
+///     rewriter.write(chunk)?;
+/// }
+/// rewriter.end()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct StreamRewriter<'h, W: Write> {
+    parser: EventParser,
+    writer: EventWriter<W>,
+    element_handlers: Vec<(Selector, ElementHandler<'h>)>,
+    text_handlers: Vec<(Selector, ContentHandler<'h>)>,
+    comment_handlers: Vec<(Selector, ContentHandler<'h>)>,
+    /// The open elements, innermost last.
+    stack: Vec<OpenElement>,
+    /// How many elements are open inside the innermost open element, while
+    /// its contents are dropped.
+    skipped_depth: usize,
+}
+
+type ElementHandler<'h> = Box<dyn FnMut(&mut ElementEdit) + 'h>;
+type ContentHandler<'h> = Box<dyn FnMut(&mut ContentEdit) + 'h>;
+
+struct OpenElement {
+    /// The tag and attributes as written, for matching selectors.
+    tag: TagBuf,
+    attributes: AttributeMap,
+    /// Whether the contents are dropped (removed or replaced).
+    skip_contents: bool,
+    /// Written, in order, when the element ends.
+    append: Fragment,
+    end_tag: Option<TagBuf>,
+    wrappers: Vec<TagBuf>,
+    after: Fragment,
+}
+
+impl<'h, W: Write> StreamRewriter<'h, W> {
+    pub fn new(output: W, options: &ParserOptions) -> Self {
+        Self::with_settings(output, options, FormatSettings::default())
+    }
+    pub fn with_settings(output: W, options: &ParserOptions, settings: FormatSettings) -> Self {
+        Self {
+            parser: EventParser::new(options),
+            writer: EventWriter::with_settings(output, settings),
+            element_handlers: Vec::new(),
+            text_handlers: Vec::new(),
+            comment_handlers: Vec::new(),
+            stack: Vec::new(),
+            skipped_depth: 0,
+        }
+    }
+    /// Calls `handler` for every element `selector` matches, in document
+    /// order. Handlers of the same element run in the order they were added.
+    pub fn on_element(mut self, selector: Selector, handler: impl FnMut(&mut ElementEdit) + 'h) -> Self {
+        self.element_handlers.push((selector, Box::new(handler)));
+        self
+    }
+    /// Calls `handler` for every text node whose parent element `selector`
+    /// matches. A text node arrives whole.
+    pub fn on_text(mut self, selector: Selector, handler: impl FnMut(&mut ContentEdit) + 'h) -> Self {
+        self.text_handlers.push((selector, Box::new(handler)));
+        self
+    }
+    /// Calls `handler` for every comment whose parent element `selector` matches.
+    pub fn on_comment(mut self, selector: Selector, handler: impl FnMut(&mut ContentEdit) + 'h) -> Self {
+        self.comment_handlers.push((selector, Box::new(handler)));
+        self
+    }
+    /// Rewrites the next chunk of input, writing whatever output it completes.
+    pub fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.parser.feed(chunk);
+        self.drain()
+    }
+    /// Rewrites the rest of the input and returns the output.
+    pub fn end(mut self) -> std::io::Result<W> {
+        self.parser.end();
+        self.drain()?;
+        self.writer.finish()
+    }
+    /// Rewrites all of `input` and returns the output.
+    pub fn rewrite(mut self, mut input: impl Read) -> std::io::Result<W> {
+        let mut chunk = vec![0; CHUNK_LENGTH];
+        loop {
+            match input.read(&mut chunk) {
+                Ok(0) => return self.end(),
+                Ok(length) => self.write(&chunk[..length])?,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+    /// Diagnostics of the input parsed so far.
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        self.parser.diagnostics()
+    }
+    fn drain(&mut self) -> std::io::Result<()> {
+        while let Some(event) = self.parser.next_event() {
+            self.event(event)?;
+        }
+        Ok(())
+    }
+    fn event(&mut self, event: Event) -> std::io::Result<()> {
+        if self.stack.last().is_some_and(|open| open.skip_contents) {
+            match &event {
+                Event::StartTag(_) => {
+                    self.skipped_depth += 1;
+                    return Ok(())
+                }
+                Event::EndTag(_) if self.skipped_depth > 0 => {
+                    self.skipped_depth -= 1;
+                    return Ok(())
+                }
+                Event::EndTag(_) => (),
+                _ => return Ok(()),
+            }
+        }
+        match event {
+            Event::StartTag(start) => self.start_tag(start),
+            Event::EndTag(_) => self.end_tag(),
+            Event::Text(text) => self.content(Node::Text(text), false),
+            Event::Comment(comment) => self.content(Node::Comment(comment), true),
+            event => self.writer.write(&event),
+        }
+    }
+    fn start_tag(&mut self, start: StartTag) -> std::io::Result<()> {
+        let matched = {
+            let path = self.stack
+                .iter()
+                .map(|open| (&open.tag, &open.attributes))
+                .chain([(&start.tag, &start.attributes)])
+                .collect::<Vec<_>>();
+            self.element_handlers
+                .iter()
+                .enumerate()
+                .filter(|(_, (selector, _))| selector.matches(&path))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+        let (tag, attributes) = (start.tag.clone(), start.attributes.clone());
+        let mut edit = ElementEdit::new(start);
+        for index in matched {
+            (self.element_handlers[index].1)(&mut edit);
+        }
+        let ElementEdit { start, before, prepend, append, inner, replacement, removed, keep_content, wrappers, after } = edit;
+        self.write_nodes(&before)?;
+        for wrapper in &wrappers {
+            self.writer.write(&Event::StartTag(wrapper.clone()))?;
+        }
+        let has_contents = !is_void_tag(&start.tag);
+        let mut open = OpenElement {
+            tag,
+            attributes,
+            skip_contents: true,
+            append: Fragment::default(),
+            end_tag: None,
+            wrappers: wrappers.into_iter().rev().map(|wrapper| wrapper.tag).collect(),
+            after,
+        };
+        match (replacement, removed) {
+            (Some(replacement), _) => self.write_node(&replacement)?,
+            (None, true) if keep_content && has_contents => {
+                self.write_nodes(&prepend)?;
+                open.skip_contents = false;
+                open.append = append;
+            }
+            (None, true) => (),
+            (None, false) => {
+                let end_tag = start.tag.clone();
+                self.writer.write(&Event::StartTag(start))?;
+                open.end_tag = Some(end_tag);
+                match inner {
+                    _ if !has_contents => (),
+                    Some(inner) => self.write_node(&inner)?,
+                    None => {
+                        self.write_nodes(&prepend)?;
+                        open.skip_contents = false;
+                        open.append = append;
+                    }
+                }
+            }
+        }
+        self.stack.push(open);
+        Ok(())
+    }
+    fn end_tag(&mut self) -> std::io::Result<()> {
+        let Some(open) = self.stack.pop() else {
+            return Ok(())
+        };
+        self.write_nodes(&open.append)?;
+        if let Some(tag) = open.end_tag {
+            self.writer.write(&Event::EndTag(EndTag::new(tag)))?;
+        }
+        for wrapper in open.wrappers {
+            self.writer.write(&Event::EndTag(EndTag::new(wrapper)))?;
+        }
+        self.write_nodes(&open.after)
+    }
+    /// Runs the text (or, if `comment`, comment) handlers on `node` and writes the result.
+    fn content(&mut self, node: Node, comment: bool) -> std::io::Result<()> {
+        let handlers = match comment {
+            true => &mut self.comment_handlers,
+            false => &mut self.text_handlers,
+        };
+        let path = self.stack.iter().map(|open| (&open.tag, &open.attributes)).collect::<Vec<_>>();
+        let mut edit = ContentEdit::new(node);
+        if !path.is_empty() {
+            for (selector, handler) in handlers.iter_mut() {
+                if selector.matches(&path) {
+                    handler(&mut edit);
+                }
+            }
+        }
+        let ContentEdit { node, before, replacement, removed, after } = edit;
+        self.write_nodes(&before)?;
+        match (replacement, removed) {
+            (Some(replacement), _) => self.write_node(&replacement)?,
+            (None, true) => (),
+            (None, false) => self.write_node(&node)?,
+        }
+        self.write_nodes(&after)
+    }
+    fn write_node(&mut self, node: &Node) -> std::io::Result<()> {
+        node.events().try_for_each(|event| self.writer.write(&event))
+    }
+    fn write_nodes(&mut self, nodes: &Fragment) -> std::io::Result<()> {
+        nodes.events().try_for_each(|event| self.writer.write(&event))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// ELEMENT EDITS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// An element passing through a [`StreamRewriter`], as seen by its handlers:
+/// its start tag can be changed, and edits to its contents and surroundings
+/// are queued until they pass.
+///
+/// Void elements (`<img>`, `<br>`, …) have no contents, so prepending,
+/// appending and replacing their contents does nothing.
+pub struct ElementEdit {
+    start: StartTag,
+    before: Fragment,
+    prepend: Fragment,
+    append: Fragment,
+    inner: Option<Node>,
+    replacement: Option<Node>,
+    removed: bool,
+    keep_content: bool,
+    wrappers: Vec<StartTag>,
+    after: Fragment,
+}
+
+impl ElementEdit {
+    fn new(start: StartTag) -> Self {
+        Self {
+            start,
+            before: Fragment::default(),
+            prepend: Fragment::default(),
+            append: Fragment::default(),
+            inner: None,
+            replacement: None,
+            removed: false,
+            keep_content: false,
+            wrappers: Vec::new(),
+            after: Fragment::default(),
+        }
+    }
+    pub fn tag(&self) -> &TagBuf {
+        &self.start.tag
+    }
+    /// Renames the element; its end tag is renamed to match.
+    pub fn set_tag(&mut self, tag: impl Into<TagBuf>) {
+        self.start.tag = tag.into();
+    }
+    pub fn attributes(&self) -> &AttributeMap {
+        &self.start.attributes
+    }
+    pub fn attributes_mut(&mut self) -> &mut AttributeMap {
+        &mut self.start.attributes
+    }
+    pub fn get_attribute(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.start.attributes.get(key).map(|value| value.as_str())
+    }
+    pub fn set_attribute(&mut self, key: impl Into<AttributeKeyBuf>, value: impl Into<String>) {
+        self.start.attributes.insert(key, AttributeValueBuf::literal(value));
+    }
+    pub fn remove_attribute(&mut self, key: impl AsRef<str>) -> Option<AttributeValueBuf> {
+        self.start.attributes.remove(key)
+    }
+    /// Inserts `node` before the element (and any wrappers).
+    pub fn before(&mut self, node: impl Into<Node>) {
+        self.before.push(node.into());
+    }
+    /// Inserts `node` after the element (and any wrappers).
+    pub fn after(&mut self, node: impl Into<Node>) {
+        self.after.insert(0, node.into());
+    }
+    /// Inserts `node` at the start of the element's contents.
+    pub fn prepend(&mut self, node: impl Into<Node>) {
+        self.prepend.insert(0, node.into());
+    }
+    /// Inserts `node` at the end of the element's contents.
+    pub fn append(&mut self, node: impl Into<Node>) {
+        self.append.push(node.into());
+    }
+    /// Replaces the element's contents with `node`, dropping what was
+    /// prepended or appended.
+    pub fn set_inner_content(&mut self, node: impl Into<Node>) {
+        self.inner = Some(node.into());
+    }
+    /// Replaces the whole element, contents included, with `node`.
+    pub fn replace(&mut self, node: impl Into<Node>) {
+        self.replacement = Some(node.into());
+    }
+    /// Removes the whole element, contents included.
+    pub fn remove(&mut self) {
+        self.removed = true;
+        self.keep_content = false;
+    }
+    /// Removes the element's tags, keeping its (possibly prepended or
+    /// appended) contents in its place.
+    pub fn remove_and_keep_content(&mut self) {
+        self.removed = true;
+        self.keep_content = true;
+    }
+    /// Wraps the element in `wrapper`; each wrapper goes around the previous
+    /// ones. What was inserted before or after the element stays outside.
+    pub fn wrap(&mut self, wrapper: StartTag) {
+        self.wrappers.insert(0, wrapper);
+    }
+    pub fn is_removed(&self) -> bool {
+        self.removed || self.replacement.is_some()
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// CONTENT EDITS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A text node or comment passing through a [`StreamRewriter`], as seen by
+/// its handlers.
+pub struct ContentEdit {
+    node: Node,
+    before: Fragment,
+    replacement: Option<Node>,
+    removed: bool,
+    after: Fragment,
+}
+
+impl ContentEdit {
+    fn new(node: Node) -> Self {
+        Self { node, before: Fragment::default(), replacement: None, removed: false, after: Fragment::default() }
+    }
+    /// The text or comment, as a [`Node::Text`] or [`Node::Comment`].
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+    /// The text or comment, to be edited in place.
+    pub fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+    pub fn before(&mut self, node: impl Into<Node>) {
+        self.before.push(node.into());
+    }
+    pub fn after(&mut self, node: impl Into<Node>) {
+        self.after.insert(0, node.into());
+    }
+    pub fn replace(&mut self, node: impl Into<Node>) {
+        self.replacement = Some(node.into());
+    }
+    pub fn remove(&mut self) {
+        self.removed = true;
+    }
+    pub fn is_removed(&self) -> bool {
+        self.removed || self.replacement.is_some()
+    }
+}
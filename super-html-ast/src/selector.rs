@@ -0,0 +1,367 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use crate::{Atom, AttributeMap, TagBuf};
+
+// ————————————————————————————————————————————————————————————————————————————
+// SELECTORS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A list of CSS selectors (`a[href], img`), matched against an element and
+/// its ancestors only, so it can be tested while a document streams past (see
+/// [`crate::rewriter::StreamRewriter`]).
+///
+/// Supported are type (`div`) and universal (`*`) selectors, `#id`, `.class`,
+/// attribute selectors (`[href]`, `[rel=nofollow]`, `~=`, `|=`, `^=`, `$=`,
+/// `*=`, with an `i` flag for ASCII case-insensitive values), and the
+/// descendant (` `) and child (`>`) combinators. Sibling combinators and
+/// pseudo-classes would need to look at siblings and are rejected.
+///
+/// Tag names and attribute names compare case-insensitively, as in HTML.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    alternatives: Vec<ComplexSelector>,
+}
+
+/// Compound selectors, the subject last, each with the combinator relating
+/// it to the one before (ignored for the first).
+#[derive(Debug, Clone)]
+struct ComplexSelector {
+    parts: Vec<(Combinator, CompoundSelector)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CompoundSelector {
+    /// Normalized tag name; `None` for `*` or no type selector.
+    tag: Option<String>,
+    conditions: Vec<AttributeCondition>,
+}
+
+#[derive(Debug, Clone)]
+struct AttributeCondition {
+    /// Normalized attribute name.
+    name: Atom,
+    test: Option<(Operator, String)>,
+    ignore_case: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    /// `=`
+    Equals,
+    /// `~=`, one of the whitespace-separated words.
+    Includes,
+    /// `|=`, equal or followed by `-`.
+    DashMatch,
+    /// `^=`
+    Prefix,
+    /// `$=`
+    Suffix,
+    /// `*=`
+    Substring,
+}
+
+impl Selector {
+    /// Parses a comma-separated selector list.
+    pub fn parse(source: &str) -> Result<Selector, SelectorError> {
+        let mut parser = SelectorParser { source, offset: 0 };
+        let mut alternatives = vec![parser.complex()?];
+        while parser.eat(',') {
+            alternatives.push(parser.complex()?);
+        }
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(Selector { alternatives }),
+            Some(_) => Err(parser.error("Expected `,` or the end of the selector")),
+        }
+    }
+    /// Whether the element at the end of `path` matches. `path` lists the tags
+    /// and attributes of the elements from the root (or as far up as known)
+    /// down to the element.
+    pub fn matches(&self, path: &[(&TagBuf, &AttributeMap)]) -> bool {
+        self.matches_elements(path)
+    }
+    /// [`Selector::matches`] for any representation of elements.
+    pub(crate) fn matches_elements<E: SelectorElement>(&self, path: &[E]) -> bool {
+        self.alternatives.iter().any(|alternative| alternative.matches(path))
+    }
+}
+
+/// What selectors look at in an element.
+pub(crate) trait SelectorElement {
+    fn local_name(&self) -> &str;
+    /// The value of the attribute whose name equals `name` ignoring ASCII case.
+    fn attribute(&self, name: &Atom) -> Option<&str>;
+}
+
+impl SelectorElement for (&TagBuf, &AttributeMap) {
+    fn local_name(&self) -> &str {
+        self.0.local_name()
+    }
+    fn attribute(&self, name: &Atom) -> Option<&str> {
+        self.1
+            .iter()
+            .find(|(key, _)| key.as_atom() == name || key.as_str().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorError;
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl ComplexSelector {
+    fn matches<E: SelectorElement>(&self, path: &[E]) -> bool {
+        let Some((subject, ancestors)) = path.split_last() else {
+            return false
+        };
+        let Some(((combinator, last), rest)) = self.parts.split_last() else {
+            return false
+        };
+        last.matches(subject) && Self::matches_ancestors(rest, *combinator, ancestors)
+    }
+    /// Whether the compound selectors `parts` match ancestors of an element
+    /// whose parent is the last of `ancestors`, `combinator` relating the last
+    /// part to that element. Descendant combinators take the innermost
+    /// ancestor that matches, backtracking to outer ones if the rest fails.
+    ///
+    /// Each part is looked for at each ancestor at most once through a
+    /// descendant combinator, so matching takes time linear in the depth.
+    fn matches_ancestors<E: SelectorElement>(parts: &[(Combinator, CompoundSelector)], combinator: Combinator, ancestors: &[E]) -> bool {
+        // Pending choices: the part to match next and how many ancestors remain.
+        let mut pending = vec![(parts.len(), combinator, ancestors.len())];
+        // For each part, the ancestors below this many were already tried as
+        // descendant matches; whether the rest matches from there does not
+        // depend on how they were reached.
+        let mut searched = vec![0; parts.len() + 1];
+        while let Some((count, combinator, available)) = pending.pop() {
+            let Some(((next_combinator, part), _)) = parts[..count].split_last() else {
+                return true
+            };
+            let candidates = match combinator {
+                Combinator::Child => available.saturating_sub(1)..available,
+                Combinator::Descendant => {
+                    let candidates = searched[count]..available;
+                    searched[count] = searched[count].max(available);
+                    candidates
+                }
+            };
+            // Outer ancestors first, so the innermost is tried first.
+            for position in candidates {
+                if part.matches(&ancestors[position]) {
+                    pending.push((count - 1, *next_combinator, position));
+                }
+            }
+        }
+        false
+    }
+}
+
+impl CompoundSelector {
+    fn matches(&self, element: &impl SelectorElement) -> bool {
+        if let Some(name) = &self.tag
+            && !element.local_name().eq_ignore_ascii_case(name) {
+            return false
+        }
+        self.conditions.iter().all(|condition| condition.matches(element))
+    }
+}
+
+impl AttributeCondition {
+    fn matches(&self, element: &impl SelectorElement) -> bool {
+        let value = element.attribute(&self.name);
+        let (Some(value), Some((operator, expected))) = (value, &self.test) else {
+            return value.is_some()
+        };
+        let (value, expected) = match self.ignore_case {
+            true => (value.to_ascii_lowercase(), expected.to_ascii_lowercase()),
+            false => (value.to_string(), expected.clone()),
+        };
+        match operator {
+            Operator::Equals => value == expected,
+            Operator::Includes => value.split_ascii_whitespace().any(|word| word == expected),
+            Operator::DashMatch => value == expected || value.starts_with(&format!("{expected}-")),
+            Operator::Prefix => !expected.is_empty() && value.starts_with(&expected),
+            Operator::Suffix => !expected.is_empty() && value.ends_with(&expected),
+            Operator::Substring => !expected.is_empty() && value.contains(&expected),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// ERRORS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A selector that could not be parsed.
+#[derive(Debug, Clone)]
+pub struct SelectorError {
+    pub message: String,
+    /// The byte offset in the selector where parsing failed.
+    pub offset: usize,
+}
+
+impl Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL — PARSER
+// ————————————————————————————————————————————————————————————————————————————
+
+struct SelectorParser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl SelectorParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.offset += expected.len_utf8();
+            return true
+        }
+        false
+    }
+    /// Skips whitespace, returning whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let rest = &self.source[self.offset..];
+        let trimmed = rest.trim_start();
+        self.offset += rest.len() - trimmed.len();
+        rest.len() != trimmed.len()
+    }
+    fn error(&self, message: &str) -> SelectorError {
+        SelectorError { message: message.to_string(), offset: self.offset }
+    }
+    fn complex(&mut self) -> Result<ComplexSelector, SelectorError> {
+        self.skip_whitespace();
+        let mut parts = vec![(Combinator::Descendant, self.compound()?)];
+        loop {
+            let had_whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => {
+                    self.offset += 1;
+                    self.skip_whitespace();
+                    Combinator::Child
+                }
+                Some('+' | '~') => return Err(self.error("Sibling combinators are not supported")),
+                Some(',') | None => break,
+                Some(_) if had_whitespace => Combinator::Descendant,
+                Some(_) => return Err(self.error("Expected a combinator")),
+            };
+            parts.push((combinator, self.compound()?));
+        }
+        Ok(ComplexSelector { parts })
+    }
+    fn compound(&mut self) -> Result<CompoundSelector, SelectorError> {
+        let mut compound = CompoundSelector::default();
+        let start = self.offset;
+        match self.peek() {
+            Some('*') => self.offset += 1,
+            Some(c) if is_name_char(c) => compound.tag = Some(self.name()?.to_ascii_lowercase()),
+            _ => (),
+        }
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.offset += 1;
+                    let id = self.name()?;
+                    compound.conditions.push(AttributeCondition::new("id", Some((Operator::Equals, id))));
+                }
+                Some('.') => {
+                    self.offset += 1;
+                    let class = self.name()?;
+                    compound.conditions.push(AttributeCondition::new("class", Some((Operator::Includes, class))));
+                }
+                Some('[') => {
+                    self.offset += 1;
+                    compound.conditions.push(self.attribute()?);
+                }
+                Some(':') => return Err(self.error("Pseudo-classes are not supported")),
+                _ => break,
+            }
+        }
+        if self.offset == start {
+            return Err(self.error("Expected a selector"))
+        }
+        Ok(compound)
+    }
+    fn name(&mut self) -> Result<String, SelectorError> {
+        let rest = &self.source[self.offset..];
+        let length = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error("Expected a name"))
+        }
+        self.offset += length;
+        Ok(rest[..length].to_string())
+    }
+    /// The rest of an attribute selector, after the `[`.
+    fn attribute(&mut self) -> Result<AttributeCondition, SelectorError> {
+        self.skip_whitespace();
+        let name = self.name()?;
+        self.skip_whitespace();
+        let rest = &self.source[self.offset..];
+        let operator = [
+            ("=", Operator::Equals),
+            ("~=", Operator::Includes),
+            ("|=", Operator::DashMatch),
+            ("^=", Operator::Prefix),
+            ("$=", Operator::Suffix),
+            ("*=", Operator::Substring),
+        ]
+        .into_iter()
+        .find(|(token, _)| rest.starts_with(token));
+        let Some((token, operator)) = operator else {
+            return match self.eat(']') {
+                true => Ok(AttributeCondition::new(&name, None)),
+                false => Err(self.error("Expected `]`")),
+            }
+        };
+        self.offset += token.len();
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                let rest = &self.source[self.offset + 1..];
+                let Some(length) = rest.find(quote) else {
+                    return Err(self.error("Unterminated string"))
+                };
+                self.offset += length + 2;
+                rest[..length].to_string()
+            }
+            _ => self.name()?,
+        };
+        self.skip_whitespace();
+        let ignore_case = matches!(self.peek(), Some('i' | 'I'));
+        if ignore_case {
+            self.offset += 1;
+        }
+        if !self.eat(']') {
+            return Err(self.error("Expected `]`"))
+        }
+        Ok(AttributeCondition { ignore_case, ..AttributeCondition::new(&name, Some((operator, value))) })
+    }
+}
+
+impl AttributeCondition {
+    fn new(name: &str, test: Option<(Operator, String)>) -> Self {
+        Self { name: Atom::new(&name.to_ascii_lowercase()), test, ignore_case: false }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_') || !c.is_ascii()
+}
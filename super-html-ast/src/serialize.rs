@@ -0,0 +1,578 @@
+//! `Serialize` and `Deserialize` for the AST (the `serde` feature), in the
+//! shape documented on [`Node`].
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+use crate::constants::HTML_NAMESPACE;
+use crate::{
+    Atom, AttributeKeyBuf, AttributeMap, AttributeValueBuf, CharacterReference, Doctype, Element, Fragment, Node,
+    Placeholder, ProcessingInstruction, RawSource, RawTags, SourcePosition, Span, TagBuf, Text,
+};
+
+// ————————————————————————————————————————————————————————————————————————————
+// NODES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// The namespace of elements that do not give one at the top of a tree; below
+/// that, elements default to the namespace of their parent.
+const TOP_LEVEL_NAMESPACE: &str = HTML_NAMESPACE;
+
+/// A node or fragment to serialize with the namespace its elements default to.
+struct Scoped<'a, T> {
+    value: &'a T,
+    namespace: Option<&'a str>,
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Scoped { value: self, namespace: Some(TOP_LEVEL_NAMESPACE) }.serialize(serializer)
+    }
+}
+
+impl Serialize for Scoped<'_, Node> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let namespace = self.namespace;
+        match self.value {
+            Node::Text(text) => text.serialize(serializer),
+            Node::Element(element) => Scoped { value: element, namespace }.serialize(serializer),
+            Node::Fragment(fragment) => tagged(serializer, "fragment", &Scoped { value: fragment, namespace }),
+            Node::Comment(comment) => tagged(serializer, "comment", comment),
+            Node::Doctype(doctype) => tagged(serializer, "doctype", doctype),
+            Node::ProcessingInstruction(pi) => tagged(serializer, "processing_instruction", pi),
+            Node::Placeholder(placeholder) => tagged(serializer, "placeholder", placeholder),
+        }
+    }
+}
+
+/// `{"<key>": value}`.
+fn tagged<S: Serializer>(serializer: S, key: &str, value: &impl Serialize) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(key, value)?;
+    map.end()
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        NodeSeed { namespace: Some(Atom::new(TOP_LEVEL_NAMESPACE)) }.deserialize(deserializer)
+    }
+}
+
+/// Reads a node whose elements default to `namespace`.
+struct NodeSeed {
+    namespace: Option<Atom>,
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed {
+    type Value = Node;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Node, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed {
+    type Value = Node;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a string, an element array or a node object")
+    }
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Node, E> {
+        Ok(Node::text(value))
+    }
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Node, E> {
+        Ok(Node::text(value))
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Node, A::Error> {
+        ElementSeed { namespace: self.namespace }.visit_seq(seq).map(Node::Element)
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut record = NodeRecord::default();
+        while let Some(key) = map.next_key::<NodeKey>()? {
+            let duplicate = match key {
+                NodeKey::Text => record.text.replace(map.next_value()?).is_some(),
+                NodeKey::Span => record.span.replace(map.next_value()?).is_some(),
+                NodeKey::Cdata => std::mem::replace(&mut record.cdata, map.next_value()?),
+                NodeKey::Raw => record.raw.replace(map.next_value()?).is_some(),
+                NodeKey::References => {
+                    let references = map.next_value::<Vec<CharacterReference>>()?;
+                    !std::mem::replace(&mut record.references, references).is_empty()
+                }
+                NodeKey::Comment => record.comment.replace(map.next_value()?).is_some(),
+                NodeKey::Doctype => record.doctype.replace(map.next_value()?).is_some(),
+                NodeKey::ProcessingInstruction => record.processing_instruction.replace(map.next_value()?).is_some(),
+                NodeKey::Placeholder => record.placeholder.replace(map.next_value()?).is_some(),
+                NodeKey::Fragment => {
+                    let fragment = map.next_value_seed(FragmentSeed { namespace: self.namespace.clone() })?;
+                    record.fragment.replace(fragment).is_some()
+                }
+            };
+            if duplicate {
+                return Err(de::Error::custom(format!("duplicate field `{}`", key.as_str())))
+            }
+        }
+        record.into_node().map_err(de::Error::custom)
+    }
+}
+
+/// The keys of the object forms of a node.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NodeKey {
+    Text,
+    Span,
+    Cdata,
+    Raw,
+    References,
+    Comment,
+    Doctype,
+    ProcessingInstruction,
+    Placeholder,
+    Fragment,
+}
+
+impl NodeKey {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Span => "span",
+            Self::Cdata => "cdata",
+            Self::Raw => "raw",
+            Self::References => "references",
+            Self::Comment => "comment",
+            Self::Doctype => "doctype",
+            Self::ProcessingInstruction => "processing_instruction",
+            Self::Placeholder => "placeholder",
+            Self::Fragment => "fragment",
+        }
+    }
+}
+
+/// Any of the object forms of a node.
+#[derive(Default)]
+struct NodeRecord {
+    text: Option<String>,
+    span: Option<Span>,
+    cdata: bool,
+    raw: Option<RawSource>,
+    references: Vec<CharacterReference>,
+    comment: Option<String>,
+    doctype: Option<Doctype>,
+    processing_instruction: Option<ProcessingInstruction>,
+    placeholder: Option<Placeholder>,
+    fragment: Option<Fragment>,
+}
+
+impl NodeRecord {
+    fn into_node(self) -> Result<Node, &'static str> {
+        let NodeRecord { text, span, cdata, raw, references, comment, doctype, processing_instruction, placeholder, fragment } = self;
+        let has_text_fields = span.is_some() || cdata || raw.is_some() || !references.is_empty();
+        let node = match (text, comment, doctype, processing_instruction, placeholder, fragment) {
+            (Some(value), None, None, None, None, None) => {
+                return Ok(Node::Text(Text { value, span, cdata, raw, references }))
+            }
+            (None, Some(comment), None, None, None, None) => Node::Comment(comment),
+            (None, None, Some(doctype), None, None, None) => Node::Doctype(doctype),
+            (None, None, None, Some(pi), None, None) => Node::ProcessingInstruction(pi),
+            (None, None, None, None, Some(placeholder), None) => Node::Placeholder(placeholder),
+            (None, None, None, None, None, Some(fragment)) => Node::Fragment(fragment),
+            _ => {
+                return Err("expected exactly one of `text`, `comment`, `doctype`, `processing_instruction`, `placeholder` and `fragment`")
+            }
+        };
+        match has_text_fields {
+            true => Err("only text nodes have `span`, `cdata`, `raw` and `references`"),
+            false => Ok(node),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TEXT
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Serialize for Text {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.span.is_none() && !self.cdata && self.raw.is_none() && self.references.is_empty() {
+            return serializer.serialize_str(&self.value)
+        }
+        TextRecord {
+            text: &self.value,
+            span: self.span,
+            cdata: self.cdata,
+            raw: self.raw.as_ref(),
+            references: &self.references,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Serialize)]
+struct TextRecord<'a> {
+    text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Span>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cdata: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<&'a RawSource>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    references: &'a [CharacterReference],
+}
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Node::deserialize(deserializer)? {
+            Node::Text(text) => Ok(text),
+            _ => Err(de::Error::custom("expected a text node")),
+        }
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// ELEMENTS
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Serialize for Element {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Scoped { value: self, namespace: Some(TOP_LEVEL_NAMESPACE) }.serialize(serializer)
+    }
+}
+
+impl Serialize for Scoped<'_, Element> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let element = self.value;
+        let namespace = element.tag.namespace();
+        let mut seq = serializer.serialize_seq(Some(2 + element.children.len()))?;
+        let is_plain = namespace == self.namespace
+            && element.span.is_none()
+            && element.raw.is_none()
+            && element.placeholders.is_empty()
+            && !element.template_contents;
+        match is_plain {
+            true => seq.serialize_element(element.tag.as_original())?,
+            false => seq.serialize_element(&ElementRecord {
+                name: element.tag.as_original().into(),
+                namespace: (namespace != self.namespace).then(|| namespace.map(Into::into)),
+                span: element.span,
+                raw: element.raw.clone(),
+                placeholders: element.placeholders.clone(),
+                template_contents: element.template_contents,
+            })?,
+        }
+        seq.serialize_element(&element.attributes)?;
+        for child in element.children.iter() {
+            seq.serialize_element(&Scoped { value: child, namespace })?;
+        }
+        seq.end()
+    }
+}
+
+/// The first item of an element array, when the tag name alone does not do.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ElementRecord {
+    name: String,
+    /// Left out if the element is in the namespace it defaults to, `null` if
+    /// it has none.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    namespace: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    span: Option<Span>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw: Option<RawTags>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    placeholders: Vec<(usize, Placeholder)>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    template_contents: bool,
+}
+
+/// Tells a field given as `null` from one left out, which is `None` by default.
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ElementHead {
+    Name(String),
+    Record(ElementRecord),
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ElementSeed { namespace: Some(Atom::new(TOP_LEVEL_NAMESPACE)) })
+    }
+}
+
+/// Reads an element that defaults to `namespace`.
+struct ElementSeed {
+    namespace: Option<Atom>,
+}
+
+impl<'de> Visitor<'de> for ElementSeed {
+    type Value = Element;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an element array, [tag, attributes, ...children]")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Element, A::Error> {
+        let head = seq.next_element::<ElementHead>()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut element = match head {
+            ElementHead::Name(name) => Element::new(TagBuf::new(name).with_namespace(self.namespace.clone())),
+            ElementHead::Record(record) => {
+                let namespace = match record.namespace {
+                    Some(namespace) => namespace.map(Atom::from),
+                    None => self.namespace.clone(),
+                };
+                Element {
+                    span: record.span,
+                    raw: record.raw,
+                    placeholders: record.placeholders,
+                    template_contents: record.template_contents,
+                    ..Element::new(TagBuf::new(record.name).with_namespace(namespace))
+                }
+            }
+        };
+        element.attributes = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let namespace = element.tag.namespace().map(Atom::new);
+        let mut children = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(child) = seq.next_element_seed(NodeSeed { namespace: namespace.clone() })? {
+            children.push(child);
+        }
+        element.children = Fragment::from_nodes(children);
+        Ok(element)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// FRAGMENTS
+// ————————————————————————————————————————————————————————————————————————————
+
+impl Serialize for Fragment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Scoped { value: self, namespace: Some(TOP_LEVEL_NAMESPACE) }.serialize(serializer)
+    }
+}
+
+impl Serialize for Scoped<'_, Fragment> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.value.iter().map(|value| Scoped { value, namespace: self.namespace }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Fragment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        FragmentSeed { namespace: Some(Atom::new(TOP_LEVEL_NAMESPACE)) }.deserialize(deserializer)
+    }
+}
+
+/// Reads a fragment whose elements default to `namespace`.
+struct FragmentSeed {
+    namespace: Option<Atom>,
+}
+
+impl<'de> DeserializeSeed<'de> for FragmentSeed {
+    type Value = Fragment;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Fragment, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FragmentSeed {
+    type Value = Fragment;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an array of nodes")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Fragment, A::Error> {
+        let mut nodes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(node) = seq.next_element_seed(NodeSeed { namespace: self.namespace.clone() })? {
+            nodes.push(node);
+        }
+        Ok(Fragment::from_nodes(nodes))
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// TAGS AND ATTRIBUTES
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A name that may carry metadata: a plain string, or an object.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum NameRepr {
+    Plain(String),
+    Record {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        namespace: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+}
+
+impl Serialize for TagBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.namespace() {
+            None => serializer.serialize_str(self.as_original()),
+            Some(namespace) => NameRepr::Record {
+                name: self.as_original().to_string(),
+                namespace: Some(namespace.to_string()),
+                span: None,
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TagBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match NameRepr::deserialize(deserializer)? {
+            NameRepr::Plain(name) => Ok(TagBuf::new(name)),
+            NameRepr::Record { name, namespace, span: None } => Ok(TagBuf::new(name).with_namespace(namespace.map(Atom::from))),
+            NameRepr::Record { span: Some(_), .. } => Err(de::Error::custom("tags have no `span`")),
+        }
+    }
+}
+
+impl Serialize for AttributeKeyBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.namespace().is_none() && self.span().is_none() {
+            return serializer.serialize_str(self.as_str())
+        }
+        NameRepr::Record {
+            name: self.as_str().to_string(),
+            namespace: self.namespace().map(Into::into),
+            span: self.span(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AttributeKeyBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match NameRepr::deserialize(deserializer)? {
+            NameRepr::Plain(name) => AttributeKeyBuf::new(name),
+            NameRepr::Record { name, namespace, span } => {
+                AttributeKeyBuf::new(name).with_namespace(namespace.map(Atom::from)).with_span(span)
+            }
+        })
+    }
+}
+
+impl Serialize for AttributeValueBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            AttributeValueBuf::Literal(value) => serializer.serialize_str(value),
+            AttributeValueBuf::Referenced { value, references } => {
+                AttributeRecord { value: value.clone(), namespace: None, span: None, references: references.clone() }
+                    .serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AttributeValueBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match AttributeRepr::deserialize(deserializer)? {
+            AttributeRepr::Plain(value) => Ok(AttributeValueBuf::literal(value)),
+            AttributeRepr::Record(AttributeRecord { value, namespace: None, span: None, references }) => {
+                Ok(AttributeValueBuf::referenced(value, references))
+            }
+            AttributeRepr::Record(_) => Err(de::Error::custom("attribute values have no `namespace` or `span`")),
+        }
+    }
+}
+
+/// The value of an entry of an attributes object: the value itself, or an
+/// object also carrying what the key and value know beyond their text.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AttributeRepr {
+    Plain(String),
+    Record(AttributeRecord),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AttributeRecord {
+    value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    span: Option<Span>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    references: Vec<CharacterReference>,
+}
+
+impl Serialize for AttributeMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            let is_plain = key.namespace().is_none() && key.span().is_none() && value.references().is_empty();
+            match is_plain {
+                true => map.serialize_entry(key.as_str(), value.as_str())?,
+                false => map.serialize_entry(key.as_str(), &AttributeRecord {
+                    value: value.as_str().to_string(),
+                    namespace: key.namespace().map(Into::into),
+                    span: key.span(),
+                    references: value.references().to_vec(),
+                })?,
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for AttributeMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(AttributeMapVisitor)
+    }
+}
+
+struct AttributeMapVisitor;
+
+impl<'de> Visitor<'de> for AttributeMapVisitor {
+    type Value = AttributeMap;
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an attributes object")
+    }
+    /// Entries are read in the order written, which becomes the attribute order.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<AttributeMap, A::Error> {
+        let mut attributes = AttributeMap::default();
+        while let Some((name, value)) = map.next_entry::<String, AttributeRepr>()? {
+            let (key, value) = match value {
+                AttributeRepr::Plain(value) => (AttributeKeyBuf::new(name), AttributeValueBuf::literal(value)),
+                AttributeRepr::Record(AttributeRecord { value, namespace, span, references }) => (
+                    AttributeKeyBuf::new(name).with_namespace(namespace.map(Atom::from)).with_span(span),
+                    AttributeValueBuf::referenced(value, references),
+                ),
+            };
+            attributes.insert(key, value);
+        }
+        Ok(attributes)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SPANS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// `[start offset, start line, start column, end offset, end line, end column]`.
+impl Serialize for Span {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(6)?;
+        for position in [self.start, self.end] {
+            tuple.serialize_element(&position.offset)?;
+            tuple.serialize_element(&position.line)?;
+            tuple.serialize_element(&position.column)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Span {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [offset, line, column, end_offset, end_line, end_column] = <[usize; 6]>::deserialize(deserializer)?;
+        Ok(Span::new(
+            SourcePosition { offset, line, column },
+            SourcePosition { offset: end_offset, line: end_line, column: end_column },
+        ))
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt::{Debug, Display};
+use std::ops::Range;
+
+// ————————————————————————————————————————————————————————————————————————————
+// SOURCE POSITIONS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A location in the parsed source text.
+///
+/// `offset` is a byte offset; `line` and `column` are 1-based, with columns
+/// counted in characters (not bytes).
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourcePosition {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Debug for SourcePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}@{}", self.line, self.column, self.offset)
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// SOURCE SPANS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// A half-open region (`start..end`) of the parsed source text.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+impl Span {
+    pub fn new(start: SourcePosition, end: SourcePosition) -> Self {
+        Self { start, end }
+    }
+    /// The byte range covered by this span.
+    pub fn range(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.end.offset.saturating_sub(self.start.offset)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The smallest span covering both `self` and `other`.
+    pub fn merge(&self, other: &Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+    /// Returns the source text covered by this span, if it is in bounds.
+    pub fn slice<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.range())
+    }
+}
+
+impl Debug for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}..{:?}", self.start, self.end)
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// ————————————————————————————————————————————————————————————————————————————
+// LINE INDEX
+// ————————————————————————————————————————————————————————————————————————————
+
+/// How many bytes apart the character counts of a [`LineIndex`] are taken.
+const CHAR_COUNT_STRIDE: usize = 256;
+
+/// Maps byte offsets of a source string to line/column positions.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+    /// The number of characters before every [`CHAR_COUNT_STRIDE`]th byte, so
+    /// that columns on long lines are counted from nearby rather than from the
+    /// start of the line.
+    char_counts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(ix, _)| ix + 1))
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        let mut char_counts = Vec::with_capacity(source.len() / CHAR_COUNT_STRIDE + 2);
+        for chunk in source.as_bytes().chunks(CHAR_COUNT_STRIDE) {
+            char_counts.push(count);
+            count += count_chars(chunk);
+        }
+        char_counts.push(count);
+        Self { source, line_starts, char_counts }
+    }
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+    /// Resolves a byte offset (clamped to the source length) into a position.
+    pub fn position(&self, offset: usize) -> SourcePosition {
+        let offset = offset.min(self.source.len());
+        let line_ix = match self.line_starts.binary_search(&offset) {
+            Ok(ix) => ix,
+            Err(ix) => ix - 1,
+        };
+        let line_start = self.line_starts[line_ix];
+        let column = match self.source.is_char_boundary(offset) {
+            true => self.chars_before(offset) - self.chars_before(line_start),
+            false => offset - line_start,
+        };
+        SourcePosition { offset, line: line_ix + 1, column: column + 1 }
+    }
+    /// The number of characters before `offset`, a character boundary.
+    fn chars_before(&self, offset: usize) -> usize {
+        let stride = offset / CHAR_COUNT_STRIDE;
+        let counted = stride * CHAR_COUNT_STRIDE;
+        self.char_counts[stride] + count_chars(&self.source.as_bytes()[counted..offset])
+    }
+    pub fn span(&self, range: Range<usize>) -> Span {
+        Span { start: self.position(range.start), end: self.position(range.end) }
+    }
+    /// Byte offset at which the given 1-based line starts.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.line_starts.get(line.checked_sub(1)?).copied()
+    }
+    /// Returns the full text of the given 1-based line, without its line terminator.
+    pub fn line_text(&self, line: usize) -> Option<&'a str> {
+        let start = self.line_start(line)?;
+        let end = self.line_starts
+            .get(line)
+            .map(|next| next - 1)
+            .unwrap_or(self.source.len());
+        self.source
+            .get(start..end)
+            .map(|text| text.strip_suffix('\r').unwrap_or(text))
+    }
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+/// The number of characters starting in `bytes` (UTF-8, cut anywhere).
+fn count_chars(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|byte| (**byte & 0xc0) != 0x80).count()
+}
@@ -1,10 +1,19 @@
 use std::hash::Hash;
+use crate::Atom;
 
 /// An owned, normalized tag. Like `PathBuf`.
+///
+/// The original form is the qualified name as written, prefix included
+/// (`svg:rect`). Namespace-aware parsers additionally record the namespace URI.
+/// Names and namespaces are [`Atom`]s, so tags compare by address and clone
+/// without allocating.
 #[derive(Clone)]
 pub struct TagBuf {
-    original: String,
-    normalized: String,
+    original: Atom,
+    normalized: Atom,
+    /// The normalized name without its prefix.
+    local: Atom,
+    namespace: Option<Atom>,
 }
 
 impl std::fmt::Debug for TagBuf {
@@ -15,10 +24,38 @@ impl std::fmt::Debug for TagBuf {
 
 impl TagBuf {
     /// Constructs a new TagBuf from any string-like input.
-    pub fn new(tag: impl Into<String>) -> Self {
-        let original = tag.into();
-        let normalized = original.to_lowercase();
-        Self { original, normalized }
+    pub fn new(tag: impl AsRef<str>) -> Self {
+        let original = Atom::new(tag.as_ref());
+        let normalized = match original.bytes().any(|x| x.is_ascii_uppercase() || !x.is_ascii()) {
+            true => Atom::new(&original.to_lowercase()),
+            false => original.clone(),
+        };
+        let local = match normalized.split_once(':') {
+            Some((_, local)) => Atom::new(local),
+            None => normalized.clone(),
+        };
+        Self { original, normalized, local, namespace: None }
+    }
+
+    /// Records the namespace URI of the tag.
+    pub fn with_namespace(mut self, namespace: impl Into<Option<Atom>>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// The namespace URI, if one was resolved.
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// The part of the original name before the `:`, if any.
+    pub fn prefix(&self) -> Option<&str> {
+        self.original.split_once(':').map(|(prefix, _)| prefix)
+    }
+
+    /// The original name without its prefix.
+    pub fn local_name(&self) -> &str {
+        self.original.split_once(':').map(|(_, local)| local).unwrap_or(&self.original)
     }
 
     /// Returns the original form of the tag.
@@ -30,8 +67,30 @@ impl TagBuf {
     pub fn as_normalized(&self) -> &str {
         &self.normalized
     }
+
+    /// The normalized form as an atom, for comparisons by address.
+    pub fn as_normalized_atom(&self) -> &Atom {
+        &self.normalized
+    }
+    /// What equality, ordering and hashing go by.
+    fn key(&self) -> (Option<&Atom>, &Atom) {
+        match &self.namespace {
+            Some(namespace) => (Some(namespace), &self.local),
+            None => (None, &self.normalized),
+        }
+    }
+    /// Compares normalized names. When both tags know their namespace, the
+    /// namespaces must agree and the prefixes are ignored, so an HTML `<a>` and
+    /// an SVG `<a>` are told apart while `svg:rect` matches an SVG `rect`.
+    ///
+    /// A tag without a namespace matches any tag of the same name, which makes
+    /// this looser than `==` (see below): equal tags always match, but a `rect`
+    /// matches both an SVG `rect` and one in another namespace.
     pub fn matches(&self, other: &Self) -> bool {
-        self.as_normalized() == other.as_normalized()
+        match (&self.namespace, &other.namespace) {
+            (Some(left), Some(right)) => left == right && self.local == other.local,
+            _ => self.normalized == other.normalized,
+        }
     }
 }
 
@@ -55,9 +114,34 @@ impl From<&str> for TagBuf {
     }
 }
 
+// Equality, ordering and hashing agree with `matches` where it is an
+// equivalence: tags with a namespace are equal if their namespaces and
+// normalized local names are (whatever the prefix), tags without one if their
+// normalized names are. Tags differing only in case are the same map key; a
+// tag with a namespace never equals one without.
+
+impl PartialEq for TagBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for TagBuf {}
+
+impl PartialOrd for TagBuf {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TagBuf {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
 impl Hash for TagBuf {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.as_original().hash(state);
+        self.key().hash(state);
     }
 }
-
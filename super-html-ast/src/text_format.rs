@@ -130,6 +130,8 @@ impl Node {
             Self::Element(element) => element.apply_formatter(buffer, scope),
             Self::Fragment(fragment) => fragment.apply_formatter(buffer, scope),
             Self::Text(text) => buffer.push_text(text),
+            Self::Placeholder(placeholder) => buffer.push_text(placeholder.to_string()),
+            Self::Comment(_) | Self::Doctype(_) | Self::ProcessingInstruction(_) => (),
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::{AttributeMap, Element, Fragment, Node, TagBuf};
+use crate::{AttributeMap, Doctype, Element, Node, Placeholder, ProcessingInstruction, TagBuf};
 
 // ————————————————————————————————————————————————————————————————————————————
 // HTML REDUCER
@@ -14,37 +14,80 @@ pub trait HtmlReducer {
         attributes: AttributeMap,
         children: Self::Output,
     ) -> Self::Output;
+    /// Comments are dropped (reduced to an empty fragment) unless overridden.
+    fn visit_comment(&mut self, comment: String) -> Self::Output {
+        let _ = comment;
+        self.visit_fragment(Vec::new())
+    }
+    /// Doctypes are dropped (reduced to an empty fragment) unless overridden.
+    fn visit_doctype(&mut self, doctype: Doctype) -> Self::Output {
+        let _ = doctype;
+        self.visit_fragment(Vec::new())
+    }
+    /// Processing instructions are dropped (reduced to an empty fragment) unless overridden.
+    fn visit_processing_instruction(&mut self, processing_instruction: ProcessingInstruction) -> Self::Output {
+        let _ = processing_instruction;
+        self.visit_fragment(Vec::new())
+    }
+    /// Template placeholders are reduced as their source text unless overridden.
+    fn visit_placeholder(&mut self, placeholder: Placeholder) -> Self::Output {
+        self.visit_text(placeholder.to_string())
+    }
 }
 
 // ————————————————————————————————————————————————————————————————————————————
 // IMPLEMENTATION
 // ————————————————————————————————————————————————————————————————————————————
 
-impl Node {
-    fn apply_html_reducer<R: HtmlReducer>(self, reducer: &mut R) -> R::Output {
-        match self {
-            Self::Text(text) => reducer.visit_text(text),
-            Self::Element(element) => element.apply_html_reducer(reducer),
-            Self::Fragment(fragment) => fragment.apply_html_reducer(reducer),
-        }
-    }
+/// An element or fragment whose children are being reduced.
+struct Frame<O> {
+    /// The element's tag and attributes; `None` for a fragment.
+    element: Option<(TagBuf, AttributeMap)>,
+    pending: std::vec::IntoIter<Node>,
+    outputs: Vec<O>,
 }
 
-impl Element {
+impl Node {
+    /// Reduces the tree bottom-up, in document order, with an explicit stack
+    /// of the elements and fragments still open instead of recursion.
     fn apply_html_reducer<R: HtmlReducer>(self, reducer: &mut R) -> R::Output {
-        let Element { tag, attributes, children } = self;
-        let children = children.apply_html_reducer(reducer);
-        reducer.visit_element(tag, attributes, children)
+        let mut stack = Vec::new();
+        let mut output = self.enter_html_reducer(reducer, &mut stack);
+        loop {
+            if let Some(result) = output.take() {
+                match stack.last_mut() {
+                    Some(frame) => frame.outputs.push(result),
+                    None => return result,
+                }
+            }
+            let frame = stack.last_mut().expect("an open element or fragment");
+            match frame.pending.next() {
+                Some(child) => output = child.enter_html_reducer(reducer, &mut stack),
+                None => {
+                    let Frame { element, outputs, .. } = stack.pop().unwrap();
+                    let children = reducer.visit_fragment(outputs);
+                    output = Some(match element {
+                        Some((tag, attributes)) => reducer.visit_element(tag, attributes, children),
+                        None => children,
+                    });
+                }
+            }
+        }
     }
-}
-
-impl Fragment {
-    fn apply_html_reducer<R: HtmlReducer>(self, reducer: &mut R) -> R::Output {
-        let nodes = self
-            .into_iter()
-            .map(|x| x.apply_html_reducer(reducer))
-            .collect::<Vec<_>>();
-        reducer.visit_fragment(nodes)
+    /// Reduces a leaf, or opens a frame for an element or fragment (returning `None`).
+    fn enter_html_reducer<R: HtmlReducer>(self, reducer: &mut R, stack: &mut Vec<Frame<R::Output>>) -> Option<R::Output> {
+        let (element, children) = match self {
+            Self::Text(text) => return Some(reducer.visit_text(text.value)),
+            Self::Element(Element { tag, attributes, children, .. }) => (Some((tag, attributes)), children),
+            Self::Fragment(fragment) => (None, fragment),
+            Self::Comment(comment) => return Some(reducer.visit_comment(comment)),
+            Self::Doctype(doctype) => return Some(reducer.visit_doctype(doctype)),
+            Self::ProcessingInstruction(pi) => return Some(reducer.visit_processing_instruction(pi)),
+            Self::Placeholder(placeholder) => return Some(reducer.visit_placeholder(placeholder)),
+        };
+        let outputs = Vec::with_capacity(children.len());
+        stack.push(Frame { element, pending: children.into_iter(), outputs });
+        None
     }
 }
 
@@ -1,5 +1,5 @@
 //! Basic HTML/Element to HTML rewrites.
-use crate::{AttributeMap, Element, Fragment, Node, TagBuf};
+use crate::{AttributeMap, Doctype, Element, Fragment, Node, Placeholder, ProcessingInstruction, RawTags, Span, TagBuf, Text};
 
 // ————————————————————————————————————————————————————————————————————————————
 // ELEMENT ONLY VISITOR
@@ -13,7 +13,7 @@ pub trait ElementRewriter {
         attributes: AttributeMap,
         children: Fragment,
     ) -> Node {
-        Node::Element(Element { tag, attributes, children })
+        Node::Element(Element { attributes, children, ..Element::new(tag) })
     }
 }
 
@@ -23,43 +23,15 @@ pub fn apply_element_rewriter<V: ElementRewriter>(node: Node, visitor: &mut V) -
 
 impl Node {
     fn apply_element_visitor<V: ElementRewriter>(self, visitor: &mut V) -> Node {
-        match self {
-            Self::Text(text) => Self::Text(text),
-            Self::Element(element) => element.apply_element_visitor(visitor),
-            Self::Fragment(fragment) => fragment.apply_element_visitor(visitor),
-        }
-    }
-}
-
-impl Element {
-    fn apply_element_visitor<V: ElementRewriter>(self, visitor: &mut V) -> Node {
-        let Element { tag, attributes, children } = self;
-        let children = children
-            .into_iter()
-            .map(|element| {
-                element.apply_element_visitor(visitor)
-            })
-            .collect::<Vec<_>>();
-        let children = Fragment::from_nodes(children);
-        visitor.visit_element(tag, attributes, children)
-    }
-}
-
-impl Fragment {
-    fn apply_element_visitor<V: ElementRewriter>(self, visitor: &mut V) -> Node {
-        let nodes = self
-            .into_iter()
-            .map(|element| {
-                element.apply_element_visitor(visitor)
-            })
-            .flat_map(|node| {
-                match node {
-                    Node::Fragment(fragment) => fragment.to_vec(),
-                    node => vec![node]
-                }
-            })
-            .collect::<Vec<_>>();
-        Node::Fragment(Fragment::from_nodes(nodes))
+        let leaf = |_: &mut V, node: Node| node;
+        let finish = |visitor: &mut V, frame: Frame| match frame.element {
+            Some(Element { tag, attributes, span, raw, placeholders, .. }) => {
+                let children = Fragment::from_nodes(frame.outputs);
+                visitor.visit_element(tag, attributes, children).with_fallback_source(span, raw, placeholders)
+            }
+            None => Node::Fragment(Fragment::from_nodes(splice_fragments(frame.outputs))),
+        };
+        rewrite(self, visitor, false, leaf, finish)
     }
 }
 
@@ -79,7 +51,7 @@ pub trait HtmlRewriter {
         &mut self,
         text: String,
     ) -> Node {
-        Node::Text(text)
+        Node::text(text)
     }
     fn visit_element(
         &mut self,
@@ -87,7 +59,31 @@ pub trait HtmlRewriter {
         attributes: AttributeMap,
         children: Fragment,
     ) -> Node {
-        Node::Element(Element { tag, attributes, children })
+        Node::Element(Element { attributes, children, ..Element::new(tag) })
+    }
+    fn visit_comment(
+        &mut self,
+        comment: String,
+    ) -> Node {
+        Node::Comment(comment)
+    }
+    fn visit_doctype(
+        &mut self,
+        doctype: Doctype,
+    ) -> Node {
+        Node::Doctype(doctype)
+    }
+    fn visit_processing_instruction(
+        &mut self,
+        processing_instruction: ProcessingInstruction,
+    ) -> Node {
+        Node::ProcessingInstruction(processing_instruction)
+    }
+    fn visit_placeholder(
+        &mut self,
+        placeholder: Placeholder,
+    ) -> Node {
+        Node::Placeholder(placeholder)
     }
 }
 
@@ -97,44 +93,129 @@ pub fn apply_html_rewriter<V: HtmlRewriter>(node: Node, visitor: &mut V) -> Node
 
 impl Node {
     fn full_markup_visitor<V: HtmlRewriter>(self, visitor: &mut V) -> Node {
-        match self {
-            Self::Text(text) => visitor.visit_text(text),
-            Self::Element(element) => element.full_markup_visitor(visitor),
-            Self::Fragment(fragment) => fragment.full_markup_visitor(visitor),
-        }
+        let leaf = |visitor: &mut V, node: Node| match node {
+            Self::Text(Text { value, span, cdata, raw, references }) => {
+                match visitor.visit_text(value) {
+                    // Rewritten text stays a CDATA section if it was one, and keeps
+                    // the span, source and references of the visited text unless
+                    // given its own (stale references are ignored when formatting).
+                    Self::Text(text) => Self::Text(Text {
+                        span: text.span.or(span),
+                        cdata: cdata || text.cdata,
+                        raw: text.raw.or(raw),
+                        references: if text.references.is_empty() { references } else { text.references },
+                        ..text
+                    }),
+                    node => node,
+                }
+            }
+            Self::Comment(comment) => visitor.visit_comment(comment),
+            Self::Doctype(doctype) => visitor.visit_doctype(doctype),
+            Self::ProcessingInstruction(pi) => visitor.visit_processing_instruction(pi),
+            Self::Placeholder(placeholder) => visitor.visit_placeholder(placeholder),
+            node @ (Self::Element(_) | Self::Fragment(_)) => node,
+        };
+        let finish = |visitor: &mut V, frame: Frame| match frame.element {
+            Some(Element { tag, attributes, span, raw, placeholders, .. }) => {
+                let children = Fragment::from_nodes(frame.outputs);
+                let children = Fragment::from_nodes(visitor.visit_fragment(children).flatten());
+                visitor.visit_element(tag, attributes, children).with_fallback_source(span, raw, placeholders)
+            }
+            None => visitor.visit_fragment(Fragment::from_nodes(splice_fragments(frame.outputs))),
+        };
+        rewrite(self, visitor, true, leaf, finish)
     }
 }
 
-impl Element {
-    fn full_markup_visitor<V: HtmlRewriter>(self, visitor: &mut V) -> Node {
-        let Element { tag, attributes, children } = self;
-        let children = children
-            .flatten()
-            .into_iter()
-            .map(|element| {
-                element.full_markup_visitor(visitor)
-            })
-            .collect::<Vec<_>>();
-        let children = Fragment::from_nodes(children);
-        let children = Fragment::from_nodes(visitor.visit_fragment(children).flatten());
-        visitor.visit_element(tag, attributes, children)
+// ————————————————————————————————————————————————————————————————————————————
+// INTERNAL HELPERS
+// ————————————————————————————————————————————————————————————————————————————
+
+/// An element or fragment whose children are being rewritten.
+struct Frame {
+    /// The element without its children; `None` for a fragment.
+    element: Option<Element>,
+    pending: std::vec::IntoIter<Node>,
+    outputs: Vec<Node>,
+}
+
+/// Rebuilds the tree bottom-up, in document order, keeping the elements and
+/// fragments still open on an explicit stack instead of recursing. `leaf`
+/// rewrites text, comments and declarations; `finish` rebuilds an element or
+/// fragment from its rewritten children. With `flatten_children`, fragments
+/// nested in an element's children are spliced in before they are visited.
+fn rewrite<V>(
+    root: Node,
+    visitor: &mut V,
+    flatten_children: bool,
+    leaf: impl Fn(&mut V, Node) -> Node,
+    finish: impl Fn(&mut V, Frame) -> Node,
+) -> Node {
+    let enter = |node: Node, stack: &mut Vec<Frame>| -> Option<Node> {
+        let (element, children) = match node {
+            Node::Element(mut element) => {
+                let children = std::mem::take(&mut element.children);
+                (Some(element), children)
+            }
+            Node::Fragment(fragment) => (None, fragment),
+            node => return Some(node),
+        };
+        let pending = match (&element, flatten_children) {
+            (Some(_), true) => children.flatten().into_iter(),
+            _ => children.into_iter(),
+        };
+        stack.push(Frame { element, outputs: Vec::with_capacity(pending.len()), pending });
+        None
+    };
+    let mut stack = Vec::new();
+    let mut output = enter(root, &mut stack).map(|node| leaf(visitor, node));
+    loop {
+        if let Some(result) = output.take() {
+            match stack.last_mut() {
+                Some(frame) => frame.outputs.push(result),
+                None => return result,
+            }
+        }
+        let frame = stack.last_mut().expect("an open element or fragment");
+        match frame.pending.next() {
+            Some(child) => output = enter(child, &mut stack).map(|node| leaf(visitor, node)),
+            None => {
+                let frame = stack.pop().unwrap();
+                output = Some(finish(visitor, frame));
+            }
+        }
     }
 }
 
-impl Fragment {
-    fn full_markup_visitor<V: HtmlRewriter>(self, visitor: &mut V) -> Node {
-        let nodes = self
-            .into_iter()
-            .map(|element| {
-                element.full_markup_visitor(visitor)
-            })
-            .flat_map(|node| {
-                match node {
-                    Node::Fragment(fragment) => fragment.to_vec(),
-                    node => vec![node]
+/// Replaces each fragment in `nodes` with its contents (one level deep).
+fn splice_fragments(nodes: Vec<Node>) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .flat_map(|node| {
+            match node {
+                Node::Fragment(fragment) => fragment.to_vec(),
+                node => vec![node]
+            }
+        })
+        .collect::<Vec<_>>()
+}
+
+impl Node {
+    /// Visitors rebuild elements from their parts, so re-attach the source span,
+    /// raw tags and start tag placeholders of the element that was visited unless
+    /// the visitor already set them. Raw tags are only reused while the tag and
+    /// attributes are unchanged.
+    fn with_fallback_source(self, span: Option<Span>, raw: Option<RawTags>, placeholders: Vec<(usize, Placeholder)>) -> Node {
+        match self {
+            Self::Element(mut element) => {
+                element.span = element.span.or(span);
+                element.raw = element.raw.or(raw);
+                if element.placeholders.is_empty() {
+                    element.placeholders = placeholders;
                 }
-            })
-            .collect::<Vec<_>>();
-        visitor.visit_fragment(Fragment::from_nodes(nodes))
+                Self::Element(element)
+            }
+            node => node,
+        }
     }
 }
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use super_html_ast::{Atom, AttributeKeyBuf, TagBuf};
+
+#[test]
+fn standard_names_are_static() {
+    for name in ["div", "viewBox", "viewbox", "xlink:href", "aria-label"] {
+        let atom = Atom::new(name);
+        assert!(atom.is_static(), "{name}");
+        assert_eq!(atom, Atom::from(name.to_string()));
+        assert_eq!(atom, name);
+    }
+    assert!(!Atom::new("x-standard-test").is_static());
+    // Case matters: only the standard forms and their lowercase are static.
+    assert!(!Atom::new("VIEWBOX").is_static());
+}
+
+#[test]
+fn equal_names_share_one_atom() {
+    let name = String::from("x-shared-test");
+    let left = Atom::new(&name);
+    let right = Atom::from(name.clone());
+    assert_eq!(left, right);
+    assert_eq!(left.as_str().as_ptr(), right.as_str().as_ptr());
+    assert_ne!(left, Atom::new("x-other-test"));
+    // Hashing and ordering go by the name, so atoms can be looked up by `str`.
+    let map = HashMap::from([(left.clone(), 1), (Atom::new("div"), 2)]);
+    assert_eq!(map.get("x-shared-test"), Some(&1));
+    assert_eq!(map.get("div"), Some(&2));
+    let mut sorted = vec![Atom::new("x-shared-test"), Atom::new("div"), Atom::new("a")];
+    sorted.sort();
+    assert_eq!(sorted, ["a", "div", "x-shared-test"]);
+}
+
+#[test]
+fn tags_and_keys_use_the_same_atoms() {
+    let tag = TagBuf::new("X-Widget-Test");
+    assert_eq!(tag.as_normalized_atom(), &Atom::new("x-widget-test"));
+    assert_eq!(TagBuf::new("DIV").as_normalized_atom(), &Atom::new("div"));
+    assert!(TagBuf::new("DIV").as_normalized_atom().is_static());
+    let key = AttributeKeyBuf::new("data-test");
+    assert_eq!(key.as_atom(), &Atom::new("data-test"));
+    assert_eq!(key.clone().as_atom().as_str().as_ptr(), key.as_atom().as_str().as_ptr());
+}
+
+#[test]
+fn dead_atoms_are_swept_and_live_ones_kept() {
+    let live = (0..1500).map(|i| Atom::new(&format!("x-live-{i}"))).collect::<Vec<_>>();
+    for i in 0..20_000 {
+        Atom::new(&format!("x-dead-{i}"));
+    }
+    // Dead names are swept whenever the interner doubles, so most are gone.
+    let count = Atom::interned_count();
+    assert!(count >= live.len(), "{count}");
+    assert!(count < 3 * live.len() + 1024, "{count}");
+    for (i, atom) in live.iter().enumerate() {
+        let name = format!("x-live-{i}");
+        assert_eq!(atom, &Atom::new(&name));
+        assert_eq!(atom.as_str(), name);
+    }
+}
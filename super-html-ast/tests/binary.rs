@@ -0,0 +1,82 @@
+use super_html_ast::{EncodedNode, EncodedTree, Node, BINARY_FORMAT_VERSION};
+use super_html_ast::parser::{parse_document_with, parse_fragment_with, ParserOptions, PlaceholderSyntax};
+
+const SOURCE: &str = "<!DOCTYPE html><p class=x id='a&amp;b'>caf&eacute; {{ name }}<br>\
+    <svg viewBox='0 0 1 1'><rect/></svg><template><i>t</i></template><!-- c --></p>";
+
+fn trees() -> Vec<Node> {
+    let options = [
+        ParserOptions::html5(),
+        ParserOptions::native().with_lossless(true).with_character_references(true),
+        ParserOptions::native().with_placeholders(PlaceholderSyntax::default()),
+    ];
+    let xml = "<?xml version='1.0'?><a xmlns='urn:a' xmlns:b='urn:b'><b:c b:d='1'><![CDATA[<>]]></b:c></a>";
+    options
+        .iter()
+        .flat_map(|options| [parse_document_with(SOURCE, options), parse_fragment_with(SOURCE, options)])
+        .chain([parse_document_with(xml, &ParserOptions::xml().with_lossless(true))])
+        .map(|result| result.unwrap_unchecked())
+        .collect()
+}
+
+#[test]
+fn trees_round_trip() {
+    for tree in trees() {
+        let bytes = tree.to_bytes();
+        let read = Node::from_bytes(&bytes).unwrap();
+        assert_eq!(read, tree);
+        // Spans, lossless text and namespaces are not all seen by `==`.
+        assert_eq!(read.to_bytes(), bytes);
+        assert_eq!(read.format_lossless(), tree.format_lossless());
+        assert_eq!(EncodedTree::new(&bytes).unwrap().to_node().to_bytes(), bytes);
+    }
+}
+
+/// Checks the zero-copy view of `encoded` against the decoded `node`.
+fn check_view(encoded: EncodedNode<'_, '_>, node: &Node) {
+    assert_eq!(encoded.to_node().to_bytes(), node.to_bytes());
+    match node {
+        Node::Element(element) => {
+            assert_eq!(encoded.span(), element.span);
+            assert_eq!(encoded.tag_name(), Some(element.tag.as_original()));
+            assert_eq!(encoded.namespace(), element.tag.namespace());
+            let attributes = element.attributes.iter().map(|(key, value)| (key.as_str(), value.as_str()));
+            assert!(encoded.attributes().eq(attributes));
+            assert_eq!(encoded.children().count(), element.children.len());
+            for (encoded, child) in encoded.children().zip(element.children.iter()) {
+                check_view(encoded, child);
+            }
+        }
+        Node::Fragment(fragment) => {
+            assert!(encoded.is_fragment());
+            for (encoded, child) in encoded.children().zip(fragment.iter()) {
+                check_view(encoded, child);
+            }
+        }
+        Node::Text(text) => {
+            assert_eq!(encoded.text(), Some(text.value.as_str()));
+            assert_eq!(encoded.span(), text.span);
+        }
+        Node::Comment(comment) => assert_eq!(encoded.comment(), Some(comment.as_str())),
+        _ => (),
+    }
+}
+
+#[test]
+fn encoded_trees_read_like_decoded_ones() {
+    for tree in trees() {
+        let bytes = tree.to_bytes();
+        check_view(EncodedTree::new(&bytes).unwrap().root(), &tree);
+    }
+}
+
+#[test]
+fn other_versions_and_truncated_input_are_rejected() {
+    let mut bytes = trees()[0].to_bytes();
+    assert!(Node::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Node::from_bytes(b"HTML").is_err());
+    bytes[4] = BINARY_FORMAT_VERSION + 1;
+    let error = Node::from_bytes(&bytes).unwrap_err();
+    assert!(error.to_string().contains("Unsupported format version"), "{error}");
+    assert!(EncodedTree::new(&bytes).is_err());
+}
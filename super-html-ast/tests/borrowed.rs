@@ -0,0 +1,78 @@
+use std::borrow::Cow;
+use super_html_ast::Node;
+use super_html_ast::borrowed;
+use super_html_ast::parser::{parse_borrowed, parse_fragment_with, DiagnosticCode, ParseLimits, ParserOptions, Severity};
+
+/// The messages of the warnings about ignored options.
+fn warnings<T>(result: &super_html_ast::parser::ParseResult<T>) -> Vec<String> {
+    result
+        .diagnostics()
+        .iter()
+        .filter(|x| x.code == DiagnosticCode::UnsupportedOption)
+        .inspect(|x| assert_eq!(x.severity, Severity::Warning))
+        .map(|x| x.message.clone())
+        .collect()
+}
+
+fn messages<T>(result: &super_html_ast::parser::ParseResult<T>) -> Vec<String> {
+    result.diagnostics().iter().map(|x| format!("{x:?}")).collect()
+}
+
+fn spans(tree: &Node) -> Vec<Option<super_html_ast::Span>> {
+    tree.iter_with_paths().filter(|(_, node)| node.as_element().is_some()).map(|(_, node)| node.span()).collect()
+}
+
+#[test]
+fn borrowed_trees_match_the_native_parser() {
+    let sources = [
+        "<ul><li>a &amp; b<li>c</ul><p>d<div>e</div>",
+        "<svg viewBox=\"0 0 1 1\"><foreignObject><p>x</p></foreignObject></svg><template><b>t</b></template>",
+        "<!DOCTYPE html><!-- c --><script>if (a < b) {}</script><textarea>&lt;</textarea>",
+        "<p>a</x>b<![CDATA[c]]>d</p>",
+        "<r xmlns:a=\"u\"><a:b a:c=\"&#65;\">&lt;x</a:b><![CDATA[y]]></r>",
+    ];
+    let limits = ParseLimits { max_text_length: Some(3), max_attribute_length: Some(2), ..ParseLimits::default() };
+    let options = [ParserOptions::native(), ParserOptions::native().with_limits(limits), ParserOptions::xml()];
+    for source in sources {
+        for options in options.iter() {
+            let owned = parse_fragment_with(source, options);
+            let borrowed = parse_borrowed(source, options);
+            assert_eq!(messages(&borrowed), messages(&owned), "{source:?} with {options:?}");
+            let borrowed = borrowed.unwrap_unchecked().into_owned();
+            let owned = owned.unwrap_unchecked();
+            assert_eq!(borrowed, owned, "{source:?} with {options:?}");
+            assert_eq!(spans(&borrowed), spans(&owned), "{source:?} with {options:?}");
+        }
+    }
+}
+
+#[test]
+fn verbatim_text_and_names_are_borrowed() {
+    let source = "<p class=\"x\" title=\"a&amp;b\">one<!-- two -->three &lt; four</p>";
+    let tree = parse_borrowed(source, &ParserOptions::native()).unwrap_unchecked();
+    let borrowed::Node::Fragment(fragment) = &tree else { panic!("{tree:?}") };
+    let element = fragment[0].as_element().unwrap();
+    assert!(matches!(element.tag.name, Cow::Borrowed("p")));
+    assert!(matches!(element.attributes[0].name, Cow::Borrowed("class")));
+    assert!(matches!(element.attributes[0].value, Cow::Borrowed("x")));
+    assert!(matches!(element.attributes[1].value, Cow::Owned(ref x) if x == "a&b"));
+    let children = element.children.as_node_slice();
+    assert!(matches!(&children[0], borrowed::Node::Text(x) if matches!(x.value, Cow::Borrowed("one"))));
+    assert!(matches!(&children[1], borrowed::Node::Comment(Cow::Borrowed(" two "))));
+    assert!(matches!(&children[2], borrowed::Node::Text(x) if matches!(x.value, Cow::Owned(ref x) if x == "three < four")));
+}
+
+#[test]
+fn unsupported_options_are_warned_about() {
+    let source = "<table><tr><td>a &amp; b</table>";
+    let native = parse_borrowed(source, &ParserOptions::native());
+    assert!(warnings(&native).is_empty());
+    let html5 = parse_borrowed(source, &ParserOptions::html5().with_lossless(true).with_character_references(true));
+    assert_eq!(warnings(&html5), [
+        "Borrowed parsing uses the native parser; the HTML5 backend is not supported",
+        "Lossless parsing is not supported by borrowed parsing",
+        "Keeping character references is not supported by borrowed parsing",
+    ]);
+    // No `<tbody>` is inserted: the native parser builds the tree.
+    assert_eq!(html5.unwrap_unchecked().into_owned(), native.unwrap_unchecked().into_owned());
+}
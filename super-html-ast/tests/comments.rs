@@ -0,0 +1,27 @@
+use super_html_ast::Node;
+use super_html_ast::parser::{parse_document_with, DiagnosticCode, ParserOptions};
+
+fn children(source: &str) -> (Vec<Node>, Vec<DiagnosticCode>) {
+    let result = parse_document_with(source, &ParserOptions::native());
+    let codes = result.diagnostics().iter().map(|x| x.code).collect();
+    let Node::Fragment(fragment) = result.unwrap_unchecked() else { panic!("expected a fragment") };
+    (fragment.to_vec(), codes)
+}
+
+#[test]
+fn abruptly_closed_comments_end_early() {
+    for source in ["<!--><p>a</p>", "<!---><p>a</p>"] {
+        let (nodes, codes) = children(source);
+        assert_eq!(nodes.len(), 2, "{source}");
+        assert_eq!(nodes[0].as_comment(), Some(""), "{source}");
+        assert_eq!(nodes[1].as_element().map(|x| x.tag.as_normalized()), Some("p"), "{source}");
+        assert_eq!(codes, [DiagnosticCode::UnexpectedToken], "{source}");
+    }
+}
+
+#[test]
+fn comments_may_contain_dashes() {
+    let (nodes, codes) = children("<!-- a -> b --><p></p>");
+    assert_eq!(nodes[0].as_comment(), Some(" a -> b "));
+    assert!(codes.is_empty());
+}
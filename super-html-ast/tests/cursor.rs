@@ -0,0 +1,87 @@
+use super_html_ast::{Cursor, Element, Node, NodePath};
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_fragment_with, ParserOptions};
+
+fn cursor(source: &str) -> Cursor {
+    Cursor::new(parse_fragment_with(source, &ParserOptions::native()).unwrap_unchecked())
+}
+
+fn markup(cursor: Cursor) -> String {
+    cursor.finish().format(FormatSettings::default())
+}
+
+#[test]
+fn moves_track_the_path() {
+    let mut cursor = cursor("<ul><li>a</li><li>b</li></ul>");
+    assert!(cursor.is_root());
+    assert!(cursor.to_first_child() && cursor.to_last_child());
+    assert_eq!((cursor.depth(), cursor.index()), (2, 1));
+    assert_eq!(cursor.path(), NodePath::from(vec![0, 1]));
+    assert!(!cursor.to_next_sibling());
+    assert!(cursor.to_previous_sibling() && !cursor.to_previous_sibling());
+    assert!(cursor.to_first_child());
+    assert_eq!(cursor.node().as_text(), Some("a"));
+    assert!(!cursor.to_child(0));
+    assert!(cursor.to_parent());
+    cursor.to_root();
+    assert!(cursor.is_root() && !cursor.to_parent());
+    assert_eq!(markup(cursor), "<ul><li>a</li><li>b</li></ul>");
+}
+
+#[test]
+fn siblings_are_inserted_around_the_focus() {
+    let mut cursor = cursor("<p>b</p>");
+    cursor.to_first_child();
+    cursor.insert_before(Node::text("a"));
+    cursor.insert_after(Node::text("c"));
+    assert_eq!(cursor.index(), 1);
+    cursor.to_next_sibling();
+    cursor.insert_after(Node::text("d"));
+    assert_eq!(markup(cursor), "a<p>b</p>cd");
+}
+
+#[test]
+fn wrapping_and_unwrapping() {
+    let mut cursor = cursor("<p>a<i>b</i>c</p>");
+    cursor.to_first_child();
+    cursor.to_child(1);
+    cursor.wrap(Element::new("b").with_children([Node::text("x")]));
+    assert_eq!(cursor.node().as_element().map(|x| x.tag.to_string()).as_deref(), Some("b"));
+    assert_eq!(cursor.path(), NodePath::from(vec![0, 1]));
+    // The wrapped element goes after the wrapper's own children.
+    assert!(cursor.to_last_child() && cursor.unwrap());
+    assert_eq!(cursor.node().as_text(), Some("b"));
+    assert_eq!(markup(cursor), "<p>a<b>xb</b>c</p>");
+}
+
+#[test]
+fn unwrapping_the_root_or_an_empty_element() {
+    let mut cursor = cursor("<p>a<br>b</p>");
+    cursor.to_first_child();
+    cursor.to_child(1);
+    // An element without children is removed; the cursor moves to the next sibling.
+    assert!(cursor.unwrap());
+    assert_eq!(cursor.node().as_text(), Some("b"));
+    assert!(!cursor.unwrap());
+    assert_eq!(markup(cursor), "<p>ab</p>");
+    // At the root, an element becomes a fragment.
+    let mut root = Cursor::new(Element::new("div").with_children([Node::text("x"), Node::text("y")]));
+    assert!(root.unwrap());
+    assert!(matches!(root.node(), Node::Fragment(x) if x.len() == 2));
+}
+
+#[test]
+fn removing_moves_to_a_neighbour() {
+    let mut cursor = cursor("<p>a</p><p>b</p><p>c</p>");
+    cursor.to_child(1);
+    let removed = cursor.remove().unwrap();
+    assert_eq!(removed.format(FormatSettings::default()), "<p>b</p>");
+    assert_eq!(cursor.index(), 1);
+    cursor.remove();
+    assert_eq!(cursor.index(), 0);
+    cursor.to_first_child();
+    cursor.remove();
+    assert_eq!(cursor.depth(), 1);
+    assert!(cursor.to_parent() && cursor.remove().is_none());
+    assert_eq!(markup(cursor), "<p></p>");
+}
@@ -0,0 +1,54 @@
+//! Deeply nested trees, on the default test thread stack.
+use std::hash::{DefaultHasher, Hash, Hasher};
+use pretty_tree::ToPrettyTree;
+use super_html_ast::{Element, Node};
+use super_html_ast::format::{EventWriter, FormatSettings};
+
+const DEPTH: usize = 100_000;
+
+fn nested(depth: usize, text: &str) -> Node {
+    let mut node = Node::text(text);
+    for _ in 0..depth {
+        node = Node::Element(Element::new("div").with_children([node]));
+    }
+    node
+}
+
+fn hash(node: &Node) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    node.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn deep_trees_are_cloned_compared_and_hashed() {
+    let tree = nested(DEPTH, "x");
+    let copy = tree.clone();
+    assert_eq!(copy, tree);
+    assert_ne!(nested(DEPTH, "y"), tree);
+    assert_eq!(hash(&copy), hash(&tree));
+}
+
+#[test]
+fn deep_trees_are_formatted() {
+    let tree = nested(DEPTH, "x");
+    let markup = tree.format(FormatSettings::default());
+    assert_eq!(markup, format!("{}x{}", "<div>".repeat(DEPTH), "</div>".repeat(DEPTH)));
+    let debug = format!("{tree:?}");
+    assert!(debug.starts_with("Element { tag: \"div\", children: [Element {"), "{}", &debug[..100]);
+    assert!(debug.contains('…'));
+    let _ = tree.to_pretty_tree();
+}
+
+#[test]
+fn deep_trees_round_trip_through_events_and_bytes() {
+    let tree = nested(DEPTH, "x");
+    let mut writer = EventWriter::new(Vec::new());
+    for event in tree.events() {
+        writer.write(&event).unwrap();
+    }
+    let markup = String::from_utf8(writer.finish().unwrap()).unwrap();
+    assert_eq!(markup, tree.format(FormatSettings::default()));
+    assert_eq!(Node::from_events(tree.events()), Node::Fragment([tree.clone()].into_iter().collect()));
+    assert_eq!(Node::from_bytes(&tree.to_bytes()).unwrap(), tree);
+}
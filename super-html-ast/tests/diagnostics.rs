@@ -0,0 +1,17 @@
+use super_html_ast::{SourcePosition, Span};
+use super_html_ast::parser::{DiagnosticCode, ParseDiagnostic};
+
+#[test]
+fn code_frame_with_a_zero_column() {
+    let position = SourcePosition { offset: 0, line: 1, column: 0 };
+    let diagnostic = ParseDiagnostic::new(DiagnosticCode::UnexpectedToken, "Oops")
+        .with_span(Span::new(position, position));
+    let frame = diagnostic.code_frame("<p>");
+    assert!(frame.ends_with("1 | <p>\n  | ^"), "{frame}");
+}
+
+#[test]
+fn code_frame_of_a_default_span() {
+    let diagnostic = ParseDiagnostic::new(DiagnosticCode::UnexpectedToken, "Oops").with_span(Span::default());
+    assert_eq!(diagnostic.code_frame("<p>"), "error[unexpected-token]: Oops");
+}
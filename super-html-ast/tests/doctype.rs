@@ -0,0 +1,19 @@
+use super_html_ast::Doctype;
+use super_html_ast::parser::{parse_document_with, ParserOptions};
+
+fn doctype(source: &str) -> Doctype {
+    let tree = parse_document_with(source, &ParserOptions::native()).unwrap_unchecked();
+    tree.as_fragment().unwrap().iter().find_map(|x| x.as_doctype()).unwrap().clone()
+}
+
+#[test]
+fn identifiers_are_written_as_they_were_quoted() {
+    let sources = [
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"http://www.w3.org/TR/html4/strict.dtd\">",
+        "<!DOCTYPE html SYSTEM 'C:\\dtd\\x\"y.dtd'>",
+        "<!DOCTYPE html SYSTEM \"it's.dtd\">",
+    ];
+    for source in sources {
+        assert_eq!(doctype(source).to_string(), source);
+    }
+}
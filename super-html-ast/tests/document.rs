@@ -0,0 +1,76 @@
+use super_html_ast::{Document, Element, Node, NodeId, NodePath};
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_fragment_with, ParserOptions};
+
+fn document(source: &str) -> Document {
+    Document::new(parse_fragment_with(source, &ParserOptions::native()).unwrap_unchecked())
+}
+
+/// The tag of an element or the value of a text node.
+fn name(document: &Document, id: NodeId) -> String {
+    match document.get(id) {
+        Node::Element(element) => element.tag.to_string(),
+        Node::Text(text) => text.value.clone(),
+        Node::Fragment(_) => "fragment".to_string(),
+        node => format!("{node:?}"),
+    }
+}
+
+fn names(document: &Document, ids: impl IntoIterator<Item = NodeId>) -> Vec<String> {
+    ids.into_iter().map(|id| name(document, id)).collect()
+}
+
+fn find(document: &Document, tag: &str) -> NodeId {
+    let root = document.root();
+    document.descendants(root).find(|id| name(document, *id) == tag).unwrap()
+}
+
+#[test]
+fn nodes_link_to_their_parent_and_siblings() {
+    let document = document("<ul><li>a</li><li>b</li><li>c</li></ul><p>d</p>");
+    let ul = find(&document, "ul");
+    assert_eq!(names(&document, document.children(ul)), ["li"; 3]);
+    let middle = document.children(ul).nth(1).unwrap();
+    assert_eq!(document.parent(middle), Some(ul));
+    assert_eq!(document.parent(ul), Some(document.root()));
+    assert_eq!(document.parent(document.root()), None);
+    let first = document.previous_sibling(middle).unwrap();
+    let last = document.next_sibling(middle).unwrap();
+    assert_eq!((document.first_child(ul), document.last_child(ul)), (Some(first), Some(last)));
+    assert_eq!(document.previous_sibling(first), None);
+    assert_eq!(document.next_sibling(last), None);
+    assert_eq!(document.following_siblings(first).collect::<Vec<_>>(), [middle, last]);
+    assert_eq!(document.preceding_siblings(last).collect::<Vec<_>>(), [middle, first]);
+    assert_eq!(document.children(ul).rev().collect::<Vec<_>>(), [last, middle, first]);
+    assert_eq!(names(&document, document.next_sibling(ul)), ["p"]);
+}
+
+#[test]
+fn ancestors_descendants_and_paths() {
+    let document = document("<div><p>a<b>c</b></p></div>");
+    let c = document.descendants(document.root()).find(|id| name(&document, *id) == "c").unwrap();
+    assert_eq!(names(&document, document.ancestors(c)), ["b", "p", "div", "fragment"]);
+    let div = find(&document, "div");
+    assert_eq!(names(&document, document.descendants(div)), ["div", "p", "a", "b", "c"]);
+    assert_eq!(document.closest_element(c, |x| x.tag.as_normalized() == "p"), Some(find(&document, "p")));
+    assert!(document.is_inclusive_ancestor(div, c));
+    assert!(!document.is_inclusive_ancestor(c, div));
+    assert_eq!(document.path(c), NodePath::from(vec![0, 0, 1, 0]));
+    assert_eq!(document.to_node(document.root()).get_by_path(document.path(c)).and_then(Node::as_text), Some("c"));
+}
+
+#[test]
+fn moved_nodes_are_relinked() {
+    let mut document = document("<ul><li>a</li><li>b</li></ul>");
+    let ul = find(&document, "ul");
+    let (a, b) = (document.first_child(ul).unwrap(), document.last_child(ul).unwrap());
+    document.insert_before(a, b);
+    assert_eq!(document.children(ul).collect::<Vec<_>>(), [b, a]);
+    let c = document.create(Node::Element(Element::new("li").with_children([Node::text("c")])));
+    document.append(ul, c);
+    document.detach(b);
+    assert_eq!(document.parent(b), None);
+    assert_eq!((document.previous_sibling(a), document.next_sibling(a)), (None, Some(c)));
+    let markup = document.into_node().format(FormatSettings::default());
+    assert_eq!(markup, "<ul><li>a</li><li>c</li></ul>");
+}
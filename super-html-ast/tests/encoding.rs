@@ -0,0 +1,23 @@
+use std::io::Cursor;
+use super_html_ast::Node;
+use super_html_ast::parser::{parse_document_bytes, parse_document_reader, parse_fragment_bytes, ParserOptions};
+
+#[test]
+fn byte_input_comes_back_decoded() {
+    let bytes = b"<meta charset=windows-1252><p>caf\xe9</p>";
+    let parsed = parse_document_bytes(bytes, &ParserOptions::native());
+    assert_eq!(parsed.source, "<meta charset=windows-1252><p>caf\u{e9}</p>");
+    assert_eq!(parsed.result.encoding().unwrap().name(), "windows-1252");
+    let read = parse_document_reader(Cursor::new(bytes), &ParserOptions::native()).unwrap();
+    assert_eq!(read.source, parsed.source);
+}
+
+#[test]
+fn spans_point_into_the_decoded_text() {
+    let bytes = b"\xef\xbb\xbf<p>\xc3\xa9</p><b>";
+    let parsed = parse_fragment_bytes(bytes, &ParserOptions::native());
+    let node = parsed.result.unwrap_unchecked();
+    let Node::Fragment(fragment) = &node else { panic!("{node:?}") };
+    let span = fragment.iter().next().and_then(|x| x.span()).unwrap();
+    assert_eq!(&parsed.source[span.start.offset..span.end.offset], "<p>\u{e9}</p>");
+}
@@ -0,0 +1,63 @@
+use super_html_ast::{EndTag, Event, Node, StartTag};
+use super_html_ast::format::{EventWriter, FormatSettings};
+use super_html_ast::parser::{parse_events, parse_fragment_with, ParserOptions};
+
+const SOURCE: &str = "<div class=\"a\">x &amp; y<br><svg><circle r=\"1\"></circle></svg><p></p><!--c--></div>";
+
+fn write(events: impl IntoIterator<Item = Event>) -> String {
+    let mut writer = EventWriter::new(Vec::new());
+    for event in events {
+        writer.write(&event).unwrap();
+    }
+    String::from_utf8(writer.finish().unwrap()).unwrap()
+}
+
+/// Each event as a short string, tags with their names.
+fn describe(event: &Event) -> String {
+    match event {
+        Event::StartTag(start) => format!("<{}>", start.tag),
+        Event::EndTag(end) => format!("</{}>", end.tag),
+        Event::Text(text) => text.value.clone(),
+        Event::Comment(comment) => format!("<!--{comment}-->"),
+        event => format!("{event:?}"),
+    }
+}
+
+#[test]
+fn node_events_are_balanced() {
+    let tree = parse_fragment_with("<p>a<br>b</p>", &ParserOptions::native()).unwrap_unchecked();
+    let events = tree.events().map(|x| describe(&x)).collect::<Vec<_>>();
+    assert_eq!(events, ["<p>", "a", "<br>", "</br>", "b", "</p>"]);
+}
+
+#[test]
+fn events_round_trip_through_the_writer() {
+    let tree = parse_fragment_with(SOURCE, &ParserOptions::native()).unwrap_unchecked();
+    let expected = "<div class=\"a\">x &amp; y<br /><svg><circle r=\"1\" /></svg><p></p><!--c--></div>";
+    assert_eq!(tree.format(FormatSettings::default()), expected);
+    assert_eq!(write(tree.events()), expected);
+    assert_eq!(Node::from_events(tree.events()), tree);
+}
+
+#[test]
+fn read_events_match_the_tree() {
+    let events = parse_events(SOURCE, &ParserOptions::native()).collect::<std::io::Result<Vec<_>>>().unwrap();
+    let tree = parse_fragment_with(SOURCE, &ParserOptions::native()).unwrap_unchecked();
+    let described = events.iter().map(describe).collect::<Vec<_>>();
+    assert_eq!(described, tree.events().map(|x| describe(&x)).collect::<Vec<_>>());
+    assert_eq!(write(events), tree.format(FormatSettings::default()));
+}
+
+#[test]
+fn start_tags_are_held_back_until_their_end_tag() {
+    let events = [
+        Event::StartTag(StartTag::new("br")),
+        Event::EndTag(EndTag::new("br")),
+        Event::StartTag(StartTag::new("div")),
+        Event::EndTag(EndTag::new("div")),
+        Event::StartTag(StartTag::new("span")),
+    ];
+    // An element closed right away closes itself if it can; one still open at
+    // the end is written as it is.
+    assert_eq!(write(events), "<br /><div></div><span>");
+}
@@ -0,0 +1,67 @@
+use super_html_ast::Node;
+use super_html_ast::constants::SVG_NAMESPACE;
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_fragment_in, parse_fragment_with, ParserOptions};
+
+/// The top-level nodes, as text for text and the tag for elements.
+fn top_level(tree: &Node) -> Vec<String> {
+    tree.as_fragment()
+        .unwrap()
+        .iter()
+        .map(|node| match node {
+            Node::Element(element) => format!("<{}>", element.tag),
+            node => node.as_text().unwrap().to_string(),
+        })
+        .collect()
+}
+
+fn parse_in(source: &str, context: &str) -> Node {
+    parse_fragment_with(source, &ParserOptions::html5().with_fragment_context(context)).unwrap_unchecked()
+}
+
+#[test]
+fn top_level_text_is_kept() {
+    for options in [ParserOptions::native(), ParserOptions::html5()] {
+        let tree = parse_fragment_with("Hello <b>world</b>!", &options).unwrap_unchecked();
+        assert_eq!(top_level(&tree), ["Hello ", "<b>", "!"], "{options:?}");
+    }
+}
+
+#[test]
+fn table_rows_need_a_table_context() {
+    let source = "a <tr><td>1</td></tr> b";
+    assert_eq!(top_level(&parse_in(source, "tbody")), ["a ", "<tr>", " b"]);
+    assert_eq!(parse_in(source, "tbody").format(FormatSettings::default()), source);
+    // In a `<body>`, the row and cell tags are dropped.
+    assert_eq!(top_level(&parse_in(source, "body")), ["a 1 b"]);
+}
+
+#[test]
+fn select_contexts_keep_options_and_text() {
+    let source = "a<option>x<option>y</select>z<b>w</b>";
+    let tree = parse_in(source, "select");
+    assert_eq!(top_level(&tree), ["a", "<option>", "<option>"]);
+    // Only text and options are allowed in a `<select>`.
+    assert_eq!(tree.format(FormatSettings::default()), "a<option>x</option><option>yzw</option>");
+}
+
+#[test]
+fn svg_contexts_parse_foreign_content() {
+    let tree = parse_in("<rect/>t", "svg");
+    assert_eq!(top_level(&tree), ["<rect>", "t"]);
+    let rect = tree.as_fragment().unwrap().iter().find_map(Node::as_element).unwrap();
+    assert_eq!(rect.tag.namespace(), Some(SVG_NAMESPACE));
+}
+
+#[test]
+fn context_fragments_carry_spans() {
+    let source = "x<tr><td>1</td></tr>";
+    let tree = parse_fragment_in(source, "tbody").unwrap_unchecked();
+    let spans = tree
+        .as_fragment()
+        .unwrap()
+        .iter()
+        .map(|node| node.span().unwrap().slice(source).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(spans, ["x", "<tr><td>1</td></tr>"]);
+}
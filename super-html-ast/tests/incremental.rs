@@ -0,0 +1,72 @@
+use super_html_ast::{Node, Span};
+use super_html_ast::parser::{parse_document_with, parse_fragment_with, reparse_document, reparse_fragment, ParserBackend, ParserOptions, TextEdit};
+
+fn spans(tree: &Node) -> Vec<Option<Span>> {
+    tree.iter_with_paths().map(|(_, node)| node.span()).collect()
+}
+
+/// Reparses `source` after `edit` and checks the result against a full parse.
+fn check(source: &str, edit: TextEdit, backend: ParserBackend) {
+    let options = ParserOptions::default().with_backend(backend).with_locate_spans(true);
+    let previous = parse_document_with(source, &options);
+    let reparse = reparse_document(previous, source, &edit, &options);
+    let full = parse_document_with(&reparse.source, &options).unwrap_unchecked();
+    let incremental = reparse.result.unwrap_unchecked();
+    assert_eq!(incremental, full, "{edit:?} on {source:?} with {backend:?}");
+    assert_eq!(spans(&incremental), spans(&full), "{edit:?} on {source:?} with {backend:?}");
+}
+
+#[test]
+fn edits_at_element_boundaries_match_a_full_parse() {
+    let source = "<div><p>a</p><span>b</span></div>";
+    let offsets = (0..=source.len()).collect::<Vec<_>>();
+    for backend in [ParserBackend::Native, ParserBackend::Html5] {
+        for offset in offsets.iter().copied() {
+            check(source, TextEdit::insert(offset, "XYZ"), backend);
+            check(source, TextEdit::insert(offset, "<b>x</b>"), backend);
+        }
+        for start in offsets.iter().copied() {
+            for end in [start + 1, start + 3].into_iter().filter(|x| *x <= source.len()) {
+                check(source, TextEdit::delete(start..end), backend);
+                check(source, TextEdit::new(start..end, "q"), backend);
+            }
+        }
+    }
+}
+
+#[test]
+fn insertion_after_an_element_is_kept() {
+    let source = "<div><p>a</p><span>b</span></div>";
+    let offset = source.find("</span>").unwrap() + "</span>".len();
+    check(source, TextEdit::insert(offset, "XYZ"), ParserBackend::Native);
+    check(source, TextEdit::insert(offset, "XYZ"), ParserBackend::Html5);
+}
+
+#[test]
+fn fragment_edits_at_element_boundaries_match_a_full_parse() {
+    let source = "<p>a</p><ul><li>b</li></ul>";
+    for backend in [ParserBackend::Native, ParserBackend::Html5] {
+        let options = ParserOptions::default().with_backend(backend).with_locate_spans(true);
+        for offset in 0..=source.len() {
+            let edit = TextEdit::insert(offset, "XYZ");
+            let previous = parse_fragment_with(source, &options);
+            let reparse = reparse_fragment(previous, source, &edit, &options);
+            let full = parse_fragment_with(&reparse.source, &options).unwrap_unchecked();
+            let incremental = reparse.result.unwrap_unchecked();
+            assert_eq!(incremental, full, "{edit:?} with {backend:?}");
+            assert_eq!(spans(&incremental), spans(&full), "{edit:?} with {backend:?}");
+        }
+    }
+}
+
+#[test]
+fn deeply_nested_edits_reparse_the_innermost_element() {
+    let depth = 20_000;
+    let source = format!("{}x{}", "<div>".repeat(depth), "</div>".repeat(depth));
+    let options = ParserOptions::native();
+    let previous = parse_document_with(&source, &options);
+    let reparse = reparse_document(previous, &source, &TextEdit::new(depth * 5..depth * 5 + 1, "y"), &options);
+    assert_eq!(reparse.changed, [vec![0; depth]]);
+    let innermost = reparse.get(&vec![0; depth]).and_then(|x| x.as_element()).unwrap();
+    assert_eq!(innermost.children.get(0).and_then(|x| x.as_text()), Some("y"));
+}
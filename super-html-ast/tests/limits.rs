@@ -0,0 +1,78 @@
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_fragment_with, DiagnosticCode, LimitAction, ParseLimits, ParserOptions, Severity};
+
+/// The markup parsed under `limits`, and the limit diagnostics.
+fn limited(source: &str, options: ParserOptions, limits: ParseLimits) -> (String, Vec<(Severity, String)>) {
+    let result = parse_fragment_with(source, &options.with_limits(limits));
+    let diagnostics = result
+        .diagnostics()
+        .iter()
+        .filter(|x| x.code == DiagnosticCode::LimitExceeded)
+        .map(|x| (x.severity, x.message.clone()))
+        .collect();
+    (result.unwrap_unchecked().format(FormatSettings::default()), diagnostics)
+}
+
+fn warning(message: &str) -> Vec<(Severity, String)> {
+    vec![(Severity::Warning, message.to_string())]
+}
+
+fn error(message: &str) -> Vec<(Severity, String)> {
+    vec![(Severity::Error, message.to_string())]
+}
+
+#[test]
+fn deeper_elements_are_dropped_with_their_contents() {
+    let source = "<a><b><c>x</c>y</b></a><i><u>z</u></i>";
+    let limits = ParseLimits::default().with_max_depth(1);
+    for options in [ParserOptions::native(), ParserOptions::html5()] {
+        let (markup, diagnostics) = limited(source, options, limits.clone());
+        assert_eq!(markup, "<a></a><i></i>");
+        assert_eq!(diagnostics, warning("Exceeded the nesting depth limit of 1; truncated"));
+    }
+}
+
+#[test]
+fn nodes_past_the_limit_are_dropped() {
+    let source = "<p>a</p><p>b</p><p>c</p>";
+    let (markup, diagnostics) = limited(source, ParserOptions::native(), ParseLimits::default().with_max_nodes(3));
+    assert_eq!(markup, "<p>a</p><p></p>");
+    assert_eq!(diagnostics, warning("Exceeded the node count limit of 3; truncated"));
+}
+
+#[test]
+fn attributes_and_text_are_cut_short() {
+    let source = "<p a=1 b=\"éé\" c=3>abcdef</p>";
+    let limits = ParseLimits::default().with_max_attributes(2).with_max_attribute_length(3).with_max_text_length(4);
+    for options in [ParserOptions::native(), ParserOptions::html5()] {
+        let (markup, diagnostics) = limited(source, options, limits.clone());
+        assert_eq!(markup, "<p a=\"1\" b=\"é\">abcd</p>");
+        let messages = diagnostics.iter().map(|(_, message)| message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, [
+            "Exceeded the attributes per element limit of 2; truncated",
+            "Exceeded the attribute length limit of 3; truncated",
+            "Exceeded the text length limit of 4; truncated",
+        ]);
+    }
+}
+
+#[test]
+fn failing_stops_parsing_with_an_error() {
+    let source = "<a><b>x</b></a><p>after</p>";
+    let limits = ParseLimits::default().with_max_depth(1).with_action(LimitAction::Fail);
+    let (markup, diagnostics) = limited(source, ParserOptions::native(), limits);
+    assert_eq!(markup, "<a></a>");
+    assert_eq!(diagnostics, error("Exceeded the nesting depth limit of 1; parsing stopped"));
+}
+
+#[test]
+fn oversized_input_is_cut_or_refused() {
+    let source = "<p>hello</p>";
+    let limits = ParseLimits::default().with_max_input_bytes(5);
+    let (markup, diagnostics) = limited(source, ParserOptions::native(), limits.clone());
+    assert_eq!(markup, "<p>he</p>");
+    assert_eq!(diagnostics, warning("Exceeded the input size limit of 5; truncated"));
+    let (markup, diagnostics) = limited(source, ParserOptions::native(), limits.with_action(LimitAction::Fail));
+    assert_eq!(markup, "");
+    assert_eq!(diagnostics, error("Exceeded the input size limit of 5; parsing stopped"));
+}
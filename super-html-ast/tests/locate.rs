@@ -0,0 +1,60 @@
+use super_html_ast::{LineIndex, Node};
+use super_html_ast::parser::{parse_document_with, parse_fragment_with, parse_from_fragment, ParserOptions};
+
+/// The source text of every node with a span, in document order.
+fn located<'a>(tree: &Node, source: &'a str) -> Vec<&'a str> {
+    tree.iter_with_paths()
+        .filter_map(|(_, node)| node.span())
+        .map(|span| span.slice(source).unwrap())
+        .collect()
+}
+
+#[test]
+fn html5_spans_are_opt_in() {
+    let source = "<p>a<b>b</b></p>";
+    let plain = parse_fragment_with(source, &ParserOptions::html5()).unwrap_unchecked();
+    assert!(located(&plain, source).is_empty());
+    let options = ParserOptions::html5().with_locate_spans(true);
+    let tree = parse_fragment_with(source, &options).unwrap_unchecked();
+    assert_eq!(located(&tree, source), ["<p>a<b>b</b></p>", "a", "<b>b</b>", "b"]);
+    // The native parser records spans regardless.
+    let native = parse_fragment_with(source, &ParserOptions::native()).unwrap_unchecked();
+    assert_eq!(located(&native, source), located(&tree, source));
+}
+
+#[test]
+fn default_entry_points_locate_spans() {
+    let tree = parse_from_fragment("<p>hi</p>").unwrap_unchecked();
+    assert_eq!(located(&tree, "<p>hi</p>"), ["<p>hi</p>", "hi"]);
+}
+
+#[test]
+fn text_between_many_comments_is_located() {
+    let source = "a<!--c-->".repeat(20_000);
+    let tree = parse_from_fragment(&source).unwrap_unchecked();
+    let located = located(&tree, &source);
+    assert_eq!(located.len(), 20_000);
+    assert!(located.iter().all(|x| *x == "a"));
+}
+
+#[test]
+fn stray_end_tags_do_not_shift_spans() {
+    let source = format!("<div>{}<p>z</p></div>", "</x>".repeat(5000));
+    let options = ParserOptions::html5().with_locate_spans(true);
+    let tree = parse_fragment_with(&source, &options).unwrap_unchecked();
+    assert_eq!(located(&tree, &source)[1..], ["<p>z</p>", "z"]);
+}
+
+#[test]
+fn long_lines_get_character_columns() {
+    let source = format!("<p>{}</p><b>x</b>", "é".repeat(5000));
+    let options = ParserOptions::html5().with_locate_spans(true);
+    let tree = parse_document_with(&source, &options).unwrap_unchecked();
+    let bold = tree.iter_with_paths().filter_map(|(_, node)| node.as_element()).last().unwrap();
+    let span = bold.span.unwrap();
+    assert_eq!((span.start.line, span.start.column), (1, 5008));
+    assert_eq!(span.start, LineIndex::new(&source).position(span.start.offset));
+    let index = LineIndex::new("ab\nçdé\u{1F600}f");
+    assert_eq!(index.position(12).column, 5);
+    assert_eq!(index.position(13).column, 6);
+}
@@ -0,0 +1,49 @@
+use super_html_ast::{AttributeValueBuf, Node};
+use super_html_ast::parser::{parse_document_with, ParserOptions};
+
+const SOURCE: &str = "<div   class='a' >x &amp; y<br/></div ><!DOCTYPE  html>";
+
+fn parse() -> Node {
+    parse_document_with(SOURCE, &ParserOptions::native().with_lossless(true)).unwrap_unchecked()
+}
+
+#[test]
+fn unmodified_nodes_keep_their_source_text() {
+    assert_eq!(parse().format_lossless(), SOURCE);
+}
+
+#[test]
+fn source_text_survives_the_binary_encoding() {
+    let decoded = Node::from_bytes(&parse().to_bytes()).unwrap();
+    assert_eq!(decoded.format_lossless(), SOURCE);
+}
+
+#[test]
+fn modified_nodes_are_written_fresh() {
+    let mut tree = parse();
+    let Node::Fragment(fragment) = &mut tree else { panic!("{tree:?}") };
+    let Some(Node::Element(div)) = fragment.iter_mut().next() else { panic!() };
+    div.attributes.insert("class", AttributeValueBuf::literal("b"));
+    let output = tree.format_lossless();
+    assert!(output.starts_with("<div class=\"b\">x &amp; y<br/></div >"), "{output}");
+}
+
+#[test]
+fn html5_keeps_the_source_text_of_what_it_locates() {
+    let source = "<!DOCTYPE  html><html><head><title>t</title></head>\n<body  id=b><div   class='a' >x &amp; y<br/></div >\n<svg><path d='M0'/></svg></body></html>";
+    let options = ParserOptions::html5().with_lossless(true);
+    let tree = parse_document_with(source, &options).unwrap_unchecked();
+    assert_eq!(tree.format_lossless(), source);
+}
+
+#[test]
+fn html5_writes_implied_end_tags_fresh() {
+    let source = "<b  id=x>1<p>2</b>3</p><ul><li  class=a>x<li>y</ul>";
+    let options = ParserOptions::html5().with_lossless(true);
+    let tree = parse_document_with(source, &options).unwrap_unchecked();
+    let output = tree.format_lossless();
+    assert!(output.contains("<b id=\"x\">1</b>"), "{output}");
+    assert!(output.contains("<ul><li class=\"a\">x</li><li>y</li></ul>"), "{output}");
+    let reparsed = parse_document_with(&output, &options).unwrap_unchecked();
+    assert_eq!(reparsed.format_lossless(), output);
+}
@@ -0,0 +1,74 @@
+use super_html_ast::Node;
+use super_html_ast::constants::{HTML_NAMESPACE, MATHML_NAMESPACE, SVG_NAMESPACE, XLINK_NAMESPACE, XML_NAMESPACE};
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_fragment_with, ParserOptions};
+
+const SOURCE: &str = concat!(
+    "<svg viewBox=\"0 0 1 1\"><use xlink:href=\"#a\" xml:lang=\"en\"/>",
+    "<foreignObject><p>x</p></foreignObject></svg>",
+    "<math><mi>y</mi></math><div></div>",
+);
+
+/// Every element's original name and namespace, in document order.
+fn tags(tree: &Node) -> Vec<(&str, Option<&str>)> {
+    tree.iter_with_paths()
+        .filter_map(|(_, node)| node.as_element())
+        .map(|element| (element.tag.as_original(), element.tag.namespace()))
+        .collect()
+}
+
+#[test]
+fn foreign_content_gets_its_namespace() {
+    for options in [ParserOptions::native(), ParserOptions::html5()] {
+        let tree = parse_fragment_with(SOURCE, &options).unwrap_unchecked();
+        assert_eq!(tags(&tree), [
+            ("svg", Some(SVG_NAMESPACE)),
+            ("use", Some(SVG_NAMESPACE)),
+            ("foreignObject", Some(SVG_NAMESPACE)),
+            // `<foreignObject>` is an HTML integration point.
+            ("p", Some(HTML_NAMESPACE)),
+            ("math", Some(MATHML_NAMESPACE)),
+            ("mi", Some(MATHML_NAMESPACE)),
+            ("div", Some(HTML_NAMESPACE)),
+        ], "{options:?}");
+    }
+}
+
+#[test]
+fn foreign_attributes_keep_their_prefix_and_namespace() {
+    for options in [ParserOptions::native(), ParserOptions::html5()] {
+        let tree = parse_fragment_with(SOURCE, &options).unwrap_unchecked();
+        let usage = tree
+            .iter_with_paths()
+            .filter_map(|(_, node)| node.as_element())
+            .find(|element| element.tag.local_name() == "use")
+            .unwrap();
+        let keys = usage.attributes
+            .iter()
+            .map(|(key, _)| (key.as_str(), key.prefix(), key.local_name(), key.namespace()))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [
+            ("xlink:href", Some("xlink"), "href", Some(XLINK_NAMESPACE)),
+            ("xml:lang", Some("xml"), "lang", Some(XML_NAMESPACE)),
+        ], "{options:?}");
+        let formatted = tree.format(FormatSettings::default());
+        assert!(formatted.contains("<use xlink:href=\"#a\" xml:lang=\"en\" />"), "{formatted}");
+        assert!(formatted.contains("<foreignObject>"), "{formatted}");
+    }
+}
+
+#[test]
+fn svg_and_html_elements_of_the_same_name_do_not_match() {
+    let tree = parse_fragment_with("<a></a><svg><a></a></svg>", &ParserOptions::native()).unwrap_unchecked();
+    let anchors = tree
+        .iter_with_paths()
+        .filter_map(|(_, node)| node.as_element())
+        .filter(|element| element.tag.local_name() == "a")
+        .map(|element| &element.tag)
+        .collect::<Vec<_>>();
+    assert_eq!(anchors.len(), 2);
+    assert_eq!(anchors[0].namespace(), Some(HTML_NAMESPACE));
+    assert_eq!(anchors[1].namespace(), Some(SVG_NAMESPACE));
+    assert!(!anchors[0].matches(anchors[1]));
+    assert_ne!(anchors[0], anchors[1]);
+}
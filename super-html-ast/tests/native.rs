@@ -0,0 +1,35 @@
+use super_html_ast::parser::{parse_fragment_with, DiagnosticCode, ParseLimits, ParserOptions};
+
+fn duplicates(source: &str, options: &ParserOptions) -> (usize, usize) {
+    let result = parse_fragment_with(source, options);
+    let reported = result.diagnostics().iter().filter(|x| x.code == DiagnosticCode::DuplicateAttribute).count();
+    let tree = result.unwrap_unchecked();
+    let element = tree.as_fragment().and_then(|x| x.get(0)).and_then(|x| x.as_element()).unwrap();
+    (element.attributes.len(), reported)
+}
+
+#[test]
+fn html_attribute_names_are_duplicates_regardless_of_case() {
+    let source = "<p id=a ID=b Id=c class=x>";
+    assert_eq!(duplicates(source, &ParserOptions::native()), (2, 2));
+    assert_eq!(duplicates(source, &ParserOptions::xml()), (4, 0));
+    assert_eq!(duplicates("<p a='1' a='2'/>", &ParserOptions::xml()), (1, 1));
+}
+
+#[test]
+fn many_attributes_are_checked_in_linear_time() {
+    let attributes = (0..50_000).map(|ix| format!(" a{ix}=x")).collect::<String>();
+    let source = format!("<p{attributes} A0=y>");
+    assert_eq!(duplicates(&source, &ParserOptions::native()), (50_000, 1));
+}
+
+#[test]
+fn attributes_past_the_limit_are_not_read_into_the_tag() {
+    let attributes = (0..100_000).map(|ix| format!(" a{ix}=x a{ix}=y")).collect::<String>();
+    let source = format!("<p a0=z{attributes}>");
+    let options = ParserOptions::native().with_lossless(true).with_limits(ParseLimits::default().with_max_attributes(2));
+    assert_eq!(duplicates(&source, &options), (2, 2));
+    let result = parse_fragment_with(&source, &options);
+    assert_eq!(result.diagnostics().iter().filter(|x| x.code == DiagnosticCode::LimitExceeded).count(), 1);
+    assert_eq!(result.unwrap_unchecked().format_lossless(), "<p a0=\"z\" a1=\"x\"></p>");
+}
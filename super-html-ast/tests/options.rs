@@ -0,0 +1,42 @@
+use super_html_ast::Node;
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_document_with, parse_fragment_with, DiagnosticCode, ParseDiagnostic, ParserOptions, Severity};
+
+fn warnings(diagnostics: &[ParseDiagnostic]) -> Vec<String> {
+    diagnostics
+        .iter()
+        .filter(|x| x.code == DiagnosticCode::UnsupportedOption)
+        .inspect(|x| assert_eq!(x.severity, Severity::Warning))
+        .map(|x| x.message.clone())
+        .collect()
+}
+
+/// The warnings about ignored options, the same for documents and fragments.
+fn unsupported(options: &ParserOptions) -> Vec<String> {
+    let document = parse_document_with("<p>a &amp; b</p>", options);
+    let fragment = parse_fragment_with("<p>a &amp; b</p>", options);
+    assert_eq!(warnings(document.diagnostics()), warnings(fragment.diagnostics()));
+    warnings(document.diagnostics())
+}
+
+#[test]
+fn html5_supports_lossless_mode() {
+    assert!(unsupported(&ParserOptions::html5().with_lossless(true)).is_empty());
+    assert!(unsupported(&ParserOptions::native().with_lossless(true)).is_empty());
+    assert!(unsupported(&ParserOptions::xml().with_lossless(true)).is_empty());
+    assert!(unsupported(&ParserOptions::html5()).is_empty());
+}
+
+#[test]
+fn html5_keeps_character_references() {
+    assert!(unsupported(&ParserOptions::html5().with_character_references(true)).is_empty());
+    assert!(unsupported(&ParserOptions::native().with_character_references(true)).is_empty());
+    let source = "<p title=\"a&nbsp;b\">soft&shy;hyphen &#8203;</p>";
+    let options = ParserOptions::html5().with_character_references(true);
+    let tree = parse_fragment_with(source, &options).unwrap_unchecked();
+    assert_eq!(tree.format(FormatSettings::default()), source);
+    let paragraph = tree.as_fragment().unwrap()[0].as_element().unwrap();
+    let Node::Text(text) = &paragraph.children[0] else { panic!("{paragraph:?}") };
+    let references = text.references.iter().map(|x| x.source.as_str()).collect::<Vec<_>>();
+    assert_eq!(references, ["&shy;", "&#8203;"]);
+}
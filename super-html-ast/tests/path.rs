@@ -0,0 +1,54 @@
+use super_html_ast::{Node, NodePath};
+use super_html_ast::parser::{parse_fragment_with, ParserOptions};
+
+fn tree() -> Node {
+    parse_fragment_with("<ul><li>a</li><li>b<i>c</i></li></ul>d", &ParserOptions::native()).unwrap_unchecked()
+}
+
+#[test]
+fn paths_lead_to_nodes() {
+    let mut tree = tree();
+    let path = "/0/1/1/0".parse::<NodePath>().unwrap();
+    assert_eq!(tree.get_by_path(&path).and_then(Node::as_text), Some("c"));
+    assert_eq!(tree.get_by_path([1]).and_then(Node::as_text), Some("d"));
+    assert!(tree.get_by_path(NodePath::root()).is_some_and(|x| x.as_fragment().is_some()));
+    // Past the children, or into a node that has none.
+    assert!(tree.get_by_path([2]).is_none());
+    assert!(tree.get_by_path([1, 0]).is_none());
+    *tree.get_by_path_mut(&path).unwrap() = Node::text("e");
+    assert_eq!(tree.get_by_path(&path).and_then(Node::as_text), Some("e"));
+}
+
+#[test]
+fn paths_are_listed_in_document_order() {
+    let tree = tree();
+    let paths = tree.iter_with_paths().map(|(path, _)| path.to_string()).collect::<Vec<_>>();
+    assert_eq!(paths, ["/", "/0", "/0/0", "/0/0/0", "/0/1", "/0/1/0", "/0/1/1", "/0/1/1/0", "/1"]);
+    for (path, node) in tree.iter_with_paths() {
+        assert!(std::ptr::eq(tree.get_by_path(&path).unwrap(), node), "{path}");
+    }
+    let mut sorted = tree.iter_with_paths().map(|(path, _)| path).collect::<Vec<_>>();
+    sorted.sort();
+    assert_eq!(sorted, tree.iter_with_paths().map(|(path, _)| path).collect::<Vec<_>>());
+}
+
+#[test]
+fn paths_parse_back_from_their_display() {
+    let path = NodePath::from(vec![3, 0, 12]);
+    assert_eq!(path.to_string(), "/3/0/12");
+    assert_eq!("/3/0/12".parse::<NodePath>().unwrap(), path);
+    assert_eq!(NodePath::root().to_string(), "/");
+    assert_eq!(path.parent(), Some(NodePath::from(vec![3, 0])));
+    assert_eq!(path.parent().unwrap().child(12), path);
+    assert!(NodePath::from(vec![3]).is_ancestor_of(&path));
+    assert!(!path.is_ancestor_of(&NodePath::from(vec![3])));
+    let errors = ["", "3/0", "/3//0", "/x", "/99999999999999999999999"]
+        .map(|x| x.parse::<NodePath>().unwrap_err().to_string());
+    assert_eq!(errors, [
+        "Expected `/` (at byte 0)",
+        "Expected `/` (at byte 0)",
+        "Expected a child index (at byte 3)",
+        "Expected a child index (at byte 1)",
+        "Child index out of range (at byte 1)",
+    ]);
+}
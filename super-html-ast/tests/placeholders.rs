@@ -0,0 +1,21 @@
+use super_html_ast::Node;
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_fragment_with, ParserOptions, PlaceholderSyntax};
+
+#[test]
+fn placeholders_in_text_are_written_verbatim() {
+    let settings = FormatSettings::default().with_placeholders(PlaceholderSyntax::default());
+    let text = Node::text("a < {{ b < c }} {% d");
+    assert_eq!(text.format(settings), "a &lt; {{ b < c }} {% d");
+}
+
+#[test]
+fn unterminated_placeholders_are_read_over_once() {
+    let source = format!("<p title=\"{}\">{}</p>", "{{ ".repeat(40_000), "{% {{ ".repeat(40_000));
+    let options = ParserOptions::native().with_placeholders(PlaceholderSyntax::default());
+    let tree = parse_fragment_with(&source, &options).unwrap_unchecked();
+    let settings = FormatSettings::default().with_placeholders(PlaceholderSyntax::default());
+    assert_eq!(tree.format(settings.clone()), source);
+    let text = Node::text("{{ ".repeat(40_000));
+    assert_eq!(text.format(settings), "{{ ".repeat(40_000));
+}
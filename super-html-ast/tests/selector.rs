@@ -0,0 +1,48 @@
+use super_html_ast::{Node, Selector, StartTag};
+use super_html_ast::parser::{parse_document_with, ParserOptions};
+use super_html_ast::rewriter::StreamRewriter;
+
+fn count(selector: &str, source: &str) -> usize {
+    let tree = parse_document_with(source, &ParserOptions::native()).unwrap_unchecked();
+    tree.select_paths(&Selector::parse(selector).unwrap()).len()
+}
+
+#[test]
+fn child_combinators_backtrack_to_outer_ancestors() {
+    let source = "<a><b><c><b><d></d></b></c></b></a>";
+    assert_eq!(count("a > b d", source), 1);
+    assert_eq!(count("a > c d", source), 0);
+    assert_eq!(count("b > c > b > d", source), 1);
+    assert_eq!(count("a b c b d", source), 1);
+    assert_eq!(count("a b c b b d", source), 0);
+}
+
+#[test]
+fn descendant_chains_in_deep_trees() {
+    let source = "<div>".repeat(300);
+    assert_eq!(count("x div div div div", &source), 0);
+    assert_eq!(count("div div div div", &source), 297);
+    assert_eq!(count("div > div div > div div", &source), 296);
+}
+
+#[test]
+fn borrowed_trees_match_like_owned_trees() {
+    let source = format!("<p>{}</p>", "<i><b>".repeat(100));
+    let selector = Selector::parse("p i b i b i > b").unwrap();
+    let owned = parse_document_with(&source, &ParserOptions::native()).unwrap_unchecked();
+    let borrowed = super_html_ast::parser::parse_borrowed(&source, &ParserOptions::native()).unwrap_unchecked();
+    assert_eq!(owned.select_paths(&selector), borrowed.select_paths(&selector));
+    assert_eq!(owned.select_paths(&selector).len(), 98);
+}
+
+#[test]
+fn wrappers_go_inside_inserted_siblings() {
+    let rewriter = StreamRewriter::new(Vec::new(), &ParserOptions::native())
+        .on_element(Selector::parse("a").unwrap(), |edit| {
+            edit.before(Node::text("B"));
+            edit.after(Node::text("A"));
+            edit.wrap(StartTag::new("span"));
+        });
+    let output = rewriter.rewrite("<a href=\"x\">l</a>".as_bytes()).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "B<span><a href=\"x\">l</a></span>A");
+}
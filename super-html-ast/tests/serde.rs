@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+use serde::Deserialize;
+use super_html_ast::{AttributeMap, Element, Node};
+use super_html_ast::parser::{parse_document_with, parse_fragment_with, ParserOptions, PlaceholderSyntax};
+
+/// Serializes `tree`, reads it back and checks that nothing was lost.
+fn round_trip(tree: &Node) -> String {
+    let json = serde_json::to_string(tree).unwrap();
+    let read = serde_json::from_str::<Node>(&json).unwrap();
+    assert_eq!(&read, tree, "{json}");
+    // Spans, lossless text and namespaces are not all seen by `==`.
+    assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    json
+}
+
+const SOURCE: &str = "<!DOCTYPE html><p class=x id='a&amp;b'>caf&eacute; {{ name }}<br>\
+    <svg viewBox='0 0 1 1'><rect/><foreignObject><b>x</b></foreignObject></svg>\
+    <template><i>t</i></template><!-- c --><math><mi>x</mi></math></p>";
+
+#[test]
+fn parsed_trees_round_trip() {
+    let options = [
+        ParserOptions::html5(),
+        ParserOptions::native().with_lossless(true).with_character_references(true),
+        ParserOptions::native().with_placeholders(PlaceholderSyntax::default()),
+    ];
+    for options in options.iter() {
+        round_trip(&parse_document_with(SOURCE, options).unwrap_unchecked());
+        round_trip(&parse_fragment_with(SOURCE, options).unwrap_unchecked());
+    }
+    let xml = "<?xml version='1.0'?><a xmlns='urn:a' xmlns:b='urn:b'><b:c b:d='1'><![CDATA[<>]]></b:c><e xmlns=''/></a>";
+    round_trip(&parse_document_with(xml, &ParserOptions::xml().with_lossless(true)).unwrap_unchecked());
+}
+
+#[test]
+fn html_namespace_and_cleared_spans_are_left_out() {
+    let mut tree = parse_fragment_with("<p class=x>a<svg><rect/></svg></p>", &ParserOptions::html5()).unwrap_unchecked();
+    tree.clear_spans();
+    let svg = r#"{"name":"svg","namespace":"http://www.w3.org/2000/svg"}"#;
+    assert_eq!(round_trip(&tree), format!(r#"{{"fragment":[["p",{{"class":"x"}},"a",[{svg},{{}},["rect",{{}}]]]]}}"#));
+}
+
+#[test]
+fn elements_without_a_namespace_say_so_once() {
+    let b = Node::element("b", AttributeMap::default(), vec![Node::text("b")]);
+    let built = Node::element("p", AttributeMap::default(), vec![Node::text("a"), b]);
+    assert_eq!(round_trip(&built), r#"[{"name":"p","namespace":null},{},"a",["b",{},"b"]]"#);
+    let element = serde_json::from_str::<Element>(r#"["p",{}]"#).unwrap();
+    assert_eq!(element.tag.namespace(), Some("http://www.w3.org/1999/xhtml"));
+}
+
+#[test]
+fn deep_trees_need_the_recursion_limit_lifted() {
+    // Unoptimized builds take several kilobytes of stack per level.
+    let test = std::thread::Builder::new().stack_size(32 << 20).spawn(|| {
+        let mut tree = Node::text("x");
+        for _ in 0..300 {
+            tree = Node::Element(Element::new("div").with_children(vec![tree]));
+        }
+        let json = serde_json::to_string(&tree).unwrap();
+        let error = serde_json::from_str::<Node>(&json).unwrap_err();
+        assert!(error.to_string().contains("recursion limit"), "{error}");
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        deserializer.disable_recursion_limit();
+        assert_eq!(Node::deserialize(&mut deserializer).unwrap(), tree);
+    });
+    test.unwrap().join().unwrap();
+}
@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+use super_html_ast::{Atom, TagBuf};
+use super_html_ast::constants::{HTML_NAMESPACE, SVG_NAMESPACE};
+
+fn tags() -> Vec<TagBuf> {
+    let svg = || Some(Atom::new(SVG_NAMESPACE));
+    let html = || Some(Atom::new(HTML_NAMESPACE));
+    vec![
+        TagBuf::new("rect"),
+        TagBuf::new("RECT"),
+        TagBuf::new("svg:rect"),
+        TagBuf::new("rect").with_namespace(svg()),
+        TagBuf::new("svg:rect").with_namespace(svg()),
+        TagBuf::new("s:Rect").with_namespace(svg()),
+        TagBuf::new("rect").with_namespace(html()),
+        TagBuf::new("a"),
+        TagBuf::new("a").with_namespace(html()),
+        TagBuf::new("a").with_namespace(svg()),
+    ]
+}
+
+#[test]
+fn equal_tags_match() {
+    for left in tags().iter() {
+        for right in tags().iter() {
+            if left == right {
+                assert!(left.matches(right), "{left:?} {right:?}");
+                assert_eq!(left.cmp(right), std::cmp::Ordering::Equal);
+            }
+        }
+    }
+}
+
+#[test]
+fn prefixes_are_ignored_once_the_namespace_is_known() {
+    let tags = tags();
+    assert_eq!(tags[3], tags[4]);
+    assert_eq!(tags[3], tags[5]);
+    assert_ne!(tags[0], tags[2]);
+    assert_ne!(tags[3], tags[6]);
+    assert_ne!(tags[0], tags[3]);
+    assert!(tags[0].matches(&tags[3]));
+}
+
+#[test]
+fn hashing_agrees_with_equality() {
+    let unique = tags().into_iter().collect::<HashSet<_>>();
+    // rect/RECT, svg:rect, the three SVG rects, the HTML rect and the three `a`s.
+    assert_eq!(unique.len(), 7);
+    let mut sorted = tags();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted.len(), 7);
+}
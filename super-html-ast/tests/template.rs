@@ -0,0 +1,15 @@
+use super_html_ast::{Atom, Element, Node, TagBuf};
+use super_html_ast::parser::{parse_document_with, ParserOptions};
+
+#[test]
+fn template_built_by_hand_holds_template_contents() {
+    assert!(Element::new("template").template_contents);
+    assert!(!Element::new(TagBuf::new("template").with_namespace(Atom::new("urn:x"))).template_contents);
+}
+
+#[test]
+fn template_without_namespace_in_xml_is_plain() {
+    let tree = parse_document_with("<template><a/></template>", &ParserOptions::xml()).unwrap_unchecked();
+    let Node::Fragment(fragment) = tree else { panic!("expected a fragment") };
+    assert!(!fragment.get(0).unwrap().as_element().unwrap().template_contents);
+}
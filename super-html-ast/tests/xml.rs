@@ -0,0 +1,79 @@
+use super_html_ast::{Element, Node};
+use super_html_ast::format::FormatSettings;
+use super_html_ast::parser::{parse_from_xml, DiagnosticCode, ParseResult};
+
+fn codes(result: &ParseResult<Node>) -> Vec<DiagnosticCode> {
+    result.diagnostics().iter().map(|x| x.code).collect()
+}
+
+fn root(tree: &Node) -> &Element {
+    tree.as_fragment().unwrap().iter().find_map(Node::as_element).unwrap()
+}
+
+#[test]
+fn prefixes_resolve_to_the_namespaces_in_scope() {
+    let source = "<r xmlns=\"urn:d\" xmlns:a=\"urn:a\"><a:x a:y=\"1\" z=\"2\"><w xmlns=\"\"/></a:x></r>";
+    let result = parse_from_xml(source);
+    assert!(result.diagnostics().is_empty(), "{:?}", result.diagnostics());
+    let tree = result.unwrap_unchecked();
+    let r = root(&tree);
+    assert_eq!(r.tag.namespace(), Some("urn:d"));
+    let x = r.children[0].as_element().unwrap();
+    assert_eq!((x.tag.prefix(), x.tag.local_name(), x.tag.namespace()), (Some("a"), "x", Some("urn:a")));
+    let attributes = x.attributes.iter().map(|(key, _)| (key.as_str(), key.namespace())).collect::<Vec<_>>();
+    // Unprefixed attributes are in no namespace, whatever the default.
+    assert_eq!(attributes, [("a:y", Some("urn:a")), ("z", None)]);
+    let w = x.children[0].as_element().unwrap();
+    assert_eq!(w.tag.namespace(), None);
+}
+
+#[test]
+fn undeclared_prefixes_are_reported() {
+    let result = parse_from_xml("<r><p:x q:y=\"1\"/></r>");
+    assert_eq!(codes(&result), [DiagnosticCode::UndeclaredNamespacePrefix; 2]);
+    let tree = result.unwrap_unchecked();
+    let x = root(&tree).children[0].as_element().unwrap();
+    assert_eq!((x.tag.as_original(), x.tag.namespace()), ("p:x", None));
+}
+
+#[test]
+fn cdata_sections_stay_cdata() {
+    let tree = parse_from_xml("<r>a &amp; <![CDATA[<b> & c]]></r>").unwrap_unchecked();
+    let children = root(&tree).children.iter().map(|x| match x {
+        Node::Text(text) => (text.value.as_str(), text.cdata),
+        node => panic!("{node:?}"),
+    });
+    assert_eq!(children.collect::<Vec<_>>(), [("a & ", false), ("<b> & c", true)]);
+    assert_eq!(tree.format(FormatSettings::default()), "<r>a &amp; <![CDATA[<b> & c]]></r>");
+}
+
+#[test]
+fn any_element_closes_itself_and_names_keep_their_case() {
+    let result = parse_from_xml("<Root><div/><Item/>tail</Root>");
+    assert!(result.diagnostics().is_empty(), "{:?}", result.diagnostics());
+    let tree = result.unwrap_unchecked();
+    let r = root(&tree);
+    assert_eq!(r.tag.as_original(), "Root");
+    let children = r.children.iter().map(|x| match x {
+        Node::Element(element) => (element.tag.as_original(), element.children.len()),
+        Node::Text(text) => (text.value.as_str(), 0),
+        node => panic!("{node:?}"),
+    });
+    assert_eq!(children.collect::<Vec<_>>(), [("div", 0), ("Item", 0), ("tail", 0)]);
+}
+
+#[test]
+fn well_formedness_errors_are_reported() {
+    let cases = [
+        ("<a><b></a>", vec![DiagnosticCode::MisnestedTags]),
+        ("<a></b></a>", vec![DiagnosticCode::UnmatchedEndTag]),
+        ("<a>", vec![DiagnosticCode::UnclosedElement]),
+        ("", vec![DiagnosticCode::UnexpectedEof]),
+        ("<a/>text", vec![DiagnosticCode::UnexpectedToken]),
+        ("<a x=\"1\" x=\"2\"/>", vec![DiagnosticCode::DuplicateAttribute]),
+        ("<R></r></R>", vec![DiagnosticCode::UnmatchedEndTag]),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(codes(&parse_from_xml(source)), expected, "{source:?}");
+    }
+}